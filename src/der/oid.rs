@@ -33,6 +33,15 @@ pub(crate) static OID_SECP521R1: Lazy<ObjectIdentifier> =
 pub(crate) static OID_SECP256K1: Lazy<ObjectIdentifier> =
     Lazy::new(|| ObjectIdentifier::from_slice(&[1, 3, 132, 0, 10]));
 
+pub(crate) static OID_BRAINPOOL_P256R1: Lazy<ObjectIdentifier> =
+    Lazy::new(|| ObjectIdentifier::from_slice(&[1, 3, 36, 3, 3, 2, 8, 1, 1, 7]));
+
+pub(crate) static OID_BRAINPOOL_P384R1: Lazy<ObjectIdentifier> =
+    Lazy::new(|| ObjectIdentifier::from_slice(&[1, 3, 36, 3, 3, 2, 8, 1, 1, 11]));
+
+pub(crate) static OID_BRAINPOOL_P512R1: Lazy<ObjectIdentifier> =
+    Lazy::new(|| ObjectIdentifier::from_slice(&[1, 3, 36, 3, 3, 2, 8, 1, 1, 13]));
+
 pub(crate) static OID_X25519: Lazy<ObjectIdentifier> =
     Lazy::new(|| ObjectIdentifier::from_slice(&[1, 3, 101, 110]));
 
@@ -1,9 +1,90 @@
 mod error;
 
+use crate::util;
 use serde_json::{Map, Value};
 use std::fmt::Display;
 
-pub use crate::jose::error::JoseError;
+pub use crate::jose::error::{JoseError, JoseErrorKind};
+
+/// Header claim names shared by JWS and JWE that do not depend on the algorithm.
+pub(crate) const COMMON_HEADER_CLAIMS: [&str; 9] = [
+    "jku", "jwk", "kid", "x5u", "x5c", "x5t", "x5t#S256", "typ", "cty",
+];
+
+/// The base64 alphabet used for the segments of compact serialization.
+///
+/// RFC 7515 and RFC 7516 require `UrlSafeNoPad`. Other alphabets produce tokens that are
+/// NOT conformant and should only be used to interoperate with non-standard consumers.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Base64Alphabet {
+    /// URL-safe alphabet without padding (base64url). This is the default.
+    UrlSafeNoPad,
+    /// Standard alphabet with padding. This is not allowed by the RFCs.
+    Standard,
+}
+
+impl Base64Alphabet {
+    pub(crate) fn config(&self) -> base64::Config {
+        match self {
+            Self::UrlSafeNoPad => base64::URL_SAFE_NO_PAD,
+            Self::Standard => base64::STANDARD,
+        }
+    }
+
+    pub(crate) fn encoded_len(&self, len: usize) -> usize {
+        match self {
+            Self::UrlSafeNoPad => util::ceiling(len * 4, 3),
+            Self::Standard => util::ceiling(len, 3) * 4,
+        }
+    }
+}
+
+/// The kind of a JOSE token that is detected by `classify`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum TokenKind {
+    /// A JWS by compact serialization (three segments).
+    Jws,
+    /// A JWE by compact serialization (five segments).
+    JweCompact,
+    /// A JWS by flattened or general json serialization.
+    JwsJson,
+    /// A JWE by flattened or general json serialization.
+    JweJson,
+    /// Anything else.
+    Unknown,
+}
+
+/// Return the kind of a JOSE token by its shape.
+///
+/// This only counts the segments of compact serialization, or looks for the top-level member
+/// names of json serialization. It doesn't decode the segments or verify the token, so a result
+/// other than `Unknown` does not mean the token is valid.
+///
+/// # Arguments
+///
+/// * `input` - a token string representation.
+pub fn classify(input: &str) -> TokenKind {
+    let input = input.trim();
+    if input.starts_with('{') {
+        let map: Map<String, Value> = match serde_json::from_str(input) {
+            Ok(val) => val,
+            Err(_) => return TokenKind::Unknown,
+        };
+        if map.contains_key("ciphertext") {
+            TokenKind::JweJson
+        } else if map.contains_key("signature") || map.contains_key("signatures") {
+            TokenKind::JwsJson
+        } else {
+            TokenKind::Unknown
+        }
+    } else {
+        match input.bytes().filter(|c| *c == b'.').count() {
+            2 => TokenKind::Jws,
+            4 => TokenKind::JweCompact,
+            _ => TokenKind::Unknown,
+        }
+    }
+}
 
 pub trait JoseHeader: Display + Send + Sync {
     /// Return the value for algorithm header claim (alg).
@@ -42,3 +123,52 @@ impl Clone for Box<dyn JoseHeader> {
         self.box_clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::jose::{self, TokenKind};
+    use crate::jwe::{self, Dir, JweHeader};
+    use crate::jws::{self, JwsHeader, HS256};
+    use crate::util;
+
+    #[test]
+    fn test_classify() -> Result<()> {
+        let key = util::rand_bytes(32);
+        let signer = HS256.signer_from_slice(&key)?;
+        let encrypter = Dir.encrypter_from_slice(&key)?;
+
+        let header = JwsHeader::new();
+        let input = jws::serialize_compact(b"payload", &header, &signer)?;
+        assert_eq!(jose::classify(&input), TokenKind::Jws);
+        let input = jws::serialize_flattened_json(b"payload", Some(&header), None, &signer)?;
+        assert_eq!(jose::classify(&input), TokenKind::JwsJson);
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128CBC-HS256");
+        let input = jwe::serialize_compact(b"payload", &header, &encrypter)?;
+        assert_eq!(jose::classify(&input), TokenKind::JweCompact);
+        let input =
+            jwe::serialize_flattened_json(b"payload", Some(&header), None, None, None, &encrypter)?;
+        assert_eq!(jose::classify(&input), TokenKind::JweJson);
+
+        let input = "{\"header\":{\"ciphertext\":1},\"payload\":\"\",\"signature\":\"\"}";
+        assert_eq!(jose::classify(input), TokenKind::JwsJson);
+
+        for input in &[
+            "",
+            "abc",
+            "a.b",
+            "a.b.c.d",
+            "{\"payload\":\"\"}",
+            "{\"payload\":\"\\\"ciphertext\\\"\"}",
+            "{\"ciphertext\"",
+            "[]",
+        ] {
+            assert_eq!(jose::classify(input), TokenKind::Unknown);
+        }
+
+        Ok(())
+    }
+}
@@ -23,9 +23,143 @@ pub enum JoseError {
     #[error("Invalid json: {0}")]
     InvalidJson(#[source] anyhow::Error),
 
+    #[error("Invalid JWT payload: {0}")]
+    InvalidJwtPayload(#[source] anyhow::Error),
+
     #[error("Invalid claim: {0}")]
     InvalidClaim(#[source] anyhow::Error),
 
     #[error("Invalid signature: {0}")]
     InvalidSignature(#[source] anyhow::Error),
+
+    #[error("Algorithm mismatch: the verifier expects {expected}, but the token uses {actual}")]
+    AlgorithmMismatch { expected: String, actual: String },
+}
+
+/// The kind of a `JoseError`, for branching without matching on the wrapped cause.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum JoseErrorKind {
+    UnsupportedSignatureAlgorithm,
+    InvalidJwtFormat,
+    InvalidJwkFormat,
+    InvalidJwsFormat,
+    InvalidJweFormat,
+    InvalidKeyFormat,
+    InvalidJson,
+    InvalidJwtPayload,
+    InvalidClaim,
+    InvalidSignature,
+    AlgorithmMismatch,
+}
+
+impl JoseError {
+    /// Return the kind of this error.
+    pub fn kind(&self) -> JoseErrorKind {
+        match self {
+            Self::UnsupportedSignatureAlgorithm(_) => JoseErrorKind::UnsupportedSignatureAlgorithm,
+            Self::InvalidJwtFormat(_) => JoseErrorKind::InvalidJwtFormat,
+            Self::InvalidJwkFormat(_) => JoseErrorKind::InvalidJwkFormat,
+            Self::InvalidJwsFormat(_) => JoseErrorKind::InvalidJwsFormat,
+            Self::InvalidJweFormat(_) => JoseErrorKind::InvalidJweFormat,
+            Self::InvalidKeyFormat(_) => JoseErrorKind::InvalidKeyFormat,
+            Self::InvalidJson(_) => JoseErrorKind::InvalidJson,
+            Self::InvalidJwtPayload(_) => JoseErrorKind::InvalidJwtPayload,
+            Self::InvalidClaim(_) => JoseErrorKind::InvalidClaim,
+            Self::InvalidSignature(_) => JoseErrorKind::InvalidSignature,
+            Self::AlgorithmMismatch { .. } => JoseErrorKind::AlgorithmMismatch,
+        }
+    }
+
+    /// Test if this error is caused by a signature that failed to be created or verified.
+    pub fn is_signature_error(&self) -> bool {
+        self.kind() == JoseErrorKind::InvalidSignature
+    }
+
+    /// Test if this error is caused by a claim that is invalid.
+    pub fn is_claim_error(&self) -> bool {
+        self.kind() == JoseErrorKind::InvalidClaim
+    }
+
+    /// Test if this error is caused by a key or a JWK that is invalid.
+    pub fn is_key_error(&self) -> bool {
+        matches!(
+            self.kind(),
+            JoseErrorKind::InvalidKeyFormat | JoseErrorKind::InvalidJwkFormat
+        )
+    }
+
+    /// Test if this error is caused by an input that is malformed.
+    pub fn is_format_error(&self) -> bool {
+        matches!(
+            self.kind(),
+            JoseErrorKind::InvalidJwtFormat
+                | JoseErrorKind::InvalidJwsFormat
+                | JoseErrorKind::InvalidJweFormat
+                | JoseErrorKind::InvalidJson
+                | JoseErrorKind::InvalidJwtPayload
+        )
+    }
+
+    /// Test if this error is caused by an algorithm that is not supported.
+    pub fn is_unsupported_error(&self) -> bool {
+        self.kind() == JoseErrorKind::UnsupportedSignatureAlgorithm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Constructor = fn(anyhow::Error) -> JoseError;
+
+    #[test]
+    fn test_jose_error_kind() {
+        let cases: Vec<(Constructor, JoseErrorKind)> = vec![
+            (
+                JoseError::UnsupportedSignatureAlgorithm,
+                JoseErrorKind::UnsupportedSignatureAlgorithm,
+            ),
+            (JoseError::InvalidJwtFormat, JoseErrorKind::InvalidJwtFormat),
+            (JoseError::InvalidJwkFormat, JoseErrorKind::InvalidJwkFormat),
+            (JoseError::InvalidJwsFormat, JoseErrorKind::InvalidJwsFormat),
+            (JoseError::InvalidJweFormat, JoseErrorKind::InvalidJweFormat),
+            (JoseError::InvalidKeyFormat, JoseErrorKind::InvalidKeyFormat),
+            (JoseError::InvalidJson, JoseErrorKind::InvalidJson),
+            (
+                JoseError::InvalidJwtPayload,
+                JoseErrorKind::InvalidJwtPayload,
+            ),
+            (JoseError::InvalidClaim, JoseErrorKind::InvalidClaim),
+            (JoseError::InvalidSignature, JoseErrorKind::InvalidSignature),
+        ];
+
+        for (constructor, kind) in cases {
+            let err = constructor(anyhow::Error::msg("error"));
+            assert_eq!(err.kind(), kind);
+            assert_eq!(
+                err.is_signature_error(),
+                kind == JoseErrorKind::InvalidSignature
+            );
+            assert_eq!(err.is_claim_error(), kind == JoseErrorKind::InvalidClaim);
+            assert_eq!(
+                err.is_unsupported_error(),
+                kind == JoseErrorKind::UnsupportedSignatureAlgorithm
+            );
+        }
+
+        assert!(JoseError::InvalidJwkFormat(anyhow::Error::msg("error")).is_key_error());
+        assert!(JoseError::InvalidKeyFormat(anyhow::Error::msg("error")).is_key_error());
+        assert!(!JoseError::InvalidClaim(anyhow::Error::msg("error")).is_key_error());
+        assert!(JoseError::InvalidJweFormat(anyhow::Error::msg("error")).is_format_error());
+        assert!(JoseError::InvalidJwtPayload(anyhow::Error::msg("error")).is_format_error());
+        assert!(!JoseError::InvalidSignature(anyhow::Error::msg("error")).is_format_error());
+
+        let err = JoseError::AlgorithmMismatch {
+            expected: "RS256".to_string(),
+            actual: "HS256".to_string(),
+        };
+        assert_eq!(err.kind(), JoseErrorKind::AlgorithmMismatch);
+        assert!(!err.is_signature_error());
+        assert!(!err.is_format_error());
+    }
 }
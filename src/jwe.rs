@@ -5,18 +5,27 @@ pub mod zip;
 use std::borrow::Cow;
 use std::cmp::Eq;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
-use std::convert::Into;
+use std::convert::{Into, TryInto};
 use std::fmt::{Debug, Display};
 use std::io;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
 
 use anyhow::bail;
 use once_cell::sync::Lazy;
 use serde_json::{Map, Value};
 
-use crate::jose::{JoseError, JoseHeader};
+use crate::jose::{Base64Alphabet, JoseError, JoseHeader, COMMON_HEADER_CLAIMS};
+use crate::jwe::alg::aes::AesJweAlgorithm;
+use crate::jwe::alg::aes_gcm::AesGcmJweAlgorithm;
+use crate::jwe::alg::direct::DirectJweAlgorithm;
+use crate::jwe::alg::ecdh_es::EcdhEsJweAlgorithm;
+use crate::jwe::alg::pbes2_hmac::Pbes2HmacJweAlgorithm;
+use crate::jwe::alg::rsaes::RsaesJweAlgorithm;
+use crate::jwe::enc::aes_cbc_hmac::AesCbcHmacJweEncryption;
+use crate::jwe::enc::aes_gcm::AesGcmJweEncryption;
 use crate::jwk::Jwk;
-use crate::util::{self, SourceValue};
+use crate::jws::JwsHeader;
+use crate::util::{self, SecretBytes, SourceValue};
 
 pub use crate::jwe::alg::direct::DirectJweAlgorithm::Dir;
 
@@ -54,9 +63,15 @@ pub use crate::jwe::zip::deflate::DeflateJweCompression::Def;
 
 static DEFAULT_CONTEXT: Lazy<JweContext> = Lazy::new(|| JweContext::new());
 
+/// A context for encoding and decoding JWE.
+///
+/// A context is `Send + Sync`. It can be configured once and then shared read-only across
+/// threads, for example behind an `Arc`.
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct JweContext {
     acceptable_criticals: BTreeSet<String>,
+    require_matching_kid: bool,
+    base64_alphabet: Base64Alphabet,
     compressions: BTreeMap<String, Box<dyn JweCompression>>,
     content_encryptions: BTreeMap<String, Box<dyn JweContentEncryption>>,
 }
@@ -65,6 +80,8 @@ impl JweContext {
     pub fn new() -> Self {
         Self {
             acceptable_criticals: BTreeSet::new(),
+            require_matching_kid: false,
+            base64_alphabet: Base64Alphabet::UrlSafeNoPad,
             compressions: {
                 let compressions: Vec<Box<dyn JweCompression>> = vec![Box::new(Def)];
 
@@ -120,6 +137,40 @@ impl JweContext {
         self.acceptable_criticals.remove(name);
     }
 
+    /// Set whether a kid header claim must be matched by the decrypter.
+    ///
+    /// When enabled, a token carrying a kid header claim is rejected if the decrypter has no key
+    /// ID. The default is false, which accepts any kid when the decrypter has none.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - true to reject tokens with an unmatched kid header claim
+    pub fn set_require_matching_kid(&mut self, value: bool) {
+        self.require_matching_kid = value;
+    }
+
+    /// Return whether a kid header claim must be matched by the decrypter.
+    pub fn is_require_matching_kid(&self) -> bool {
+        self.require_matching_kid
+    }
+
+    /// Set the base64 alphabet used for the segments of compact serialization.
+    ///
+    /// The default is `Base64Alphabet::UrlSafeNoPad` as required by RFC 7516. Any other
+    /// alphabet produces and accepts non-standard tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a base64 alphabet
+    pub fn set_base64_alphabet(&mut self, value: Base64Alphabet) {
+        self.base64_alphabet = value;
+    }
+
+    /// Return the base64 alphabet used for the segments of compact serialization.
+    pub fn base64_alphabet(&self) -> Base64Alphabet {
+        self.base64_alphabet
+    }
+
     /// Get a compression algorithm for zip header claim value.
     ///
     /// # Arguments
@@ -151,6 +202,16 @@ impl JweContext {
         self.compressions.remove(name);
     }
 
+    /// Return this context with an additional compression algorithm for zip header claim name.
+    ///
+    /// # Arguments
+    ///
+    /// * `compression` - a compression algorithm
+    pub fn with_compression(mut self, compression: Box<dyn JweCompression>) -> Self {
+        self.add_compression(compression);
+        self
+    }
+
     /// Get a content encryption algorithm for enc header claim value.
     ///
     /// # Arguments
@@ -182,6 +243,28 @@ impl JweContext {
         self.content_encryptions.remove(name);
     }
 
+    /// Return this context with an additional content encryption algorithm for enc header
+    /// claim name.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_encryption` - a content encryption algorithm
+    pub fn with_content_encryption(
+        mut self,
+        content_encryption: Box<dyn JweContentEncryption>,
+    ) -> Self {
+        self.add_content_encryption(content_encryption);
+        self
+    }
+
+    /// Return the names of the registered content encryption algorithms.
+    pub fn content_encryption_names(&self) -> Vec<&str> {
+        self.content_encryptions
+            .keys()
+            .map(|key| key.as_str())
+            .collect()
+    }
+
     /// Return a representation of the data that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -223,11 +306,17 @@ impl JweContext {
             let cencryption = match header.content_encryption() {
                 Some(enc) => match self.get_content_encryption(enc) {
                     Some(val) => val,
-                    None => bail!("A content encryption is not registered: {}", enc),
+                    None => bail!(
+                        "A content encryption is not registered: {} (supported: {})",
+                        enc,
+                        self.content_encryption_names().join(", ")
+                    ),
                 },
                 None => bail!("A enc header claim is required."),
             };
 
+            check_content_key_len(encrypter, cencryption)?;
+
             let compression = match header.compression() {
                 Some(zip) => match self.get_compression(zip) {
                     Some(val) => Some(val),
@@ -277,21 +366,22 @@ impl JweContext {
                 capacity += util::ceiling(val.len() * 4, 3);
             }
 
+            let config = self.base64_alphabet.config();
             let mut message = String::with_capacity(capacity);
-            base64::encode_config_buf(header_bytes, base64::URL_SAFE_NO_PAD, &mut message);
+            base64::encode_config_buf(header_bytes, config, &mut message);
             message.push_str(".");
             if let Some(val) = &encrypted_key {
-                base64::encode_config_buf(val, base64::URL_SAFE_NO_PAD, &mut message);
+                base64::encode_config_buf(val, config, &mut message);
             }
             message.push_str(".");
             if let Some(val) = iv {
-                base64::encode_config_buf(val, base64::URL_SAFE_NO_PAD, &mut message);
+                base64::encode_config_buf(val, config, &mut message);
             }
             message.push_str(".");
-            base64::encode_config_buf(ciphertext, base64::URL_SAFE_NO_PAD, &mut message);
+            base64::encode_config_buf(ciphertext, config, &mut message);
             message.push_str(".");
             if let Some(val) = &tag {
-                base64::encode_config_buf(val, base64::URL_SAFE_NO_PAD, &mut message);
+                base64::encode_config_buf(val, config, &mut message);
             }
 
             Ok(message)
@@ -302,6 +392,195 @@ impl JweContext {
         })
     }
 
+    /// Return a representation of the data that is formatted by general json serialization.
+    ///
+    /// A single content encryption key is wrapped for each recipient by its own algorithm,
+    /// so the alg header claim and the key management parameters are set per recipient.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data.
+    /// * `protected` - The JWE protected header claims.
+    /// * `unprotected` - The JWE shared unprotected header claims.
+    /// * `aad` - The JWE additional authenticated data.
+    /// * `encrypter` - The JWE encrypters and the header claims per recipient.
+    pub fn serialize_general_json(
+        &self,
+        payload: &[u8],
+        protected: Option<&JweHeader>,
+        unprotected: Option<&JweHeader>,
+        aad: Option<&[u8]>,
+        encrypter: &JweMultiEncrypter,
+    ) -> Result<String, JoseError> {
+        (|| -> anyhow::Result<String> {
+            if encrypter.recipients.is_empty() {
+                bail!("At least one recipient is required.");
+            }
+
+            let protected = match protected {
+                Some(val) => val.clone(),
+                None => JweHeader::new(),
+            };
+
+            let mut shared_map = protected.claims_set().clone();
+            if let Some(val) = unprotected {
+                for (key, value) in val.claims_set() {
+                    if shared_map.contains_key(key) {
+                        bail!("Duplicate key exists: {}", key);
+                    }
+                    shared_map.insert(key.clone(), value.clone());
+                }
+            }
+            let shared = JweHeader::from_map(shared_map)?;
+
+            let cencryption = match shared.content_encryption() {
+                Some(enc) => match self.get_content_encryption(enc) {
+                    Some(val) => val,
+                    None => bail!(
+                        "A content encryption is not registered: {} (supported: {})",
+                        enc,
+                        self.content_encryption_names().join(", ")
+                    ),
+                },
+                None => bail!("A enc header claim is required in the shared header claims."),
+            };
+
+            let compression = match shared.compression() {
+                Some(zip) => match self.get_compression(zip) {
+                    Some(val) => Some(val),
+                    None => bail!("A compression algorithm is not registered: {}", zip),
+                },
+                None => None,
+            };
+
+            let key = util::rand_bytes(cencryption.key_len());
+
+            let mut recipients = Vec::with_capacity(encrypter.recipients.len());
+            for (header, encrypter) in &encrypter.recipients {
+                let mut merged_map = shared.claims_set().clone();
+                if let Some(val) = header {
+                    for (key, value) in val.claims_set() {
+                        if merged_map.contains_key(key) {
+                            bail!("Duplicate key exists: {}", key);
+                        }
+                        merged_map.insert(key.clone(), value.clone());
+                    }
+                }
+
+                let mut merged = JweHeader::from_map(merged_map)?;
+                let encrypted_key = encrypter.wrap_key(&mut merged, &key)?;
+                if merged.claim("kid").is_none() {
+                    if let Some(key_id) = encrypter.key_id() {
+                        merged.set_key_id(key_id);
+                    }
+                }
+
+                let mut header_map = Map::new();
+                for (key, value) in merged.claims_set() {
+                    match shared.claim(key) {
+                        Some(shared_value) if shared_value == value => {}
+                        Some(_) => bail!(
+                            "The {} header claim is shared but the {} encrypter requires another value.",
+                            key,
+                            encrypter.algorithm().name()
+                        ),
+                        None => {
+                            header_map.insert(key.clone(), value.clone());
+                        }
+                    }
+                }
+
+                recipients.push((header_map, encrypted_key));
+            }
+
+            let compressed;
+            let content = if let Some(compression) = compression {
+                compressed = compression.compress(payload)?;
+                &compressed
+            } else {
+                payload
+            };
+
+            let protected = serde_json::to_vec(protected.claims_set())?;
+
+            let iv_vec;
+            let iv = if cencryption.iv_len() > 0 {
+                iv_vec = util::rand_bytes(cencryption.iv_len());
+                Some(iv_vec.as_slice())
+            } else {
+                None
+            };
+
+            let protected_b64 = base64::encode_config(protected, base64::URL_SAFE_NO_PAD);
+            let (full_aad, aad_b64) = if let Some(val) = aad {
+                let aad_b64 = base64::encode_config(val, base64::URL_SAFE_NO_PAD);
+                (format!("{}.{}", &protected_b64, &aad_b64), Some(aad_b64))
+            } else {
+                (protected_b64.clone(), None)
+            };
+
+            let (ciphertext, tag) = cencryption.encrypt(&key, iv, content, full_aad.as_bytes())?;
+
+            let mut json = String::new();
+            json.push_str("{\"protected\":\"");
+            json.push_str(&protected_b64);
+            json.push('"');
+
+            if let Some(val) = unprotected {
+                let unprotected = serde_json::to_string(val.claims_set())?;
+                json.push_str(",\"unprotected\":");
+                json.push_str(&unprotected);
+            }
+
+            json.push_str(",\"recipients\":[");
+            for (i, (header, encrypted_key)) in recipients.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+
+                json.push_str("{\"header\":");
+                json.push_str(&serde_json::to_string(header)?);
+
+                if let Some(val) = encrypted_key {
+                    json.push_str(",\"encrypted_key\":\"");
+                    base64::encode_config_buf(val, base64::URL_SAFE_NO_PAD, &mut json);
+                    json.push('"');
+                }
+                json.push('}');
+            }
+            json.push(']');
+
+            if let Some(val) = aad_b64 {
+                json.push_str(",\"aad\":\"");
+                json.push_str(&val);
+                json.push('"');
+            }
+
+            if let Some(val) = iv {
+                json.push_str(",\"iv\":\"");
+                base64::encode_config_buf(val, base64::URL_SAFE_NO_PAD, &mut json);
+                json.push('"');
+            }
+
+            json.push_str(",\"ciphertext\":\"");
+            base64::encode_config_buf(&ciphertext, base64::URL_SAFE_NO_PAD, &mut json);
+            json.push('"');
+
+            if let Some(val) = tag {
+                json.push_str(",\"tag\":\"");
+                base64::encode_config_buf(&val, base64::URL_SAFE_NO_PAD, &mut json);
+                json.push('"');
+            }
+            json.push('}');
+
+            Ok(json)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJweFormat(err),
+        })
+    }
+
     /// Return a representation of the data that is formatted by flattened json serialization.
     ///
     /// # Arguments
@@ -388,11 +667,17 @@ impl JweContext {
             let cencryption = match merged.content_encryption() {
                 Some(enc) => match self.get_content_encryption(enc) {
                     Some(val) => val,
-                    None => bail!("A content encryption is not registered: {}", enc),
+                    None => bail!(
+                        "A content encryption is not registered: {} (supported: {})",
+                        enc,
+                        self.content_encryption_names().join(", ")
+                    ),
                 },
                 None => bail!("A enc header claim is required."),
             };
 
+            check_content_key_len(encrypter, cencryption)?;
+
             let compression = match merged.compression() {
                 Some(zip) => match self.get_compression(zip) {
                     Some(val) => Some(val),
@@ -430,7 +715,7 @@ impl JweContext {
                 let aad_b64 = base64::encode_config(val, base64::URL_SAFE_NO_PAD);
                 (format!("{}.{}", &protected_b64, &aad_b64), Some(aad_b64))
             } else {
-                (format!("{}.", &protected_b64), None)
+                (protected_b64.clone(), None)
             };
 
             let (ciphertext, tag) = cencryption.encrypt(&key, iv, content, full_aad.as_bytes())?;
@@ -452,11 +737,11 @@ impl JweContext {
                 json.push_str(&header);
             }
 
-            json.push_str(",\"encrypted_key\":\"");
             if let Some(val) = encrypted_key {
+                json.push_str(",\"encrypted_key\":\"");
                 base64::encode_config_buf(&val, base64::URL_SAFE_NO_PAD, &mut json);
+                json.push('"');
             }
-            json.push_str("\"");
 
             if let Some(val) = aad_b64 {
                 json.push_str(",\"aad\":\"");
@@ -464,21 +749,22 @@ impl JweContext {
                 json.push_str("\"");
             }
 
-            json.push_str(",\"iv\":\"");
             if let Some(val) = iv {
+                json.push_str(",\"iv\":\"");
                 base64::encode_config_buf(&val, base64::URL_SAFE_NO_PAD, &mut json);
+                json.push('"');
             }
-            json.push_str("\"");
 
             json.push_str(",\"ciphertext\":\"");
             base64::encode_config_buf(&ciphertext, base64::URL_SAFE_NO_PAD, &mut json);
             json.push_str("\"");
 
-            json.push_str(",\"tag\":\"");
             if let Some(val) = tag {
+                json.push_str(",\"tag\":\"");
                 base64::encode_config_buf(&val, base64::URL_SAFE_NO_PAD, &mut json);
+                json.push('"');
             }
-            json.push_str("\"}");
+            json.push('}');
 
             Ok(json)
         })()
@@ -516,7 +802,84 @@ impl JweContext {
     where
         F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
     {
-        (|| -> anyhow::Result<(Vec<u8>, JweHeader)> {
+        let (content, header, _) = self.deserialize_compact_core(input, selector)?;
+        Ok((content, header))
+    }
+
+    /// Deserialize the input that is formatted by compact serialization,
+    /// and return the key ID of the decrypter that decrypted it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `decrypter` - The JWE decrypter.
+    pub fn deserialize_compact_with_key_id(
+        &self,
+        input: &str,
+        decrypter: &dyn JweDecrypter,
+    ) -> Result<(Vec<u8>, JweHeader, Option<String>), JoseError> {
+        self.deserialize_compact_core(input, |_header| Ok(Some(decrypter)))
+    }
+
+    /// Deserialize the input that is formatted by compact serialization,
+    /// and return the key ID of the selected decrypter that decrypted it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `selector` - a function for selecting the decrypting algorithm.
+    pub fn deserialize_compact_with_key_id_and_selector<'a, F>(
+        &self,
+        input: &str,
+        selector: F,
+    ) -> Result<(Vec<u8>, JweHeader, Option<String>), JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    {
+        self.deserialize_compact_core(input, selector)
+    }
+
+    fn deserialize_compact_core<'a, F>(
+        &self,
+        input: &str,
+        selector: F,
+    ) -> Result<(Vec<u8>, JweHeader, Option<String>), JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    {
+        self.begin_decrypt_core(input, selector)?.finish_core()
+    }
+
+    /// Validate the header of the input that is formatted by compact serialization
+    /// and unwrap the content encryption key, but defer the decryption of the content
+    /// until `DecryptSession::finish` is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `decrypter` - The JWE decrypter.
+    pub fn begin_decrypt<'a>(
+        &'a self,
+        input: &'a str,
+        decrypter: &dyn JweDecrypter,
+    ) -> Result<DecryptSession<'a>, JoseError> {
+        self.begin_decrypt_core(input, |_header| Ok(Some(decrypter)))
+    }
+
+    fn begin_decrypt_core<'a, 'b, F>(
+        &'a self,
+        input: &'a str,
+        selector: F,
+    ) -> Result<DecryptSession<'a>, JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'b dyn JweDecrypter>, JoseError>,
+    {
+        (|| -> anyhow::Result<DecryptSession<'a>> {
+            let input = input.trim_matches(|c: char| c.is_ascii_whitespace());
+            if input.contains(|c: char| c.is_ascii_whitespace()) {
+                bail!("The compact serialization form of JWE must not contain whitespace.");
+            }
+
             let indexies: Vec<usize> = input
                 .char_indices()
                 .filter(|(_, c)| c == &'.')
@@ -528,41 +891,37 @@ impl JweContext {
                 );
             }
 
+            let config = self.base64_alphabet.config();
+
             let header_b64 = &input[0..indexies[0]];
 
             let encrypted_key_b64 = &input[(indexies[0] + 1)..(indexies[1])];
             let encrypted_key_vec;
             let encrypted_key = if encrypted_key_b64.len() > 0 {
-                encrypted_key_vec =
-                    base64::decode_config(encrypted_key_b64, base64::URL_SAFE_NO_PAD)?;
+                encrypted_key_vec = base64::decode_config(encrypted_key_b64, config)?;
                 Some(encrypted_key_vec.as_slice())
             } else {
                 None
             };
 
             let iv_b64 = &input[(indexies[1] + 1)..(indexies[2])];
-            let iv_vec;
             let iv = if iv_b64.len() > 0 {
-                iv_vec = base64::decode_config(iv_b64, base64::URL_SAFE_NO_PAD)?;
-                Some(iv_vec.as_slice())
+                Some(base64::decode_config(iv_b64, config)?)
             } else {
                 None
             };
 
             let ciphertext_b64 = &input[(indexies[2] + 1)..(indexies[3])];
-            let ciphertext = base64::decode_config(ciphertext_b64, base64::URL_SAFE_NO_PAD)?;
 
             let tag_b64 = &input[(indexies[3] + 1)..];
-            let tag_vec;
             let tag = if tag_b64.len() > 0 {
-                tag_vec = base64::decode_config(tag_b64, base64::URL_SAFE_NO_PAD)?;
-                Some(tag_vec.as_slice())
+                Some(base64::decode_config(tag_b64, config)?)
             } else {
                 None
             };
 
-            let header = base64::decode_config(header_b64, base64::URL_SAFE_NO_PAD)?;
-            let merged: Map<String, Value> = serde_json::from_slice(&header)?;
+            let header = base64::decode_config(header_b64, config)?;
+            let merged = util::parse_json_object(&header)?;
             let merged = JweHeader::from_map(merged)?;
 
             let decrypter = match selector(&merged)? {
@@ -573,7 +932,11 @@ impl JweContext {
             let cencryption = match merged.claim("enc") {
                 Some(Value::String(val)) => match self.get_content_encryption(val) {
                     Some(val2) => val2,
-                    None => bail!("A content encryption is not registered: {}", val),
+                    None => bail!(
+                        "A content encryption is not registered: {} (supported: {})",
+                        val,
+                        self.content_encryption_names().join(", ")
+                    ),
                 },
                 Some(_) => bail!("A enc header claim must be a string."),
                 None => bail!("A enc header claim is required."),
@@ -598,23 +961,24 @@ impl JweContext {
                 None => bail!("The JWE alg header claim is required."),
             }
 
-            match decrypter.key_id() {
-                Some(expected) => match merged.key_id() {
-                    Some(actual) if expected == actual => {}
-                    Some(actual) => bail!("The JWE kid header claim is mismatched: {}", actual),
-                    None => bail!("The JWE kid header claim is required."),
-                },
-                None => {}
-            }
+            self.check_key_id(decrypter, &merged)?;
 
             let key = decrypter.decrypt(&merged, encrypted_key, cencryption.key_len())?;
-            let content = cencryption.decrypt(&key, iv, &ciphertext, &header, tag)?;
-            let content = match compression {
-                Some(val) => val.decompress(&content)?,
-                None => content,
-            };
-
-            Ok((content, merged))
+            let key = SecretBytes::from(key.into_owned());
+            let key_id = decrypter.key_id().map(|val| val.to_string());
+
+            Ok(DecryptSession {
+                base64_alphabet: self.base64_alphabet,
+                header: merged,
+                header_bytes: header,
+                content_encryption: cencryption,
+                compression,
+                key,
+                iv,
+                ciphertext_b64,
+                tag,
+                key_id,
+            })
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
             Ok(err) => err,
@@ -670,48 +1034,209 @@ impl JweContext {
     where
         F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
     {
-        (|| -> anyhow::Result<(Vec<u8>, JweHeader)> {
-            let mut map: Map<String, Value> = serde_json::from_str(input)?;
+        let (content, merged, _) = self.deserialize_json_core(input, selector)?;
+        Ok((content, merged))
+    }
 
-            let (protected, protected_b64) = match map.remove("protected") {
-                Some(Value::String(val)) => {
-                    if val.len() == 0 {
-                        bail!("The protected field must be empty.");
-                    }
-                    let vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
-                    let json: Map<String, Value> = serde_json::from_slice(&vec)?;
-                    (Some(json), Some(val))
-                }
-                Some(_) => bail!("The protected field must be string."),
-                None => (None, None),
-            };
-            let unprotected = match map.remove("unprotected") {
-                Some(Value::String(val)) => {
-                    if val.len() == 0 {
-                        bail!("The unprotected field must be empty.");
-                    }
-                    let json: Map<String, Value> = serde_json::from_str(&val)?;
-                    Some(json)
-                }
-                Some(_) => bail!("The unprotected field must be string."),
-                None => None,
-            };
-            let aad_b64 = match map.remove("aad") {
-                Some(Value::String(val)) => {
-                    if val.len() == 0 {
-                        bail!("The aad field must be empty.");
-                    }
-                    base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
-                    Some(val)
+    /// Deserialize the input that is formatted by json serialization,
+    /// and return the protected, shared unprotected and per-recipient header claims separately.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `decrypter` - The JWE decrypter.
+    pub fn deserialize_json_with_headers(
+        &self,
+        input: &str,
+        decrypter: &dyn JweDecrypter,
+    ) -> Result<(Vec<u8>, JweHeader, JweJsonHeaders), JoseError> {
+        self.deserialize_json_with_headers_and_selector(input, |header| {
+            match header.algorithm() {
+                Some(val) if val == decrypter.algorithm().name() => {}
+                _ => return Ok(None),
+            }
+
+            if let Some(expected) = decrypter.key_id() {
+                match header.key_id() {
+                    Some(actual) if expected == actual => {}
+                    _ => return Ok(None),
                 }
-                Some(_) => bail!("The aad field must be string."),
-                None => None,
-            };
+            }
+
+            Ok(Some(decrypter))
+        })
+    }
+
+    /// Deserialize the input that is formatted by json serialization,
+    /// and return the protected, shared unprotected and per-recipient header claims separately.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `selector` - a function for selecting the decrypting algorithm.
+    pub fn deserialize_json_with_headers_and_selector<'a, F>(
+        &self,
+        input: &str,
+        selector: F,
+    ) -> Result<(Vec<u8>, JweHeader, JweJsonHeaders), JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    {
+        self.deserialize_json_core(input, selector)
+    }
+
+    /// Return the indexes of the decrypters that match a recipient of the input that is
+    /// formatted by json serialization.
+    ///
+    /// A decrypter matches when its algorithm is the alg header claim of a recipient, and
+    /// its key ID (if any) is the kid header claim. The content is not decrypted, so a matched
+    /// decrypter may still fail to decrypt it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `decrypters` - The JWE decrypters to try.
+    pub fn match_recipients(
+        &self,
+        input: &str,
+        decrypters: &[&dyn JweDecrypter],
+    ) -> Result<Vec<usize>, JoseError> {
+        (|| -> anyhow::Result<Vec<usize>> {
+            let mut map = util::parse_json_object(input.as_bytes())?;
+
+            let protected = match map.remove("protected") {
+                Some(Value::String(val)) => {
+                    let vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
+                    Some(util::parse_json_object(&vec)?)
+                }
+                Some(_) => bail!("The protected field must be string."),
+                None => None,
+            };
+            let unprotected = match map.remove("unprotected") {
+                Some(Value::Object(val)) => Some(val),
+                Some(_) => bail!("The unprotected field must be a object."),
+                None => None,
+            };
+            let recipients = match map.remove("recipients") {
+                Some(Value::Array(vals)) => {
+                    let mut vec = Vec::with_capacity(vals.len());
+                    for val in vals {
+                        if let Value::Object(val) = val {
+                            vec.push(val);
+                        } else {
+                            bail!("The recipients field must be a array of object.");
+                        }
+                    }
+                    vec
+                }
+                Some(_) => bail!("The recipients field must be a array."),
+                None => vec![map],
+            };
+
+            let mut headers = Vec::with_capacity(recipients.len());
+            for mut recipient in recipients {
+                let mut merged = match recipient.remove("header") {
+                    Some(Value::Object(val)) => val,
+                    Some(_) => bail!("The header field must be a object."),
+                    None => Map::new(),
+                };
+                for shared in protected.iter().chain(unprotected.iter()) {
+                    for (key, value) in shared {
+                        if merged.contains_key(key) {
+                            bail!("A duplicate key exists: {}", key);
+                        }
+                        merged.insert(key.clone(), value.clone());
+                    }
+                }
+                headers.push(JweHeader::from_map(merged)?);
+            }
+
+            let mut matched = Vec::new();
+            for (i, decrypter) in decrypters.iter().enumerate() {
+                let found = headers.iter().any(|header| {
+                    header.algorithm() == Some(decrypter.algorithm().name())
+                        && match decrypter.key_id() {
+                            Some(expected) => header.key_id() == Some(expected),
+                            None => true,
+                        }
+                });
+                if found {
+                    matched.push(i);
+                }
+            }
+            Ok(matched)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJweFormat(err),
+        })
+    }
+
+    fn check_key_id(&self, decrypter: &dyn JweDecrypter, header: &JweHeader) -> anyhow::Result<()> {
+        match decrypter.key_id() {
+            Some(expected) => match header.key_id() {
+                Some(actual) if expected == actual => {}
+                Some(actual) => bail!("The JWE kid header claim is mismatched: {}", actual),
+                None => bail!("The JWE kid header claim is required."),
+            },
+            None => {
+                if self.require_matching_kid {
+                    if let Some(actual) = header.key_id() {
+                        bail!(
+                            "The JWE kid header claim is not matched by the decrypter: {}",
+                            actual
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn deserialize_json_core<'a, F>(
+        &self,
+        input: &str,
+        selector: F,
+    ) -> Result<(Vec<u8>, JweHeader, JweJsonHeaders), JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    {
+        (|| -> anyhow::Result<(Vec<u8>, JweHeader, JweJsonHeaders)> {
+            let mut map = util::parse_json_object(input.as_bytes())?;
+
+            let (protected, protected_b64) = match map.remove("protected") {
+                Some(Value::String(val)) => {
+                    if val.len() == 0 {
+                        bail!("The protected field must not be empty.");
+                    }
+                    let vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
+                    let json = util::parse_json_object(&vec)?;
+                    (Some(json), Some(val))
+                }
+                Some(_) => bail!("The protected field must be string."),
+                None => (None, None),
+            };
+            let unprotected = match map.remove("unprotected") {
+                Some(Value::Object(val)) => Some(val),
+                Some(_) => bail!("The unprotected field must be a object."),
+                None => None,
+            };
+            let aad_b64 = match map.remove("aad") {
+                Some(Value::String(val)) => {
+                    if val.len() == 0 {
+                        bail!("The aad field must not be empty.");
+                    }
+                    base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
+                    Some(val)
+                }
+                Some(_) => bail!("The aad field must be string."),
+                None => None,
+            };
             let iv_vec;
             let iv = match map.remove("iv") {
                 Some(Value::String(val)) => {
                     if val.len() == 0 {
-                        bail!("The iv field must be empty.");
+                        bail!("The iv field must not be empty.");
                     }
                     iv_vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
                     Some(iv_vec.as_slice())
@@ -722,7 +1247,7 @@ impl JweContext {
             let ciphertext = match map.remove("ciphertext") {
                 Some(Value::String(val)) => {
                     if val.len() == 0 {
-                        bail!("The ciphertext field must be empty.");
+                        bail!("The ciphertext field must not be empty.");
                     }
                     base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?
                 }
@@ -733,7 +1258,7 @@ impl JweContext {
             let tag = match map.remove("tag") {
                 Some(Value::String(val)) => {
                     if val.len() == 0 {
-                        bail!("The tag field must be empty.");
+                        bail!("The tag field must not be empty.");
                     }
                     tag_vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
                     Some(tag_vec.as_slice())
@@ -745,7 +1270,7 @@ impl JweContext {
             let recipients = match map.remove("recipients") {
                 Some(Value::Array(vals)) => {
                     if vals.len() == 0 {
-                        bail!("The recipients field must be empty.");
+                        bail!("The recipients field must not be empty.");
                     }
                     let mut vec = Vec::with_capacity(vals.len());
                     for val in vals {
@@ -772,7 +1297,7 @@ impl JweContext {
                 let encrypted_key = match recipient.get("encrypted_key") {
                     Some(Value::String(val)) => {
                         if val.len() == 0 {
-                            bail!("The encrypted_key field must be empty.");
+                            bail!("The encrypted_key field must not be empty.");
                         }
                         encrypted_key_vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
                         Some(encrypted_key_vec.as_slice())
@@ -781,9 +1306,14 @@ impl JweContext {
                     None => None,
                 };
 
-                let mut merged = match header {
-                    Some(Value::Object(val)) => val,
-                    Some(_) => bail!("The protected field must be a object."),
+                let header = match header {
+                    Some(Value::Object(val)) => Some(val),
+                    Some(_) => bail!("The header field must be a object."),
+                    None => None,
+                };
+
+                let mut merged = match &header {
+                    Some(val) => val.clone(),
                     None => Map::new(),
                 };
 
@@ -817,7 +1347,11 @@ impl JweContext {
                 let cencryption = match merged.claim("enc") {
                     Some(Value::String(val)) => match self.get_content_encryption(val) {
                         Some(val2) => val2,
-                        None => bail!("A content encryption is not registered: {}", val),
+                        None => bail!(
+                            "A content encryption is not registered: {} (supported: {})",
+                            val,
+                            self.content_encryption_names().join(", ")
+                        ),
                     },
                     Some(_) => bail!("A enc header claim must be string."),
                     None => bail!("A enc header claim is required."),
@@ -842,14 +1376,7 @@ impl JweContext {
                     None => bail!("The JWE alg header claim is required."),
                 }
 
-                match decrypter.key_id() {
-                    Some(expected) => match merged.key_id() {
-                        Some(actual) if expected == actual => {}
-                        Some(actual) => bail!("The JWE kid header claim is mismatched: {}", actual),
-                        None => bail!("The JWE kid header claim is required."),
-                    },
-                    None => {}
-                }
+                self.check_key_id(decrypter, &merged)?;
 
                 let mut full_aad = match protected_b64 {
                     Some(val) => val,
@@ -864,11 +1391,33 @@ impl JweContext {
                 let content =
                     cencryption.decrypt(&key, iv, &ciphertext, full_aad.as_bytes(), tag)?;
                 let content = match compression {
-                    Some(val) => val.decompress(&content)?,
+                    Some(val) => match val.decompress(&content) {
+                        Ok(content) => content,
+                        Err(err) => bail!(
+                            "Failed to decompress the JWE content by {}: {}",
+                            val.name(),
+                            err
+                        ),
+                    },
                     None => content,
                 };
 
-                return Ok((content, merged));
+                let headers = JweJsonHeaders {
+                    protected: match &protected {
+                        Some(val) => Some(JweHeader::from_map(val.clone())?),
+                        None => None,
+                    },
+                    unprotected: match &unprotected {
+                        Some(val) => Some(JweHeader::from_map(val.clone())?),
+                        None => None,
+                    },
+                    header: match header {
+                        Some(val) => Some(JweHeader::from_map(val)?),
+                        None => None,
+                    },
+                };
+
+                return Ok((content, merged, headers));
             }
 
             bail!("A recipient that matched the header claims is not found.");
@@ -913,6 +1462,25 @@ where
     DEFAULT_CONTEXT.serialize_compact_with_selector(payload, header, selector)
 }
 
+/// Return a representation of the data that is formatted by general json serialization.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `protected` - The JWE protected header claims.
+/// * `unprotected` - The JWE shared unprotected header claims.
+/// * `aad` - The JWE additional authenticated data.
+/// * `encrypter` - The JWE encrypters and the header claims per recipient.
+pub fn serialize_general_json(
+    payload: &[u8],
+    protected: Option<&JweHeader>,
+    unprotected: Option<&JweHeader>,
+    aad: Option<&[u8]>,
+    encrypter: &JweMultiEncrypter,
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.serialize_general_json(payload, protected, unprotected, aad, encrypter)
+}
+
 /// Return a representation of the data that is formatted by flattened json serialization.
 ///
 /// # Arguments
@@ -970,6 +1538,21 @@ where
     )
 }
 
+/// Validate the header of the input that is formatted by compact serialization
+/// and unwrap the content encryption key, but defer the decryption of the content
+/// until `DecryptSession::finish` is called.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `decrypter` - The JWE decrypter.
+pub fn begin_decrypt<'a>(
+    input: &'a str,
+    decrypter: &dyn JweDecrypter,
+) -> Result<DecryptSession<'a>, JoseError> {
+    DEFAULT_CONTEXT.begin_decrypt(input, decrypter)
+}
+
 /// Deserialize the input that is formatted by compact serialization.
 ///
 /// # Arguments
@@ -999,6 +1582,145 @@ where
     DEFAULT_CONTEXT.deserialize_compact_with_selector(input, selector)
 }
 
+/// Deserialize the input that is formatted by compact serialization,
+/// and return the key ID of the decrypter that decrypted it.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `decrypter` - The JWE decrypter.
+pub fn deserialize_compact_with_key_id(
+    input: &str,
+    decrypter: &dyn JweDecrypter,
+) -> Result<(Vec<u8>, JweHeader, Option<String>), JoseError> {
+    DEFAULT_CONTEXT.deserialize_compact_with_key_id(input, decrypter)
+}
+
+/// Deserialize the input that is formatted by compact serialization,
+/// and return the key ID of the selected decrypter that decrypted it.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `selector` - a function for selecting the decrypting algorithm.
+pub fn deserialize_compact_with_key_id_and_selector<'a, F>(
+    input: &str,
+    selector: F,
+) -> Result<(Vec<u8>, JweHeader, Option<String>), JoseError>
+where
+    F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+{
+    DEFAULT_CONTEXT.deserialize_compact_with_key_id_and_selector(input, selector)
+}
+
+/// Return a encrypter from a JWK. The algorithm is selected by the alg parameter of the JWK.
+///
+/// # Arguments
+///
+/// * `jwk` - a JWK that has the alg parameter of a key management algorithm.
+pub fn encrypter_from_jwk(jwk: &Jwk) -> Result<Box<dyn JweEncrypter>, JoseError> {
+    jwk_algorithm_kind(jwk)?.encrypter_from_jwk(jwk)
+}
+
+/// Return a decrypter from a JWK. The algorithm is selected by the alg parameter of the JWK.
+///
+/// # Arguments
+///
+/// * `jwk` - a JWK that has the alg parameter of a key management algorithm.
+pub fn decrypter_from_jwk(jwk: &Jwk) -> Result<Box<dyn JweDecrypter>, JoseError> {
+    jwk_algorithm_kind(jwk)?.decrypter_from_jwk(jwk)
+}
+
+fn check_content_key_len(
+    encrypter: &dyn JweEncrypter,
+    cencryption: &dyn JweContentEncryption,
+) -> anyhow::Result<()> {
+    if let Some(key_len) = encrypter.content_key_len() {
+        if key_len != cencryption.key_len() {
+            bail!(
+                "The {} encrypter supplies a {}-byte key but the {} content encryption requires a {}-byte key.",
+                encrypter.algorithm().name(),
+                key_len,
+                cencryption.name(),
+                cencryption.key_len()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn jwk_algorithm_kind(jwk: &Jwk) -> Result<JweAlgorithmKind, JoseError> {
+    (|| -> anyhow::Result<JweAlgorithmKind> {
+        let alg = match jwk.algorithm() {
+            Some(val) => val,
+            None => bail!("A parameter alg is required."),
+        };
+        match JweAlgorithmKind::from_name(alg) {
+            Some(val) => Ok(val),
+            None => bail!(
+                "A parameter alg must be a key management algorithm: {}",
+                alg
+            ),
+        }
+    })()
+    .map_err(JoseError::InvalidJwkFormat)
+}
+
+/// Return the header claims of the input that is formatted by compact serialization without
+/// decrypting it.
+///
+/// The header is not authenticated by this function, so it must only be used to select a key.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+pub fn peek_header(input: &str) -> Result<JweHeader, JoseError> {
+    (|| -> anyhow::Result<JweHeader> {
+        let input = input.trim_matches(|c: char| c.is_ascii_whitespace());
+        if input.contains(|c: char| c.is_ascii_whitespace()) {
+            bail!("The compact serialization form of JWE must not contain whitespace.");
+        }
+
+        let parts: Vec<&str> = input.split('.').collect();
+        if parts.len() != 5 {
+            bail!("The compact serialization form of JWE must be five parts separated by colon.");
+        }
+
+        let header = base64::decode_config(parts[0], base64::URL_SAFE_NO_PAD)?;
+        let header = util::parse_json_object(&header)?;
+        let header = JweHeader::from_map(header)?;
+        Ok(header)
+    })()
+    .map_err(|err| match err.downcast::<JoseError>() {
+        Ok(err) => err,
+        Err(err) => JoseError::InvalidJweFormat(err),
+    })
+}
+
+/// Return the byte ranges of the header, encrypted key, initialization vector, ciphertext and
+/// authentication tag of the input that is formatted by compact serialization.
+///
+/// The ranges index the input as it is, so that the exact bytes can be forwarded without
+/// copying. Nothing is decoded or decrypted.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+pub fn segment_spans(input: &str) -> Result<[Range<usize>; 5], JoseError> {
+    (|| -> anyhow::Result<[Range<usize>; 5]> {
+        let spans = util::segment_spans(input);
+        let len = spans.len();
+        match spans.try_into() {
+            Ok(val) => Ok(val),
+            Err(_) => bail!(
+                "The compact serialization form of JWE must be five parts separated by colon, but found {} parts.",
+                len
+            ),
+        }
+    })()
+    .map_err(JoseError::InvalidJweFormat)
+}
+
 /// Deserialize the input that is formatted by flattened json serialization.
 ///
 /// # Arguments
@@ -1029,7 +1751,77 @@ where
     DEFAULT_CONTEXT.deserialize_json_with_selector(input, selector)
 }
 
+/// Deserialize the input that is formatted by json serialization,
+/// and return the protected, shared unprotected and per-recipient header claims separately.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `decrypter` - The JWE decrypter.
+pub fn deserialize_json_with_headers(
+    input: &str,
+    decrypter: &dyn JweDecrypter,
+) -> Result<(Vec<u8>, JweHeader, JweJsonHeaders), JoseError> {
+    DEFAULT_CONTEXT.deserialize_json_with_headers(input, decrypter)
+}
+
+/// Deserialize the input that is formatted by json serialization,
+/// and return the protected, shared unprotected and per-recipient header claims separately.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `selector` - a function for selecting the decrypting algorithm.
+pub fn deserialize_json_with_headers_and_selector<'a, F>(
+    input: &str,
+    selector: F,
+) -> Result<(Vec<u8>, JweHeader, JweJsonHeaders), JoseError>
+where
+    F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+{
+    DEFAULT_CONTEXT.deserialize_json_with_headers_and_selector(input, selector)
+}
+
+/// Return the indexes of the decrypters that match a recipient of the input that is
+/// formatted by json serialization.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `decrypters` - The JWE decrypters to try.
+pub fn match_recipients(
+    input: &str,
+    decrypters: &[&dyn JweDecrypter],
+) -> Result<Vec<usize>, JoseError> {
+    DEFAULT_CONTEXT.match_recipients(input, decrypters)
+}
+
+/// Represents the header claims of the matched recipient in JWE json serialization.
 #[derive(Debug, Eq, PartialEq, Clone)]
+pub struct JweJsonHeaders {
+    protected: Option<JweHeader>,
+    unprotected: Option<JweHeader>,
+    header: Option<JweHeader>,
+}
+
+impl JweJsonHeaders {
+    /// Return the protected header claims.
+    pub fn protected(&self) -> Option<&JweHeader> {
+        self.protected.as_ref()
+    }
+
+    /// Return the shared unprotected header claims.
+    pub fn unprotected(&self) -> Option<&JweHeader> {
+        self.unprotected.as_ref()
+    }
+
+    /// Return the unprotected header claims of the matched recipient.
+    pub fn header(&self) -> Option<&JweHeader> {
+        self.header.as_ref()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct JweHeader {
     claims: Map<String, Value>,
     sources: HashMap<String, SourceValue>,
@@ -1044,6 +1836,24 @@ impl JweHeader {
         }
     }
 
+    /// Return a new header instance with the common header claims of a JWS header.
+    ///
+    /// Only the claims that are not specific to JWS algorithms (jku, jwk, kid, x5u, x5c,
+    /// x5t, x5t#S256, typ and cty) are copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The JWS header
+    pub fn from_jws_header_common(header: &JwsHeader) -> Result<Self, JoseError> {
+        let mut claims = Map::new();
+        for key in &COMMON_HEADER_CLAIMS {
+            if let Some(value) = header.claim(key) {
+                claims.insert(key.to_string(), value.clone());
+            }
+        }
+        Self::from_map(claims)
+    }
+
     /// Return a new header instance from json style header.
     ///
     /// # Arguments
@@ -1051,7 +1861,7 @@ impl JweHeader {
     /// * `value` - The json style header claims
     pub fn from_slice(value: &[u8]) -> Result<Self, JoseError> {
         (|| -> anyhow::Result<Self> {
-            let claims: Map<String, Value> = serde_json::from_slice(value)?;
+            let claims = util::parse_json_object(value)?;
             Ok(Self::from_map(claims)?)
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
@@ -1066,18 +1876,100 @@ impl JweHeader {
     ///
     /// * `claims` - The header claims
     pub fn from_map(claims: Map<String, Value>) -> Result<Self, JoseError> {
-        Ok(Self {
-            claims,
-            sources: HashMap::new(),
-        })
-    }
-
-    /// Set a value for algorithm header claim (alg).
-    ///
-    /// # Arguments
-    ///
-    /// * `value` - a algorithm
-    pub fn set_algorithm(&mut self, value: impl Into<String>) {
+        (|| -> anyhow::Result<Self> {
+            let mut sources = HashMap::new();
+            for (key, value) in &claims {
+                match key.as_ref() {
+                    "alg" | "enc" | "zip" | "jku" | "x5u" | "kid" | "typ" | "cty" | "url"
+                    | "iss" | "sub" => match value {
+                        Value::String(_) => {}
+                        _ => bail!("The JWE {} header claim must be a string.", key),
+                    },
+                    "jwk" => match value {
+                        Value::Object(vals) => {
+                            let val = Jwk::from_map(vals.clone())?;
+                            sources.insert(key.clone(), SourceValue::Jwk(val));
+                        }
+                        _ => bail!("The JWE {} header claim must be a object.", key),
+                    },
+                    "x5t" | "x5t#S256" | "nonce" => match value {
+                        Value::String(val) => {
+                            let val = base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
+                            sources.insert(key.clone(), SourceValue::Bytes(val));
+                        }
+                        _ => bail!("The JWE {} header claim must be a string.", key),
+                    },
+                    "x5c" => match value {
+                        Value::Array(vals) => {
+                            let mut vec = Vec::with_capacity(vals.len());
+                            for val in vals {
+                                match val {
+                                    Value::String(val) => {
+                                        let decoded =
+                                            base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
+                                        vec.push(decoded);
+                                    }
+                                    _ => bail!(
+                                        "An element of the JWE {} header claim must be a string.",
+                                        key
+                                    ),
+                                }
+                            }
+                            sources.insert(key.clone(), SourceValue::BytesArray(vec));
+                        }
+                        _ => bail!("The JWE {} header claim must be a array.", key),
+                    },
+                    "crit" => match value {
+                        Value::Array(vals) => {
+                            let mut vec = Vec::with_capacity(vals.len());
+                            for val in vals {
+                                match val {
+                                    Value::String(val) => vec.push(val.to_string()),
+                                    _ => bail!(
+                                        "An element of the JWE {} header claim must be a string.",
+                                        key
+                                    ),
+                                }
+                            }
+                            sources.insert(key.clone(), SourceValue::StringArray(vec));
+                        }
+                        _ => bail!("The JWE {} header claim must be a array.", key),
+                    },
+                    "aud" => match value {
+                        Value::String(_) => {}
+                        Value::Array(vals) => {
+                            let mut vec = Vec::with_capacity(vals.len());
+                            for val in vals {
+                                match val {
+                                    Value::String(val) => vec.push(val.to_string()),
+                                    _ => bail!(
+                                        "An element of the JWE {} header claim must be a string.",
+                                        key
+                                    ),
+                                }
+                            }
+                            sources.insert(key.clone(), SourceValue::StringArray(vec));
+                        }
+                        _ => bail!("The JWE {} header claim must be a string or array.", key),
+                    },
+                    _ => {}
+                }
+            }
+
+            Ok(Self { claims, sources })
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJweFormat(err),
+        })
+    }
+
+    /// Set a value for algorithm header claim (alg).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a algorithm
+    pub fn set_algorithm(&mut self, value: impl Into<String>) {
         let value: String = value.into();
         self.claims.insert("alg".to_string(), Value::String(value));
     }
@@ -1101,6 +1993,76 @@ impl JweHeader {
         }
     }
 
+    /// Return the content encryption key length of the built-in algorithm for
+    /// content encryption header claim (enc).
+    ///
+    /// None is returned when the enc header claim is absent or is not a built-in algorithm.
+    pub fn content_encryption_key_len(&self) -> Option<usize> {
+        self.builtin_content_encryption().map(|val| val.key_len())
+    }
+
+    /// Return the initialization vector length of the built-in algorithm for
+    /// content encryption header claim (enc).
+    ///
+    /// None is returned when the enc header claim is absent or is not a built-in algorithm.
+    pub fn content_encryption_iv_len(&self) -> Option<usize> {
+        self.builtin_content_encryption().map(|val| val.iv_len())
+    }
+
+    /// Check the header claims that are required for encryption before serializing.
+    ///
+    /// The content encryption header claim (enc) must be present and be a built-in algorithm.
+    /// The algorithm header claim (alg) is not checked because it is set by the encrypter.
+    pub fn validate_for_encryption(&self) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            match self.content_encryption() {
+                Some(enc) => {
+                    if self.builtin_content_encryption().is_none() {
+                        bail!("The JWE enc header claim is not recognized: {}", enc);
+                    }
+                }
+                None => bail!("The JWE enc header claim is required."),
+            }
+            Ok(())
+        })()
+        .map_err(JoseError::InvalidJweFormat)
+    }
+
+    /// Return the algorithm header claim (alg) as a typed value.
+    ///
+    /// None is returned when the alg header claim is absent or is not a built-in algorithm.
+    pub fn algorithm_kind(&self) -> Option<JweAlgorithmKind> {
+        JweAlgorithmKind::from_name(self.algorithm()?)
+    }
+
+    /// Return the content encryption header claim (enc) as a typed value.
+    ///
+    /// None is returned when the enc header claim is absent or is not a built-in algorithm.
+    pub fn content_encryption_kind(&self) -> Option<JweContentEncryptionKind> {
+        let kind = match self.content_encryption()? {
+            "A128CBC-HS256" => JweContentEncryptionKind::AesCbcHmac(A128CbcHS256),
+            "A192CBC-HS384" => JweContentEncryptionKind::AesCbcHmac(A192CbcHS384),
+            "A256CBC-HS512" => JweContentEncryptionKind::AesCbcHmac(A256CbcHS512),
+            "A128GCM" => JweContentEncryptionKind::AesGcm(A128Gcm),
+            "A192GCM" => JweContentEncryptionKind::AesGcm(A192Gcm),
+            "A256GCM" => JweContentEncryptionKind::AesGcm(A256Gcm),
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    fn builtin_content_encryption(&self) -> Option<&'static dyn JweContentEncryption> {
+        match self.content_encryption()? {
+            "A128CBC-HS256" => Some(&A128CbcHS256),
+            "A192CBC-HS384" => Some(&A192CbcHS384),
+            "A256CBC-HS512" => Some(&A256CbcHS512),
+            "A128GCM" => Some(&A128Gcm),
+            "A192GCM" => Some(&A192Gcm),
+            "A256GCM" => Some(&A256Gcm),
+            _ => None,
+        }
+    }
+
     /// Set a value for compression header claim (zip).
     ///
     /// # Arguments
@@ -1303,6 +2265,13 @@ impl JweHeader {
         }
     }
 
+    /// Return the media type of content type header claim (cty).
+    ///
+    /// If the value does not contain "/", "application/" is prepended.
+    pub fn content_type_media_type(&self) -> Option<String> {
+        self.content_type().map(util::normalize_media_type)
+    }
+
     /// Set values for critical header claim (crit).
     ///
     /// # Arguments
@@ -1440,6 +2409,46 @@ impl JweHeader {
             _ => unreachable!(),
         }
     }
+
+    /// Return the JSON representation of header claims in a specified key order.
+    ///
+    /// Listed keys are emitted first in the given order, and then the remaining keys are emitted in sorted order.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - a list of header claim names to emit first
+    pub fn to_json_ordered(&self, keys: &[&str]) -> String {
+        let mut map = Map::with_capacity(self.claims.len());
+        for key in keys {
+            if let Some(val) = self.claims.get(*key) {
+                map.insert(key.to_string(), val.clone());
+            }
+        }
+
+        let mut rest: Vec<&String> = self
+            .claims
+            .keys()
+            .filter(|key| !map.contains_key(*key))
+            .collect();
+        rest.sort();
+        for key in rest {
+            map.insert(key.clone(), self.claims[key].clone());
+        }
+
+        Value::Object(map).to_string()
+    }
+
+    /// Remove a header claim of a specified key, including its typed value.
+    ///
+    /// Returns the removed value if the claim was present.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key name of header claim
+    pub fn remove_claim(&mut self, key: &str) -> Option<Value> {
+        self.sources.remove(key);
+        self.claims.remove(key)
+    }
 }
 
 impl JoseHeader for JweHeader {
@@ -1566,6 +2575,8 @@ impl JoseHeader for JweHeader {
     }
 }
 
+util::impl_eq_by_claims!(JweHeader);
+
 impl AsRef<Map<String, Value>> for JweHeader {
     fn as_ref(&self) -> &Map<String, Value> {
         &self.claims
@@ -1599,10 +2610,119 @@ impl DerefMut for JweHeader {
     }
 }
 
+/// A built-in key management algorithm, grouped by family.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum JweAlgorithmKind {
+    Direct(DirectJweAlgorithm),
+    EcdhEs(EcdhEsJweAlgorithm),
+    Aes(AesJweAlgorithm),
+    AesGcm(AesGcmJweAlgorithm),
+    Pbes2Hmac(Pbes2HmacJweAlgorithm),
+    Rsaes(RsaesJweAlgorithm),
+}
+
+impl JweAlgorithmKind {
+    /// Return the built-in key management algorithm of the name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a alg header parameter value (e.g. "RSA-OAEP")
+    pub fn from_name(name: &str) -> Option<Self> {
+        let kind = match name {
+            "dir" => Self::Direct(Dir),
+            "ECDH-ES" => Self::EcdhEs(EcdhEs),
+            "ECDH-ES+A128KW" => Self::EcdhEs(EcdhEsA128Kw),
+            "ECDH-ES+A192KW" => Self::EcdhEs(EcdhEsA192Kw),
+            "ECDH-ES+A256KW" => Self::EcdhEs(EcdhEsA256Kw),
+            "A128KW" => Self::Aes(A128Kw),
+            "A192KW" => Self::Aes(A192Kw),
+            "A256KW" => Self::Aes(A256Kw),
+            "A128GCMKW" => Self::AesGcm(A128GcmKw),
+            "A192GCMKW" => Self::AesGcm(A192GcmKw),
+            "A256GCMKW" => Self::AesGcm(A256GcmKw),
+            "PBES2-HS256+A128KW" => Self::Pbes2Hmac(Pbes2HS256A128Kw),
+            "PBES2-HS384+A192KW" => Self::Pbes2Hmac(Pbes2HS384A192Kw),
+            "PBES2-HS512+A256KW" => Self::Pbes2Hmac(Pbes2HS512A256Kw),
+            #[allow(deprecated)]
+            "RSA1_5" => Self::Rsaes(Rsa1_5),
+            "RSA-OAEP" => Self::Rsaes(RsaOaep),
+            "RSA-OAEP-256" => Self::Rsaes(RsaOaep256),
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Return a encrypter of this algorithm from a JWK.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - a JWK
+    pub fn encrypter_from_jwk(&self, jwk: &Jwk) -> Result<Box<dyn JweEncrypter>, JoseError> {
+        let encrypter: Box<dyn JweEncrypter> = match self {
+            Self::Direct(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::EcdhEs(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::Aes(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::AesGcm(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::Pbes2Hmac(val) => Box::new(val.encrypter_from_jwk(jwk)?),
+            Self::Rsaes(val) => {
+                Self::check_implemented(val)?;
+                Box::new(val.encrypter_from_jwk(jwk)?)
+            }
+        };
+        Ok(encrypter)
+    }
+
+    /// Return a decrypter of this algorithm from a JWK.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - a JWK
+    pub fn decrypter_from_jwk(&self, jwk: &Jwk) -> Result<Box<dyn JweDecrypter>, JoseError> {
+        let decrypter: Box<dyn JweDecrypter> = match self {
+            Self::Direct(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::EcdhEs(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::Aes(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::AesGcm(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::Pbes2Hmac(val) => Box::new(val.decrypter_from_jwk(jwk)?),
+            Self::Rsaes(val) => {
+                Self::check_implemented(val)?;
+                Box::new(val.decrypter_from_jwk(jwk)?)
+            }
+        };
+        Ok(decrypter)
+    }
+
+    fn check_implemented(algorithm: &RsaesJweAlgorithm) -> Result<(), JoseError> {
+        match algorithm {
+            RsaesJweAlgorithm::RsaOaep256
+            | RsaesJweAlgorithm::RsaOaep384
+            | RsaesJweAlgorithm::RsaOaep512 => Err(JoseError::InvalidJwkFormat(
+                anyhow::Error::msg(format!("{} is not supported yet.", algorithm.name())),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A built-in content encryption algorithm, grouped by family.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum JweContentEncryptionKind {
+    AesCbcHmac(AesCbcHmacJweEncryption),
+    AesGcm(AesGcmJweEncryption),
+}
+
 pub trait JweAlgorithm: Debug + Send + Sync {
     /// Return the "alg" (algorithm) header parameter value of JWE.
     fn name(&self) -> &str;
 
+    /// Return the length of the key that this algorithm expects to be provisioned with.
+    ///
+    /// The default implementation returns None, which is used when the key length depends on
+    /// the content encryption (dir) or on the key itself.
+    fn recommended_key_len(&self) -> Option<usize> {
+        None
+    }
+
     fn box_clone(&self) -> Box<dyn JweAlgorithm>;
 }
 
@@ -1620,6 +2740,8 @@ impl Clone for Box<dyn JweAlgorithm> {
     }
 }
 
+/// An encrypter that supplies the content encryption key for a recipient of a JWE,
+/// and returns the encrypted key that is stored in the JWE.
 pub trait JweEncrypter: Debug + Send + Sync {
     /// Return the source algorithm instance.
     fn algorithm(&self) -> &dyn JweAlgorithm;
@@ -1639,6 +2761,28 @@ pub trait JweEncrypter: Debug + Send + Sync {
         key_len: usize,
     ) -> Result<(Cow<[u8]>, Option<Vec<u8>>), JoseError>;
 
+    /// Return the length of the content encryption key when this encrypter can only
+    /// supply a key of a fixed length (e.g. dir).
+    fn content_key_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// Return a given content encryption key encrypted for this recipient.
+    ///
+    /// This is used when the key is shared by multiple recipients. The default implementation
+    /// returns an error, which is used by algorithms that cannot wrap a key (dir, ECDH-ES).
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - the header
+    /// * `key` - the content encryption key
+    fn wrap_key(&self, _header: &mut JweHeader, _key: &[u8]) -> Result<Option<Vec<u8>>, JoseError> {
+        Err(JoseError::InvalidJweFormat(anyhow::Error::msg(format!(
+            "The {} algorithm cannot wrap a given content encryption key.",
+            self.algorithm().name()
+        ))))
+    }
+
     fn box_clone(&self) -> Box<dyn JweEncrypter>;
 }
 
@@ -1648,6 +2792,149 @@ impl Clone for Box<dyn JweEncrypter> {
     }
 }
 
+/// A JWE decryption whose header is validated and whose content encryption key is
+/// unwrapped, but whose content is not decrypted yet.
+pub struct DecryptSession<'a> {
+    base64_alphabet: Base64Alphabet,
+    header: JweHeader,
+    header_bytes: Vec<u8>,
+    content_encryption: &'a dyn JweContentEncryption,
+    compression: Option<&'a dyn JweCompression>,
+    key: SecretBytes,
+    iv: Option<Vec<u8>>,
+    ciphertext_b64: &'a str,
+    tag: Option<Vec<u8>>,
+    key_id: Option<String>,
+}
+
+impl<'a> DecryptSession<'a> {
+    /// Return the JWE header claims.
+    pub fn header(&self) -> &JweHeader {
+        &self.header
+    }
+
+    /// Return the content encryption algorithm of the enc header claim.
+    pub fn content_encryption(&self) -> &dyn JweContentEncryption {
+        self.content_encryption
+    }
+
+    /// Return the unwrapped content encryption key.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Return the decoded initialization vector.
+    pub fn iv(&self) -> Option<&[u8]> {
+        self.iv.as_deref()
+    }
+
+    /// Return the key ID of the decrypter that unwrapped the content encryption key.
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    /// Decrypt the content and return it.
+    pub fn finish(self) -> Result<Vec<u8>, JoseError> {
+        let (content, _, _) = self.finish_core()?;
+        Ok(content)
+    }
+
+    fn finish_core(self) -> Result<(Vec<u8>, JweHeader, Option<String>), JoseError> {
+        (|| -> anyhow::Result<(Vec<u8>, JweHeader, Option<String>)> {
+            let config = self.base64_alphabet.config();
+            let ciphertext = base64::decode_config(self.ciphertext_b64, config)?;
+
+            let content = self.content_encryption.decrypt(
+                &self.key,
+                self.iv.as_deref(),
+                &ciphertext,
+                &self.header_bytes,
+                self.tag.as_deref(),
+            )?;
+            let content = match self.compression {
+                Some(val) => match val.decompress(&content) {
+                    Ok(content) => content,
+                    Err(err) => bail!(
+                        "Failed to decompress the JWE content by {}: {}",
+                        val.name(),
+                        err
+                    ),
+                },
+                None => content,
+            };
+
+            Ok((content, self.header, self.key_id))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJweFormat(err),
+        })
+    }
+}
+
+impl Debug for DecryptSession<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("DecryptSession")
+            .field("header", &self.header)
+            .field("content_encryption", &self.content_encryption)
+            .field("compression", &self.compression)
+            .field("key", &"[REDACTED]")
+            .field("iv", &self.iv)
+            .field("tag", &self.tag)
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+/// The recipients of a JWE by general json serialization.
+///
+/// The content encryption key is generated once and wrapped by the encrypter of each recipient.
+pub struct JweMultiEncrypter<'a> {
+    recipients: Vec<(Option<&'a JweHeader>, &'a dyn JweEncrypter)>,
+}
+
+impl<'a> JweMultiEncrypter<'a> {
+    /// Return a JweMultiEncrypter instance that has no recipients.
+    pub fn new() -> Self {
+        JweMultiEncrypter {
+            recipients: Vec::new(),
+        }
+    }
+
+    /// Add a recipient that the content encryption key is wrapped for.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The JWE unprotected header claims for the recipient.
+    /// * `encrypter` - The JWE encrypter for the recipient.
+    pub fn add_recipient(
+        &mut self,
+        header: Option<&'a JweHeader>,
+        encrypter: &'a dyn JweEncrypter,
+    ) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            if let Some(val) = encrypter.content_key_len() {
+                bail!(
+                    "The {} encrypter supplies a fixed {}-byte key and cannot be shared with other recipients.",
+                    encrypter.algorithm().name(),
+                    val
+                );
+            }
+
+            self.recipients.push((header, encrypter));
+
+            Ok(())
+        })()
+        .map_err(JoseError::InvalidJweFormat)
+    }
+}
+
+impl Default for JweMultiEncrypter<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub trait JweDecrypter: Debug + Send + Sync {
     /// Return the source algorithm instance.
     fn algorithm(&self) -> &dyn JweAlgorithm;
@@ -1749,10 +3036,27 @@ impl Clone for Box<dyn JweCompression> {
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use serde_json::Value;
+    use serde_json::{json, Map, Value};
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::path::PathBuf;
+
+    use crate::jose::{JoseError, JoseHeader};
+    use crate::jwe::{
+        self, A128GcmKw, A128Kw, A192GcmKw, A192Kw, A256Gcm, A256GcmKw, A256Kw, Def, Dir, EcdhEs,
+        EcdhEsA128Kw, EcdhEsA192Kw, EcdhEsA256Kw, JweAlgorithm, JweAlgorithmKind, JweCompression,
+        JweContentEncryptionKind, JweContext, JweDecrypter, JweEncrypter, JweHeader,
+        JweMultiEncrypter, Pbes2HS256A128Kw, Pbes2HS384A192Kw, Pbes2HS512A256Kw, RsaOaep,
+    };
+    use crate::jwk::Jwk;
+    use crate::jws::JwsHeader;
+    use crate::util;
 
-    use crate::jose::JoseHeader;
-    use crate::jwe::{self, Dir, JweAlgorithm, JweHeader};
+    #[test]
+    fn test_jwe_context_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<JweContext>();
+    }
 
     #[test]
     fn test_jwe_compact_serialization() -> Result<()> {
@@ -1788,4 +3092,852 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_jwe_flattened_json_members() -> Result<()> {
+        let mut protected = JweHeader::new();
+        protected.set_content_encryption("A128GCM");
+        let mut unprotected = JweHeader::new();
+        unprotected.set_claim(
+            "jku",
+            Some(Value::String(
+                "https://server.example.com/keys.jwks".to_string(),
+            )),
+        )?;
+        let mut header = JweHeader::new();
+        header.set_key_id("7");
+
+        let key = b"0123456789012345";
+        let json = jwe::serialize_flattened_json(
+            b"test payload!",
+            Some(&protected),
+            Some(&unprotected),
+            Some(&header),
+            None,
+            &Dir.encrypter_from_slice(key)?,
+        )?;
+
+        // RFC 7516 7.2.1: the unprotected header is a JSON object and
+        // the empty encrypted_key, iv and tag members are absent.
+        let map: Value = serde_json::from_str(&json)?;
+        assert_eq!(
+            map.get("unprotected"),
+            Some(&Value::Object(unprotected.claims_set().clone()))
+        );
+        assert_eq!(map.get("encrypted_key"), None);
+        assert!(map.get("iv").is_some());
+        assert!(map.get("tag").is_some());
+
+        let decrypter = Dir.decrypter_from_slice(key)?;
+        let (payload, merged) = jwe::deserialize_json(&json, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(merged.key_id(), Some("7"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_compact_serialization_with_mismatched_key_len() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+
+        let encrypter = Dir.encrypter_from_slice(b"0123456789012345")?;
+        let err = jwe::serialize_compact(b"test payload!", &header, &encrypter).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Invalid JWE format: The dir encrypter supplies a 16-byte key but the A256GCM content encryption requires a 32-byte key."
+        );
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone)]
+    struct IdentityCompression;
+
+    impl JweCompression for IdentityCompression {
+        fn name(&self) -> &str {
+            "IDN"
+        }
+
+        fn compress(&self, message: &[u8]) -> Result<Vec<u8>, io::Error> {
+            Ok(message.to_vec())
+        }
+
+        fn decompress(&self, message: &[u8]) -> Result<Vec<u8>, io::Error> {
+            Ok(message.to_vec())
+        }
+
+        fn box_clone(&self) -> Box<dyn JweCompression> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Pretends to be DEF but produces the given bytes instead of a valid DEFLATE stream.
+    #[derive(Debug, Clone)]
+    struct FakeDeflateCompression(fn(&[u8]) -> Vec<u8>);
+
+    impl JweCompression for FakeDeflateCompression {
+        fn name(&self) -> &str {
+            "DEF"
+        }
+
+        fn compress(&self, message: &[u8]) -> Result<Vec<u8>, io::Error> {
+            Ok((self.0)(message))
+        }
+
+        fn decompress(&self, message: &[u8]) -> Result<Vec<u8>, io::Error> {
+            Ok(message.to_vec())
+        }
+
+        fn box_clone(&self) -> Box<dyn JweCompression> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_jwe_corrupt_deflate_content() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let encrypter = Dir.encrypter_from_slice(key)?;
+        let decrypter = Dir.decrypter_from_slice(key)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+        header.set_compression("DEF");
+
+        fn garbage(_message: &[u8]) -> Vec<u8> {
+            vec![0xFF; 16]
+        }
+
+        fn truncated(message: &[u8]) -> Vec<u8> {
+            let compressed = Def.compress(message).unwrap();
+            compressed[..(compressed.len() / 2)].to_vec()
+        }
+
+        for corruption in &[garbage, truncated] {
+            let context =
+                JweContext::new().with_compression(Box::new(FakeDeflateCompression(*corruption)));
+            let jwe = context.serialize_compact(&[b'a'; 256], &header, &encrypter)?;
+
+            match jwe::deserialize_compact(&jwe, &decrypter) {
+                Err(JoseError::InvalidJweFormat(err)) => {
+                    assert!(format!("{}", err).starts_with("Failed to decompress"));
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_context_with_algorithms() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+
+        let mut base = JweContext::new();
+        base.remove_content_encryption("A256GCM");
+
+        let tenant = base
+            .clone()
+            .with_compression(Box::new(IdentityCompression))
+            .with_content_encryption(Box::new(A256Gcm));
+        assert!(tenant.get_compression("IDN").is_some());
+        assert!(tenant.get_content_encryption("A256GCM").is_some());
+        assert!(base.get_compression("IDN").is_none());
+        assert!(base.get_content_encryption("A256GCM").is_none());
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+        header.set_compression("IDN");
+
+        let encrypter = Dir.encrypter_from_slice(key)?;
+        let decrypter = Dir.decrypter_from_slice(key)?;
+        let jwe = tenant.serialize_compact(b"test payload!", &header, &encrypter)?;
+        let (payload, _) = tenant.deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+
+        assert!(base
+            .serialize_compact(b"test payload!", &header, &encrypter)
+            .is_err());
+        assert!(base.deserialize_compact(&jwe, &decrypter).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_unregistered_content_encryption() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256CBC-HS256");
+
+        let encrypter = Dir.encrypter_from_slice(key)?;
+        let err = jwe::serialize_compact(b"test payload!", &header, &encrypter).unwrap_err();
+        let message = format!("{}", err);
+        assert!(message.contains("A content encryption is not registered: A256CBC-HS256"));
+        assert!(message.contains("A256CBC-HS512"));
+        assert!(message.contains("A256GCM"));
+
+        let context = JweContext::new();
+        assert!(context.content_encryption_names().contains(&"A128GCM"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_header_content_encryption_lengths() -> Result<()> {
+        let mut header = JweHeader::new();
+        assert_eq!(header.content_encryption_key_len(), None);
+        assert_eq!(header.content_encryption_iv_len(), None);
+
+        header.set_content_encryption("A256GCM");
+        assert_eq!(header.content_encryption_key_len(), Some(32));
+        assert_eq!(header.content_encryption_iv_len(), Some(12));
+
+        header.set_content_encryption("A128CBC-HS256");
+        assert_eq!(header.content_encryption_key_len(), Some(32));
+        assert_eq!(header.content_encryption_iv_len(), Some(16));
+
+        header.set_content_encryption("A256CBC-HS256");
+        assert_eq!(header.content_encryption_key_len(), None);
+        assert_eq!(header.content_encryption_iv_len(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_header_validate_for_encryption() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_token_type("JWT");
+        assert!(header.validate_for_encryption().is_err());
+
+        header.set_content_encryption("A256CBC-HS256");
+        assert!(header.validate_for_encryption().is_err());
+
+        header.set_content_encryption("A128CBC-HS256");
+        header.validate_for_encryption()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_segment_spans() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        let encrypter = A128GcmKw.encrypter_from_slice(b"0123456789ABCDEF")?;
+        let input = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+        let parts: Vec<&str> = input.split('.').collect();
+
+        let spans = jwe::segment_spans(&input)?;
+        for (span, part) in spans.iter().zip(&parts) {
+            assert_eq!(&input[span.clone()], *part);
+        }
+
+        assert_eq!(jwe::segment_spans("a....")?, [0..1, 2..2, 3..3, 4..4, 5..5]);
+        assert!(jwe::segment_spans("a.b.c").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_peek_header() -> Result<()> {
+        let key = b"0123456789ABCDEF";
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        header.set_key_id("key-1");
+
+        let encrypter = Dir.encrypter_from_slice(key)?;
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+
+        let peeked = jwe::peek_header(&jwe)?;
+        assert_eq!(peeked.content_encryption(), Some("A128GCM"));
+        assert_eq!(peeked.algorithm(), Some("dir"));
+        assert_eq!(peeked.key_id(), Some("key-1"));
+
+        let parts: Vec<&str> = jwe.split('.').collect();
+        assert!(jwe::peek_header(&parts[..4].join(".")).is_err());
+        assert!(jwe::peek_header(&format!("{}.", jwe)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_header_from_jws_header_common() -> Result<()> {
+        let mut jws_header = JwsHeader::new();
+        jws_header.set_algorithm("HS256");
+        jws_header.set_token_type("JWT");
+        jws_header.set_key_id("key-1");
+        jws_header.set_content_type("JWT");
+        jws_header.set_base64url_encode_payload(false);
+        jws_header.set_critical(vec!["b64"]);
+
+        let mut jwe_header = JweHeader::from_jws_header_common(&jws_header)?;
+        assert_eq!(jwe_header.token_type(), Some("JWT"));
+        assert_eq!(jwe_header.key_id(), Some("key-1"));
+        assert_eq!(jwe_header.content_type(), Some("JWT"));
+        assert_eq!(jwe_header.algorithm(), None);
+        assert_eq!(jwe_header.claim("b64"), None);
+        assert_eq!(jwe_header.claim("crit"), None);
+
+        jwe_header.set_algorithm("dir");
+        jwe_header.set_content_encryption("A128GCM");
+        jwe_header.set_compression("DEF");
+
+        let jws_header = JwsHeader::from_jwe_header_common(&jwe_header)?;
+        assert_eq!(jws_header.token_type(), Some("JWT"));
+        assert_eq!(jws_header.key_id(), Some("key-1"));
+        assert_eq!(jws_header.content_type(), Some("JWT"));
+        assert_eq!(jws_header.algorithm(), None);
+        assert_eq!(jws_header.claim("enc"), None);
+        assert_eq!(jws_header.claim("zip"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_require_matching_kid() -> Result<()> {
+        let key = b"0123456789ABCDEF";
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        header.set_key_id("key-1");
+
+        let encrypter = Dir.encrypter_from_slice(key)?;
+        let compact = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+        let json = jwe::serialize_flattened_json(
+            b"test payload!",
+            Some(&header),
+            None,
+            None,
+            None,
+            &encrypter,
+        )?;
+
+        let decrypter = Dir.decrypter_from_slice(key)?;
+
+        let mut context = JweContext::new();
+        assert!(!context.is_require_matching_kid());
+        let (payload, _) = context.deserialize_compact(&compact, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+        let (payload, _) = context.deserialize_json(&json, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+
+        context.set_require_matching_kid(true);
+        assert!(context.deserialize_compact(&compact, &decrypter).is_err());
+        assert!(context.deserialize_json(&json, &decrypter).is_err());
+
+        let mut decrypter = Dir.decrypter_from_slice(key)?;
+        decrypter.set_key_id(Some("key-1"));
+        let (payload, _) = context.deserialize_compact(&compact, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+        let (payload, _) = context.deserialize_json(&json, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_header_algorithm_kind() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+
+        let encrypter = EcdhEsA128Kw.encrypter_from_pem(&public_key)?;
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+
+        let decrypter = EcdhEsA128Kw.decrypter_from_pem(&private_key)?;
+        let (_, header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(
+            header.algorithm_kind(),
+            Some(JweAlgorithmKind::EcdhEs(EcdhEsA128Kw))
+        );
+        assert_eq!(
+            header.content_encryption_kind(),
+            Some(JweContentEncryptionKind::AesGcm(A256Gcm))
+        );
+
+        let mut header = JweHeader::new();
+        assert_eq!(header.algorithm_kind(), None);
+        assert_eq!(header.content_encryption_kind(), None);
+
+        header.set_algorithm("RSA-OAEP-384");
+        header.set_content_encryption("A256CBC-HS256");
+        assert_eq!(header.algorithm_kind(), None);
+        assert_eq!(header.content_encryption_kind(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_compact_serialization_with_key_wrap_header_claims() -> Result<()> {
+        let src_payload = b"test payload!";
+
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+
+        let encrypter = EcdhEsA128Kw.encrypter_from_pem(&public_key)?;
+        let jwe = jwe::serialize_compact(src_payload, &header, &encrypter)?;
+
+        let decrypter = EcdhEsA128Kw.decrypter_from_pem(&private_key)?;
+        let (dst_payload, dst_header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(dst_payload, src_payload);
+        assert_eq!(dst_header.algorithm(), Some("ECDH-ES+A128KW"));
+        assert!(matches!(dst_header.claim("epk"), Some(Value::Object(_))));
+
+        let key = b"0123456789ABCDEF";
+        let encrypter = A128GcmKw.encrypter_from_slice(key)?;
+        let jwe = jwe::serialize_compact(src_payload, &header, &encrypter)?;
+
+        let decrypter = A128GcmKw.decrypter_from_slice(key)?;
+        let (dst_payload, dst_header) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(dst_payload, src_payload);
+        assert!(matches!(dst_header.claim("iv"), Some(Value::String(_))));
+        assert!(matches!(dst_header.claim("tag"), Some(Value::String(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_general_json_serialization_with_mixed_algorithms() -> Result<()> {
+        let src_payload = b"test payload!";
+
+        let rsa_private_key = load_file("pem/RSA_2048bit_private.pem")?;
+        let rsa_public_key = load_file("pem/RSA_2048bit_public.pem")?;
+        let ec_private_key = load_file("pem/EC_P-256_private.pem")?;
+        let ec_public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let mut protected = JweHeader::new();
+        protected.set_content_encryption("A128GCM");
+        let mut ec_header = JweHeader::new();
+        ec_header.set_key_id("ec-recipient");
+
+        let rsa_encrypter = RsaOaep.encrypter_from_pem(&rsa_public_key)?;
+        let ec_encrypter = EcdhEsA128Kw.encrypter_from_pem(&ec_public_key)?;
+        let mut multi_encrypter = JweMultiEncrypter::new();
+        multi_encrypter.add_recipient(None, &rsa_encrypter)?;
+        multi_encrypter.add_recipient(Some(&ec_header), &ec_encrypter)?;
+
+        let jwe = jwe::serialize_general_json(
+            src_payload,
+            Some(&protected),
+            None,
+            None,
+            &multi_encrypter,
+        )?;
+
+        let json: Value = serde_json::from_str(&jwe)?;
+        let recipients = json["recipients"].as_array().unwrap();
+        assert_eq!(recipients.len(), 2);
+        assert_eq!(recipients[0]["header"]["alg"], "RSA-OAEP");
+        assert_eq!(recipients[1]["header"]["alg"], "ECDH-ES+A128KW");
+        assert!(recipients[1]["header"]["epk"].is_object());
+        assert_ne!(
+            recipients[0]["encrypted_key"],
+            recipients[1]["encrypted_key"]
+        );
+
+        let rsa_decrypter = RsaOaep.decrypter_from_pem(&rsa_private_key)?;
+        let (dst_payload, dst_header) = jwe::deserialize_json(&jwe, &rsa_decrypter)?;
+        assert_eq!(dst_payload, src_payload);
+        assert_eq!(dst_header.algorithm(), Some("RSA-OAEP"));
+        assert_eq!(dst_header.content_encryption(), Some("A128GCM"));
+
+        let ec_decrypter = EcdhEsA128Kw.decrypter_from_pem(&ec_private_key)?;
+        let (dst_payload, dst_header) = jwe::deserialize_json(&jwe, &ec_decrypter)?;
+        assert_eq!(dst_payload, src_payload);
+        assert_eq!(dst_header.algorithm(), Some("ECDH-ES+A128KW"));
+        assert_eq!(dst_header.key_id(), Some("ec-recipient"));
+
+        let dir_encrypter = Dir.encrypter_from_slice(b"0123456789ABCDEF")?;
+        assert!(multi_encrypter.add_recipient(None, &dir_encrypter).is_err());
+
+        let ecdh_es_encrypter = EcdhEs.encrypter_from_pem(&ec_public_key)?;
+        let mut multi_encrypter = JweMultiEncrypter::new();
+        multi_encrypter.add_recipient(None, &ecdh_es_encrypter)?;
+        assert!(jwe::serialize_general_json(
+            src_payload,
+            Some(&protected),
+            None,
+            None,
+            &multi_encrypter
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_match_recipients() -> Result<()> {
+        let mut protected = JweHeader::new();
+        protected.set_content_encryption("A128GCM");
+        let mut rsa_header = JweHeader::new();
+        rsa_header.set_key_id("rsa-recipient");
+        let mut kw_header = JweHeader::new();
+        kw_header.set_key_id("kw-recipient");
+
+        let rsa_encrypter = RsaOaep.encrypter_from_pem(load_file("pem/RSA_2048bit_public.pem")?)?;
+        let kw_encrypter = A128Kw.encrypter_from_slice(b"0123456789ABCDEF")?;
+        let mut multi_encrypter = JweMultiEncrypter::new();
+        multi_encrypter.add_recipient(Some(&rsa_header), &rsa_encrypter)?;
+        multi_encrypter.add_recipient(Some(&kw_header), &kw_encrypter)?;
+
+        let jwe = jwe::serialize_general_json(
+            b"test payload!",
+            Some(&protected),
+            None,
+            None,
+            &multi_encrypter,
+        )?;
+
+        let mut ec_decrypter =
+            EcdhEsA128Kw.decrypter_from_pem(load_file("pem/EC_P-256_private.pem")?)?;
+        ec_decrypter.set_key_id(Some("ec-recipient"));
+        let mut kw_decrypter = A128Kw.decrypter_from_slice(b"0123456789ABCDEF")?;
+        kw_decrypter.set_key_id(Some("kw-recipient"));
+        assert_eq!(
+            jwe::match_recipients(&jwe, &[&ec_decrypter, &kw_decrypter])?,
+            vec![1]
+        );
+
+        kw_decrypter.set_key_id(Some("other"));
+        assert!(jwe::match_recipients(&jwe, &[&ec_decrypter, &kw_decrypter])?.is_empty());
+
+        assert!(jwe::match_recipients("{}", &[&kw_decrypter])?.is_empty());
+        assert!(jwe::match_recipients("[]", &[&kw_decrypter]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_jwe_encrypters_set_alg_header_claim() -> Result<()> {
+        use crate::jwe::Rsa1_5;
+
+        let ec_public_key = load_file("pem/EC_P-256_public.pem")?;
+        let rsa_public_key = load_file("pem/RSA_2048bit_public.pem")?;
+
+        let encrypters: Vec<Box<dyn JweEncrypter>> = vec![
+            Box::new(Dir.encrypter_from_slice(util::rand_bytes(32))?),
+            Box::new(EcdhEs.encrypter_from_pem(&ec_public_key)?),
+            Box::new(EcdhEsA128Kw.encrypter_from_pem(&ec_public_key)?),
+            Box::new(EcdhEsA192Kw.encrypter_from_pem(&ec_public_key)?),
+            Box::new(EcdhEsA256Kw.encrypter_from_pem(&ec_public_key)?),
+            Box::new(A128Kw.encrypter_from_slice(util::rand_bytes(16))?),
+            Box::new(A192Kw.encrypter_from_slice(util::rand_bytes(24))?),
+            Box::new(A256Kw.encrypter_from_slice(util::rand_bytes(32))?),
+            Box::new(A128GcmKw.encrypter_from_slice(util::rand_bytes(16))?),
+            Box::new(A192GcmKw.encrypter_from_slice(util::rand_bytes(24))?),
+            Box::new(A256GcmKw.encrypter_from_slice(util::rand_bytes(32))?),
+            Box::new(Pbes2HS256A128Kw.encrypter_from_slice(b"password")?),
+            Box::new(Pbes2HS384A192Kw.encrypter_from_slice(b"password")?),
+            Box::new(Pbes2HS512A256Kw.encrypter_from_slice(b"password")?),
+            Box::new(Rsa1_5.encrypter_from_pem(&rsa_public_key)?),
+            Box::new(RsaOaep.encrypter_from_pem(&rsa_public_key)?),
+        ];
+
+        for encrypter in &encrypters {
+            let expected = encrypter.algorithm().name();
+
+            let mut header = JweHeader::new();
+            header.set_content_encryption("A128CBC-HS256");
+            header.set_algorithm("unexpected");
+            let jwe = jwe::serialize_compact(b"test payload!", &header, &**encrypter)?;
+            assert_eq!(jwe::peek_header(&jwe)?.algorithm(), Some(expected));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_compact_deserialization_with_key_id() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128CBC-HS256");
+
+        let mut decrypters = Vec::new();
+        let mut encrypters = Vec::new();
+        for kid in &["key-1", "key-2"] {
+            let key = util::rand_bytes(16);
+            let mut encrypter = A128Kw.encrypter_from_slice(&key)?;
+            encrypter.set_key_id(Some(*kid));
+            encrypters.push(encrypter);
+            let mut decrypter = A128Kw.decrypter_from_slice(&key)?;
+            decrypter.set_key_id(Some(*kid));
+            decrypters.push(decrypter);
+        }
+
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypters[1])?;
+        let (payload, dst_header, key_id) =
+            jwe::deserialize_compact_with_key_id_and_selector(&jwe, |header| {
+                for decrypter in &decrypters {
+                    if decrypter.key_id() == header.key_id() {
+                        return Ok(Some(decrypter as &dyn JweDecrypter));
+                    }
+                }
+                Ok(None)
+            })?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(dst_header.key_id(), Some("key-2"));
+        assert_eq!(key_id.as_deref(), Some("key-2"));
+
+        let (_, _, key_id) = jwe::deserialize_compact_with_key_id(&jwe, &decrypters[1])?;
+        assert_eq!(key_id.as_deref(), Some("key-2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_begin_decrypt() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+        header.set_key_id("key-1");
+        header.set_claim("x-large", Some(json!(true)))?;
+
+        let key = util::rand_bytes(16);
+        let encrypter = A128Kw.encrypter_from_slice(&key)?;
+        let decrypter = A128Kw.decrypter_from_slice(&key)?;
+
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+
+        let session = jwe::begin_decrypt(&jwe, &decrypter)?;
+        assert_eq!(session.header().algorithm(), Some("A128KW"));
+        assert_eq!(session.header().key_id(), Some("key-1"));
+        assert_eq!(session.header().claim("x-large"), Some(&json!(true)));
+        assert_eq!(session.content_encryption().name(), "A128GCM");
+        assert_eq!(session.key().len(), 16);
+        assert_eq!(session.iv().map(|val| val.len()), Some(12));
+        let debug = format!("{:?}", session);
+        assert!(debug.contains("[REDACTED]"));
+        assert!(!debug.contains(&format!("{:?}", session.key())));
+        assert_eq!(session.finish()?, b"test payload!");
+
+        // The content is not processed before finish.
+        let parts: Vec<&str> = jwe.split('.').collect();
+        let broken = format!("{}.{}.{}.!!!.{}", parts[0], parts[1], parts[2], parts[4]);
+        let session = jwe::begin_decrypt(&broken, &decrypter)?;
+        assert_eq!(session.header().key_id(), Some("key-1"));
+        assert!(session.finish().is_err());
+
+        let other = A128Kw.decrypter_from_slice(util::rand_bytes(16))?;
+        assert!(jwe::begin_decrypt(&jwe, &other).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_encrypter_and_decrypter_from_jwk() -> Result<()> {
+        let src_payload = b"test payload!";
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128GCM");
+
+        for (public_key, private_key, alg) in &[
+            ("jwk/RSA_public.jwk", "jwk/RSA_private.jwk", "RSA-OAEP"),
+            (
+                "jwk/EC_P-256_public.jwk",
+                "jwk/EC_P-256_private.jwk",
+                "ECDH-ES+A128KW",
+            ),
+        ] {
+            let mut public_key = Jwk::from_slice(load_file(public_key)?)?;
+            public_key.set_algorithm(*alg);
+            let mut private_key = Jwk::from_slice(load_file(private_key)?)?;
+            private_key.set_algorithm(*alg);
+
+            let encrypter = jwe::encrypter_from_jwk(&public_key)?;
+            assert_eq!(encrypter.algorithm().name(), *alg);
+            let jwe = jwe::serialize_compact(src_payload, &header, &*encrypter)?;
+
+            let decrypter = jwe::decrypter_from_jwk(&private_key)?;
+            assert_eq!(decrypter.algorithm().name(), *alg);
+            let (dst_payload, dst_header) = jwe::deserialize_compact(&jwe, &*decrypter)?;
+            assert_eq!(dst_payload, src_payload);
+            assert_eq!(dst_header.algorithm(), Some(*alg));
+        }
+
+        let mut jwk = Jwk::from_slice(load_file("jwk/RSA_public.jwk")?)?;
+        assert!(jwe::encrypter_from_jwk(&jwk).is_err());
+        jwk.set_algorithm("RS256");
+        assert!(jwe::encrypter_from_jwk(&jwk).is_err());
+        jwk.set_algorithm("ECDH-ES+A128KW");
+        assert!(jwe::encrypter_from_jwk(&jwk).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_flattened_json_rfc7516_example() -> Result<()> {
+        // RFC 7516 Appendix A.5 (the A128KW recipient of Appendix A.4).
+        let input = r#"{
+            "protected": "eyJlbmMiOiJBMTI4Q0JDLUhTMjU2In0",
+            "unprotected": {"jku":"https://server.example.com/keys.jwks"},
+            "header": {"alg":"A128KW","kid":"7"},
+            "encrypted_key": "6KB707dM9YTIgHtLvtgWQ8mKwboJW3of9locizkDTHzBC2IlrT1oOQ",
+            "iv": "AxY8DCtDaGlsbGljb3RoZQ",
+            "ciphertext": "KDlTtXchhZTGufMYmOYGS4HffxPSUrfmqCHXaI9wOGY",
+            "tag": "Mz-VPPyU4RlcuYv1IwIvzw"
+        }"#;
+
+        let key = base64::decode_config("GawgguFyGrWKav7AX4VKUg", base64::URL_SAFE_NO_PAD)?;
+        let decrypter = A128Kw.decrypter_from_slice(&key)?;
+        let (payload, header) = jwe::deserialize_json(input, &decrypter)?;
+        assert_eq!(payload, b"Live long and prosper.");
+        assert_eq!(header.key_id(), Some("7"));
+        assert_eq!(
+            header.claim("jku"),
+            Some(&Value::String(
+                "https://server.example.com/keys.jwks".to_string()
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_json_serialization() -> Result<()> {
+        let mut protected = JweHeader::new();
+        protected.set_content_encryption("A128CBC-HS256");
+        let mut unprotected = JweHeader::new();
+        unprotected.set_token_type("JWT");
+        let mut header = JweHeader::new();
+        header.set_key_id("recipient-1");
+
+        let key = b"01234567890123456789012345678901";
+        let encrypter = Dir.encrypter_from_slice(key)?;
+        let json = jwe::serialize_flattened_json(
+            b"test payload!",
+            Some(&protected),
+            Some(&unprotected),
+            Some(&header),
+            Some(b"aad"),
+            &encrypter,
+        )?;
+
+        let decrypter = Dir.decrypter_from_slice(key)?;
+        let (payload, merged) = jwe::deserialize_json(&json, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(merged.key_id(), Some("recipient-1"));
+        assert_eq!(merged.token_type(), Some("JWT"));
+
+        // Convert to the general json serialization.
+        let mut map: Map<String, Value> = serde_json::from_str(&json)?;
+        let mut recipient = Map::new();
+        recipient.insert("header".to_string(), map.remove("header").unwrap());
+        map.insert(
+            "recipients".to_string(),
+            Value::Array(vec![
+                json!({"header": {"kid": "recipient-0"}}),
+                Value::Object(recipient),
+            ]),
+        );
+        let json = serde_json::to_string(&map)?;
+
+        let mut decrypter = Dir.decrypter_from_slice(key)?;
+        decrypter.set_key_id(Some("recipient-1"));
+        let (payload, merged, headers) = jwe::deserialize_json_with_headers(&json, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(merged.key_id(), Some("recipient-1"));
+        assert_eq!(
+            headers.protected().and_then(|val| val.content_encryption()),
+            Some("A128CBC-HS256")
+        );
+        assert_eq!(
+            headers.unprotected().and_then(|val| val.token_type()),
+            Some("JWT")
+        );
+        assert_eq!(
+            headers.header().and_then(|val| val.key_id()),
+            Some("recipient-1")
+        );
+        assert_eq!(headers.protected().and_then(|val| val.key_id()), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_header_sources_after_decode() -> Result<()> {
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128CBC-HS256");
+        src_header.set_x509_certificate_chain(vec![b"cert1".to_vec(), b"cert2".to_vec()]);
+        src_header.set_x509_certificate_sha1_thumbprint(b"thumbprint".to_vec());
+        src_header.set_critical(vec!["x5c"]);
+
+        let mut context = JweContext::new();
+        context.add_acceptable_critical("x5c");
+
+        let key = b"01234567890123456789012345678901";
+        let encrypter = Dir.encrypter_from_slice(key)?;
+        let jwe = context.serialize_compact(b"test payload!", &src_header, &encrypter)?;
+
+        let decrypter = Dir.decrypter_from_slice(key)?;
+        let (_, dst_header) = context.deserialize_compact(&jwe, &decrypter)?;
+
+        assert_eq!(
+            dst_header.x509_certificate_chain(),
+            Some(&vec![b"cert1".to_vec(), b"cert2".to_vec()])
+        );
+        assert_eq!(
+            dst_header.x509_certificate_sha1_thumbprint(),
+            Some(&b"thumbprint".to_vec())
+        );
+        assert_eq!(dst_header.critical(), Some(&vec!["x5c".to_string()]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_header_to_json_ordered() -> Result<()> {
+        let mut header = JweHeader::new();
+        header.set_token_type("JWT");
+        header.set_key_id("key-1");
+        header.set_content_encryption("A128GCM");
+        header.set_algorithm("dir");
+
+        assert_eq!(
+            header.to_json_ordered(&["alg", "enc"]),
+            r#"{"alg":"dir","enc":"A128GCM","kid":"key-1","typ":"JWT"}"#
+        );
+        assert_eq!(
+            header.to_json_ordered(&["typ", "zip", "alg"]),
+            r#"{"typ":"JWT","alg":"dir","enc":"A128GCM","kid":"key-1"}"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwe_header_eq_ignores_sources() -> Result<()> {
+        let mut header1 = JweHeader::new();
+        header1.set_algorithm("dir");
+        header1.set_content_encryption("A128GCM");
+        header1.set_x509_certificate_chain(vec![b"cert1".to_vec(), b"cert2".to_vec()]);
+        header1.set_x509_certificate_sha256_thumbprint(b"thumbprint".to_vec());
+        header1.set_audience(vec!["a", "b"]);
+
+        let mut claims = Map::new();
+        for key in &["aud", "x5t#S256", "x5c", "enc", "alg"] {
+            claims.insert(key.to_string(), header1.claim(key).unwrap().clone());
+        }
+        let header2 = JweHeader::from_map(claims)?;
+        assert_eq!(header1, header2);
+
+        header1.set_key_id("key-1");
+        assert_ne!(header1, header2);
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let mut file = File::open(&pb)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
 }
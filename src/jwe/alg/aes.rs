@@ -9,7 +9,7 @@ use serde_json::Value;
 use crate::jose::JoseError;
 use crate::jwe::{JweAlgorithm, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::Jwk;
-use crate::util;
+use crate::util::{self, SecretBytes};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum AesJweAlgorithm {
@@ -39,7 +39,7 @@ impl AesJweAlgorithm {
 
             Ok(AesJweEncrypter {
                 algorithm: self.clone(),
-                private_key,
+                private_key: private_key.into(),
                 key_id: None,
             })
         })()
@@ -79,7 +79,7 @@ impl AesJweAlgorithm {
 
             Ok(AesJweEncrypter {
                 algorithm: self.clone(),
-                private_key: k,
+                private_key: k.into(),
                 key_id,
             })
         })()
@@ -103,7 +103,7 @@ impl AesJweAlgorithm {
 
             Ok(AesJweDecrypter {
                 algorithm: self.clone(),
-                private_key,
+                private_key: private_key.into(),
                 key_id: None,
             })
         })()
@@ -144,7 +144,7 @@ impl AesJweAlgorithm {
 
             Ok(AesJweDecrypter {
                 algorithm: self.clone(),
-                private_key: k,
+                private_key: k.into(),
                 key_id,
             })
         })()
@@ -169,6 +169,10 @@ impl JweAlgorithm for AesJweAlgorithm {
         }
     }
 
+    fn recommended_key_len(&self) -> Option<usize> {
+        Some(self.key_len())
+    }
+
     fn box_clone(&self) -> Box<dyn JweAlgorithm> {
         Box::new(self.clone())
     }
@@ -191,7 +195,7 @@ impl Deref for AesJweAlgorithm {
 #[derive(Debug, Clone)]
 pub struct AesJweEncrypter {
     algorithm: AesJweAlgorithm,
-    private_key: Vec<u8>,
+    private_key: SecretBytes,
     key_id: Option<String>,
 }
 
@@ -225,15 +229,20 @@ impl JweEncrypter for AesJweEncrypter {
         header: &mut JweHeader,
         key_len: usize,
     ) -> Result<(Cow<[u8]>, Option<Vec<u8>>), JoseError> {
-        (|| -> anyhow::Result<(Cow<[u8]>, Option<Vec<u8>>)> {
+        let key = util::rand_bytes(key_len);
+        let encrypted_key = self.wrap_key(header, &key)?;
+        Ok((Cow::Owned(key), encrypted_key))
+    }
+
+    fn wrap_key(&self, header: &mut JweHeader, key: &[u8]) -> Result<Option<Vec<u8>>, JoseError> {
+        (|| -> anyhow::Result<Option<Vec<u8>>> {
             let aes = match AesKey::new_encrypt(&self.private_key) {
                 Ok(val) => val,
                 Err(_) => bail!("Failed to set encrypt key."),
             };
 
-            let key = util::rand_bytes(key_len);
-            let mut encrypted_key = vec![0; key_len + 8];
-            let len = match aes::wrap_key(&aes, None, &mut encrypted_key, &key) {
+            let mut encrypted_key = vec![0; key.len() + 8];
+            let len = match aes::wrap_key(&aes, None, &mut encrypted_key, key) {
                 Ok(val) => val,
                 Err(_) => bail!("Failed to wrap key."),
             };
@@ -242,7 +251,7 @@ impl JweEncrypter for AesJweEncrypter {
             }
 
             header.set_algorithm(self.algorithm.name());
-            Ok((Cow::Owned(key), Some(encrypted_key)))
+            Ok(Some(encrypted_key))
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
@@ -263,7 +272,7 @@ impl Deref for AesJweEncrypter {
 #[derive(Debug, Clone)]
 pub struct AesJweDecrypter {
     algorithm: AesJweAlgorithm,
-    private_key: Vec<u8>,
+    private_key: SecretBytes,
     key_id: Option<String>,
 }
 
@@ -344,10 +353,27 @@ mod tests {
 
     use super::AesJweAlgorithm;
     use crate::jwe::enc::aes_cbc_hmac::AesCbcHmacJweEncryption;
-    use crate::jwe::JweHeader;
+    use crate::jwe::{JweAlgorithm, JweHeader};
     use crate::jwk::Jwk;
     use crate::util;
 
+    #[test]
+    fn recommended_aes_key_len() -> Result<()> {
+        for (alg, key_len) in &[
+            (AesJweAlgorithm::A128Kw, 16),
+            (AesJweAlgorithm::A192Kw, 24),
+            (AesJweAlgorithm::A256Kw, 32),
+        ] {
+            assert_eq!(alg.recommended_key_len(), Some(*key_len));
+
+            let key = util::rand_bytes(*key_len);
+            alg.encrypter_from_slice(&key)?;
+            alg.decrypter_from_slice(&key)?;
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn encrypt_and_decrypt_aes() -> Result<()> {
         let enc = AesCbcHmacJweEncryption::A128CbcHS256;
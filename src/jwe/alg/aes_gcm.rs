@@ -9,7 +9,12 @@ use serde_json::Value;
 use crate::jose::{JoseError, JoseHeader};
 use crate::jwe::{JweAlgorithm, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::Jwk;
-use crate::util;
+use crate::util::{self, SecretBytes};
+
+/// The size of the iv header claim (96 bits, RFC 7518 4.7.1.1).
+const IV_LEN: usize = 12;
+/// The size of the tag header claim (128 bits, RFC 7518 4.7.1.2).
+const TAG_LEN: usize = 16;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum AesGcmJweAlgorithm {
@@ -39,7 +44,7 @@ impl AesGcmJweAlgorithm {
 
             Ok(AesGcmJweEncrypter {
                 algorithm: self.clone(),
-                private_key,
+                private_key: private_key.into(),
                 key_id: None,
             })
         })()
@@ -79,7 +84,7 @@ impl AesGcmJweAlgorithm {
 
             Ok(AesGcmJweEncrypter {
                 algorithm: self.clone(),
-                private_key: k,
+                private_key: k.into(),
                 key_id,
             })
         })()
@@ -103,7 +108,7 @@ impl AesGcmJweAlgorithm {
 
             Ok(AesGcmJweDecrypter {
                 algorithm: self.clone(),
-                private_key,
+                private_key: private_key.into(),
                 key_id: None,
             })
         })()
@@ -144,7 +149,7 @@ impl AesGcmJweAlgorithm {
 
             Ok(AesGcmJweDecrypter {
                 algorithm: self.clone(),
-                private_key: k,
+                private_key: k.into(),
                 key_id,
             })
         })()
@@ -177,6 +182,10 @@ impl JweAlgorithm for AesGcmJweAlgorithm {
         }
     }
 
+    fn recommended_key_len(&self) -> Option<usize> {
+        Some(self.key_len())
+    }
+
     fn box_clone(&self) -> Box<dyn JweAlgorithm> {
         Box::new(self.clone())
     }
@@ -199,7 +208,7 @@ impl Deref for AesGcmJweAlgorithm {
 #[derive(Debug, Clone)]
 pub struct AesGcmJweEncrypter {
     algorithm: AesGcmJweAlgorithm,
-    private_key: Vec<u8>,
+    private_key: SecretBytes,
     key_id: Option<String>,
 }
 
@@ -233,14 +242,19 @@ impl JweEncrypter for AesGcmJweEncrypter {
         header: &mut JweHeader,
         key_len: usize,
     ) -> Result<(Cow<[u8]>, Option<Vec<u8>>), JoseError> {
-        (|| -> anyhow::Result<(Cow<[u8]>, Option<Vec<u8>>)> {
-            let key = util::rand_bytes(key_len);
-            let iv = util::rand_bytes(32);
+        let key = util::rand_bytes(key_len);
+        let encrypted_key = self.wrap_key(header, &key)?;
+        Ok((Cow::Owned(key), encrypted_key))
+    }
+
+    fn wrap_key(&self, header: &mut JweHeader, key: &[u8]) -> Result<Option<Vec<u8>>, JoseError> {
+        (|| -> anyhow::Result<Option<Vec<u8>>> {
+            let iv = util::rand_bytes(IV_LEN);
 
             let cipher = self.algorithm.cipher();
-            let mut tag = [0; 16];
+            let mut tag = [0; TAG_LEN];
             let encrypted_key =
-                symm::encrypt_aead(cipher, &self.private_key, Some(&iv), b"", &key, &mut tag)?;
+                symm::encrypt_aead(cipher, &self.private_key, Some(&iv), b"", key, &mut tag)?;
 
             header.set_algorithm(self.algorithm.name());
 
@@ -250,7 +264,7 @@ impl JweEncrypter for AesGcmJweEncrypter {
             let tag = base64::encode_config(&tag, base64::URL_SAFE_NO_PAD);
             header.set_claim("tag", Some(Value::String(tag)))?;
 
-            Ok((Cow::Owned(key), Some(encrypted_key)))
+            Ok(Some(encrypted_key))
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
             Ok(err) => err,
@@ -274,7 +288,7 @@ impl Deref for AesGcmJweEncrypter {
 #[derive(Debug, Clone)]
 pub struct AesGcmJweDecrypter {
     algorithm: AesGcmJweAlgorithm,
-    private_key: Vec<u8>,
+    private_key: SecretBytes,
     key_id: Option<String>,
 }
 
@@ -320,12 +334,26 @@ impl JweDecrypter for AesGcmJweDecrypter {
                 Some(_) => bail!("The iv header claim must be string."),
                 None => bail!("The iv header claim is required."),
             };
+            if iv.len() != IV_LEN {
+                bail!(
+                    "The iv header claim must be {} bytes: {} bytes",
+                    IV_LEN,
+                    iv.len()
+                );
+            }
 
             let tag = match header.claim("tag") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(_) => bail!("The tag header claim must be string."),
                 None => bail!("The tag header claim is required."),
             };
+            if tag.len() != TAG_LEN {
+                bail!(
+                    "The tag header claim must be {} bytes: {} bytes",
+                    TAG_LEN,
+                    tag.len()
+                );
+            }
 
             let cipher = self.algorithm.cipher();
             let key = symm::decrypt_aead(
@@ -365,6 +393,7 @@ mod tests {
     use serde_json::json;
 
     use super::AesGcmJweAlgorithm;
+    use crate::jose::{JoseError, JoseHeader};
     use crate::jwe::enc::aes_cbc_hmac::AesCbcHmacJweEncryption;
     use crate::jwe::JweHeader;
     use crate::jwk::Jwk;
@@ -403,4 +432,41 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn decrypt_aes_gcm_with_wrong_length_iv_and_tag() -> Result<()> {
+        let enc = AesCbcHmacJweEncryption::A128CbcHS256;
+        let alg = AesGcmJweAlgorithm::A128GcmKw;
+        let key = util::rand_bytes(alg.key_len());
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        let encrypter = alg.encrypter_from_slice(&key)?;
+        let (_, encrypted_key) = encrypter.encrypt(&mut header, enc.key_len())?;
+        let iv = base64::decode_config(
+            header.claim("iv").unwrap().as_str().unwrap(),
+            base64::URL_SAFE_NO_PAD,
+        )?;
+        assert_eq!(iv.len(), 12);
+
+        let decrypter = alg.decrypter_from_slice(&key)?;
+        decrypter.decrypt(&header, encrypted_key.as_deref(), enc.key_len())?;
+
+        for (name, len) in &[("iv", 16), ("iv", 8), ("tag", 12), ("tag", 32)] {
+            let mut malformed = header.clone();
+            let value = base64::encode_config(util::rand_bytes(*len), base64::URL_SAFE_NO_PAD);
+            malformed.set_claim(name, Some(json!(value)))?;
+
+            let err = decrypter
+                .decrypt(&malformed, encrypted_key.as_deref(), enc.key_len())
+                .unwrap_err();
+            assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+            assert!(err
+                .to_string()
+                .contains(&format!("The {} header claim must be", name)));
+        }
+
+        Ok(())
+    }
 }
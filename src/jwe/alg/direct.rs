@@ -3,11 +3,15 @@ use std::fmt::Display;
 use std::ops::Deref;
 
 use anyhow::bail;
+use openssl::md::Md;
+use openssl::pkey::Id;
+use openssl::pkey_ctx::PkeyCtx;
 use serde_json::Value;
 
 use crate::jose::JoseError;
 use crate::jwe::{JweAlgorithm, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::Jwk;
+use crate::util::SecretBytes;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum DirectJweAlgorithm {
@@ -24,7 +28,7 @@ impl DirectJweAlgorithm {
 
         Ok(DirectJweEncrypter {
             algorithm: self.clone(),
-            cencryption_key: cencryption_key.to_vec(),
+            cencryption_key: cencryption_key.to_vec().into(),
             key_id: None,
         })
     }
@@ -58,13 +62,36 @@ impl DirectJweAlgorithm {
 
             Ok(DirectJweEncrypter {
                 algorithm: self.clone(),
-                cencryption_key: k,
+                cencryption_key: k.into(),
                 key_id,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Return a encrypter that uses a key derived by HKDF-SHA256 (RFC 5869) as the direct key.
+    ///
+    /// # Arguments
+    /// * `master` - A shared master secret.
+    /// * `salt` - A salt value.
+    /// * `info` - A context and application specific information.
+    /// * `key_len` - A length of the derived key.
+    pub fn encrypter_from_hkdf(
+        &self,
+        master: &[u8],
+        salt: &[u8],
+        info: &[u8],
+        key_len: usize,
+    ) -> Result<DirectJweEncrypter, JoseError> {
+        let cencryption_key = Self::hkdf_sha256(master, salt, info, key_len)?;
+
+        Ok(DirectJweEncrypter {
+            algorithm: *self,
+            cencryption_key,
+            key_id: None,
+        })
+    }
+
     pub fn decrypter_from_slice(
         &self,
         input: impl AsRef<[u8]>,
@@ -72,8 +99,8 @@ impl DirectJweAlgorithm {
         let cencryption_key = input.as_ref();
 
         Ok(DirectJweDecrypter {
-            algorithm: self.clone(),
-            cencryption_key: cencryption_key.to_vec(),
+            algorithm: *self,
+            cencryption_key: cencryption_key.to_vec().into(),
             key_id: None,
         })
     }
@@ -107,13 +134,68 @@ impl DirectJweAlgorithm {
             let key_id = jwk.key_id().map(|val| val.to_string());
 
             Ok(DirectJweDecrypter {
-                algorithm: self.clone(),
-                cencryption_key: k,
+                algorithm: *self,
+                cencryption_key: k.into(),
                 key_id,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
+
+    /// Return a decrypter that uses a key derived by HKDF-SHA256 (RFC 5869) as the direct key.
+    ///
+    /// # Arguments
+    /// * `master` - A shared master secret.
+    /// * `salt` - A salt value.
+    /// * `info` - A context and application specific information.
+    /// * `key_len` - A length of the derived key.
+    pub fn decrypter_from_hkdf(
+        &self,
+        master: &[u8],
+        salt: &[u8],
+        info: &[u8],
+        key_len: usize,
+    ) -> Result<DirectJweDecrypter, JoseError> {
+        let cencryption_key = Self::hkdf_sha256(master, salt, info, key_len)?;
+
+        Ok(DirectJweDecrypter {
+            algorithm: *self,
+            cencryption_key,
+            key_id: None,
+        })
+    }
+
+    fn hkdf_sha256(
+        master: &[u8],
+        salt: &[u8],
+        info: &[u8],
+        key_len: usize,
+    ) -> Result<SecretBytes, JoseError> {
+        (|| -> anyhow::Result<SecretBytes> {
+            let md = Md::sha256();
+            if key_len == 0 || key_len > 255 * md.size() {
+                bail!(
+                    "The key size must be between 1 and {}: {}",
+                    255 * md.size(),
+                    key_len
+                );
+            }
+
+            let mut ctx = PkeyCtx::new_id(Id::HKDF)?;
+            ctx.derive_init()?;
+            ctx.set_hkdf_md(md)?;
+            if !salt.is_empty() {
+                ctx.set_hkdf_salt(salt)?;
+            }
+            ctx.set_hkdf_key(master)?;
+            ctx.add_hkdf_info(info)?;
+
+            let mut okm = vec![0; key_len];
+            ctx.derive(Some(&mut okm))?;
+            Ok(SecretBytes::from(okm))
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
 }
 
 impl JweAlgorithm for DirectJweAlgorithm {
@@ -145,7 +227,7 @@ impl Deref for DirectJweAlgorithm {
 #[derive(Debug, Clone)]
 pub struct DirectJweEncrypter {
     algorithm: DirectJweAlgorithm,
-    cencryption_key: Vec<u8>,
+    cencryption_key: SecretBytes,
     key_id: Option<String>,
 }
 
@@ -191,6 +273,10 @@ impl JweEncrypter for DirectJweEncrypter {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    fn content_key_len(&self) -> Option<usize> {
+        Some(self.cencryption_key.len())
+    }
+
     fn box_clone(&self) -> Box<dyn JweEncrypter> {
         Box::new(self.clone())
     }
@@ -207,7 +293,7 @@ impl Deref for DirectJweEncrypter {
 #[derive(Debug, Clone)]
 pub struct DirectJweDecrypter {
     algorithm: DirectJweAlgorithm,
-    cencryption_key: Vec<u8>,
+    cencryption_key: SecretBytes,
     key_id: Option<String>,
 }
 
@@ -277,7 +363,7 @@ mod tests {
 
     use super::DirectJweAlgorithm;
     use crate::jwe::enc::aes_cbc_hmac::AesCbcHmacJweEncryption;
-    use crate::jwe::JweHeader;
+    use crate::jwe::{self, JweHeader};
     use crate::jwk::Jwk;
 
     #[test]
@@ -309,4 +395,87 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encrypt_direct_sets_alg_header_claim() -> Result<()> {
+        let enc = AesCbcHmacJweEncryption::A128CbcHS256;
+        let alg = DirectJweAlgorithm::Dir;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+        assert_eq!(header.algorithm(), None);
+
+        let encrypter = alg.encrypter_from_slice([0; 32])?;
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+        assert_eq!(jwe::peek_header(&jwe)?.algorithm(), Some("dir"));
+
+        header.set_algorithm("A128KW");
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+        assert_eq!(jwe::peek_header(&jwe)?.algorithm(), Some("dir"));
+
+        let encrypter = alg.encrypter_from_slice([0; 16])?;
+        assert!(encrypter.encrypt(&mut header, enc.key_len()).is_err());
+        assert!(jwe::serialize_compact(b"test payload!", &header, &encrypter).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_direct_with_hkdf() -> Result<()> {
+        let enc = AesCbcHmacJweEncryption::A128CbcHS256;
+        let alg = DirectJweAlgorithm::Dir;
+        let master = b"master secret";
+        let salt = b"salt";
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        let encrypter = alg.encrypter_from_hkdf(master, salt, b"session-1", enc.key_len())?;
+        let jwe = jwe::serialize_compact(b"test payload!", &header, &encrypter)?;
+
+        let decrypter = alg.decrypter_from_hkdf(master, salt, b"session-1", enc.key_len())?;
+        let (payload, _) = jwe::deserialize_compact(&jwe, &decrypter)?;
+        assert_eq!(payload, b"test payload!");
+
+        let decrypter = alg.decrypter_from_hkdf(master, salt, b"session-2", enc.key_len())?;
+        assert!(jwe::deserialize_compact(&jwe, &decrypter).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn hkdf_sha256_rfc5869_test_case_1() -> Result<()> {
+        let ikm = [0x0b; 22];
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+
+        let okm = DirectJweAlgorithm::hkdf_sha256(&ikm, &salt, &info, 42)?;
+        assert_eq!(
+            okm.to_vec(),
+            vec![
+                0x3c, 0xb2, 0x5f, 0x25, 0xfa, 0xac, 0xd5, 0x7a, 0x90, 0x43, 0x4f, 0x64, 0xd0, 0x36,
+                0x2f, 0x2a, 0x2d, 0x2d, 0x0a, 0x90, 0xcf, 0x1a, 0x5a, 0x4c, 0x5d, 0xb0, 0x2d, 0x56,
+                0xec, 0xc4, 0xc5, 0xbf, 0x34, 0x00, 0x72, 0x08, 0xd5, 0xb8, 0x87, 0x18, 0x58, 0x65,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn hkdf_sha256_rfc5869_test_case_3() -> Result<()> {
+        let ikm = [0x0b; 22];
+
+        let okm = DirectJweAlgorithm::hkdf_sha256(&ikm, &[], &[], 42)?;
+        assert_eq!(
+            okm.to_vec(),
+            vec![
+                0x8d, 0xa4, 0xe7, 0x75, 0xa5, 0x63, 0xc1, 0x8f, 0x71, 0x5f, 0x80, 0x2a, 0x06, 0x3c,
+                0x5a, 0x31, 0xb8, 0xa1, 0x1f, 0x5c, 0x5e, 0xe1, 0x87, 0x9e, 0xc3, 0x45, 0x4e, 0x5f,
+                0x3c, 0x73, 0x8d, 0x2d, 0x9d, 0x20, 0x13, 0x95, 0xfa, 0xa4, 0xb6, 0x1a, 0x96, 0xc8,
+            ]
+        );
+
+        Ok(())
+    }
 }
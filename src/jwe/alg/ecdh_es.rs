@@ -524,26 +524,16 @@ impl EcdhEsJweEncrypter {
             }
         }
     }
-}
-
-impl JweEncrypter for EcdhEsJweEncrypter {
-    fn algorithm(&self) -> &dyn JweAlgorithm {
-        &self.algorithm
-    }
 
-    fn key_id(&self) -> Option<&str> {
-        match &self.key_id {
-            Some(val) => Some(val.as_ref()),
-            None => None,
-        }
-    }
-
-    fn encrypt(
+    /// Agree on a key with the recipient and return the content encryption key and the
+    /// encrypted key. When `key` is given, it is wrapped instead of a newly generated one.
+    fn agree(
         &self,
         header: &mut JweHeader,
         key_len: usize,
-    ) -> Result<(Cow<[u8]>, Option<Vec<u8>>), JoseError> {
-        (|| -> anyhow::Result<(Cow<[u8]>, Option<Vec<u8>>)> {
+        key: Option<&[u8]>,
+    ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
+        (|| -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
             let apu = match header.claim("apu") {
                 Some(Value::String(val)) => {
                     let apu = base64::decode_config(val, base64::URL_SAFE_NO_PAD)?;
@@ -650,14 +640,17 @@ impl JweEncrypter for EcdhEsJweEncrypter {
             }
 
             if self.algorithm.is_direct() {
-                Ok((Cow::Owned(shared_key), None))
+                Ok((shared_key, None))
             } else {
                 let aes = match AesKey::new_encrypt(&shared_key) {
                     Ok(val) => val,
                     Err(_) => bail!("Failed to set encrypt key."),
                 };
 
-                let key = util::rand_bytes(key_len);
+                let key = match key {
+                    Some(val) => val.to_vec(),
+                    None => util::rand_bytes(key_len),
+                };
                 let mut encrypted_key = vec![0; key.len() + 8];
                 match aes::wrap_key(&aes, None, &mut encrypted_key, &key) {
                     Ok(len) => {
@@ -668,7 +661,7 @@ impl JweEncrypter for EcdhEsJweEncrypter {
                     Err(_) => bail!("Failed to wrap key."),
                 }
 
-                Ok((Cow::Owned(key), Some(encrypted_key)))
+                Ok((key, Some(encrypted_key)))
             }
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
@@ -676,6 +669,40 @@ impl JweEncrypter for EcdhEsJweEncrypter {
             Err(err) => JoseError::InvalidKeyFormat(err),
         })
     }
+}
+
+impl JweEncrypter for EcdhEsJweEncrypter {
+    fn algorithm(&self) -> &dyn JweAlgorithm {
+        &self.algorithm
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        match &self.key_id {
+            Some(val) => Some(val.as_ref()),
+            None => None,
+        }
+    }
+
+    fn encrypt(
+        &self,
+        header: &mut JweHeader,
+        key_len: usize,
+    ) -> Result<(Cow<[u8]>, Option<Vec<u8>>), JoseError> {
+        let (key, encrypted_key) = self.agree(header, key_len, None)?;
+        Ok((Cow::Owned(key), encrypted_key))
+    }
+
+    fn wrap_key(&self, header: &mut JweHeader, key: &[u8]) -> Result<Option<Vec<u8>>, JoseError> {
+        if self.algorithm.is_direct() {
+            return Err(JoseError::InvalidJweFormat(anyhow::Error::msg(format!(
+                "The {} algorithm cannot wrap a given content encryption key.",
+                self.algorithm.name()
+            ))));
+        }
+
+        let (_, encrypted_key) = self.agree(header, key.len(), Some(key))?;
+        Ok(encrypted_key)
+    }
 
     fn box_clone(&self) -> Box<dyn JweEncrypter> {
         Box::new(self.clone())
@@ -808,6 +835,8 @@ impl JweDecrypter for EcdhEsJweDecrypter {
                             vec.extend_from_slice(&x);
                             vec.extend_from_slice(&y);
 
+                            // OpenSSL rejects a point that is not on the curve when importing it,
+                            // so invalid-curve points never reach the key agreement.
                             let pkcs8 = EcKeyPair::to_pkcs8(&vec, true, *curve);
                             PKey::public_key_from_der(&pkcs8)?
                         }
@@ -916,15 +945,17 @@ impl Deref for EcdhEsJweDecrypter {
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use serde_json::{json, Value};
     use std::fs::File;
     use std::io::Read;
     use std::path::PathBuf;
 
-    use super::{EcdhEsJweAlgorithm, EcdhEsKeyType};
+    use super::EcdhEsJweAlgorithm;
+    use crate::jose::JoseHeader;
     use crate::jwe::enc::aes_cbc_hmac::AesCbcHmacJweEncryption;
     use crate::jwe::enc::aes_gcm::AesGcmJweEncryption;
     use crate::jwe::JweHeader;
-    use crate::jwk::{EcCurve, EcxCurve, Jwk};
+    use crate::jwk::Jwk;
 
     #[test]
     fn encrypt_and_decrypt_ecdh_es_with_pkcs8_der() -> Result<()> {
@@ -936,31 +967,28 @@ mod tests {
             EcdhEsJweAlgorithm::EcdhEsA192Kw,
             EcdhEsJweAlgorithm::EcdhEsA256Kw,
         ] {
-            for key in vec![
-                EcdhEsKeyType::Ec(EcCurve::P256),
-                EcdhEsKeyType::Ec(EcCurve::P384),
-                EcdhEsKeyType::Ec(EcCurve::P521),
-                EcdhEsKeyType::Ec(EcCurve::Secp256K1),
-                EcdhEsKeyType::Ecx(EcxCurve::X25519),
-                EcdhEsKeyType::Ecx(EcxCurve::X448),
+            for (private_key, public_key) in vec![
+                (
+                    "der/EC_P-256_pkcs8_private.der",
+                    "der/EC_P-256_spki_public.der",
+                ),
+                (
+                    "der/EC_P-384_pkcs8_private.der",
+                    "der/EC_P-384_spki_public.der",
+                ),
+                (
+                    "der/EC_P-521_pkcs8_private.der",
+                    "der/EC_P-521_spki_public.der",
+                ),
+                (
+                    "der/EC_secp256k1_pkcs8_private.der",
+                    "der/EC_secp256k1_spki_public.der",
+                ),
+                ("der/X25519_pkcs8_private.der", "der/X25519_spki_public.der"),
+                ("der/X448_pkcs8_private.der", "der/X448_spki_public.der"),
             ] {
-                let private_key = load_file(match key {
-                    EcdhEsKeyType::Ec(EcCurve::P256) => "der/EC_P-256_pkcs8_private.der",
-                    EcdhEsKeyType::Ec(EcCurve::P384) => "der/EC_P-384_pkcs8_private.der",
-                    EcdhEsKeyType::Ec(EcCurve::P521) => "der/EC_P-521_pkcs8_private.der",
-                    EcdhEsKeyType::Ec(EcCurve::Secp256K1) => "der/EC_secp256k1_pkcs8_private.der",
-                    EcdhEsKeyType::Ecx(EcxCurve::X25519) => "der/X25519_pkcs8_private.der",
-                    EcdhEsKeyType::Ecx(EcxCurve::X448) => "der/X448_pkcs8_private.der",
-                })?;
-
-                let public_key = load_file(match key {
-                    EcdhEsKeyType::Ec(EcCurve::P256) => "der/EC_P-256_spki_public.der",
-                    EcdhEsKeyType::Ec(EcCurve::P384) => "der/EC_P-384_spki_public.der",
-                    EcdhEsKeyType::Ec(EcCurve::P521) => "der/EC_P-521_spki_public.der",
-                    EcdhEsKeyType::Ec(EcCurve::Secp256K1) => "der/EC_secp256k1_spki_public.der",
-                    EcdhEsKeyType::Ecx(EcxCurve::X25519) => "der/X25519_spki_public.der",
-                    EcdhEsKeyType::Ecx(EcxCurve::X448) => "der/X448_spki_public.der",
-                })?;
+                let private_key = load_file(private_key)?;
+                let public_key = load_file(public_key)?;
 
                 let mut header = JweHeader::new();
                 header.set_content_encryption(enc.name());
@@ -979,6 +1007,70 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn decrypt_ecdh_es_with_invalid_epk() -> Result<()> {
+        let enc = AesCbcHmacJweEncryption::A128CbcHS256;
+        // The direct key agreement has no key wrapping that could hide a bad point.
+        let alg = EcdhEsJweAlgorithm::EcdhEs;
+
+        for (public_key, private_key) in &[
+            ("pem/EC_P-256_public.pem", "pem/EC_P-256_private.pem"),
+            ("pem/X25519_public.pem", "pem/X25519_private.pem"),
+        ] {
+            let mut header = JweHeader::new();
+            header.set_content_encryption(enc.name());
+
+            let encrypter = alg.encrypter_from_pem(load_file(public_key)?)?;
+            let (_, encrypted_key) = encrypter.encrypt(&mut header, enc.key_len())?;
+
+            let decrypter = alg.decrypter_from_pem(load_file(private_key)?)?;
+            decrypter.decrypt(&header, encrypted_key.as_deref(), enc.key_len())?;
+
+            let epk = match header.claim("epk") {
+                Some(Value::Object(val)) => val.clone(),
+                _ => unreachable!(),
+            };
+
+            let mut invalid_epks = Vec::new();
+            if epk.contains_key("y") {
+                // A point that is not on the curve.
+                let mut y =
+                    base64::decode_config(epk["y"].as_str().unwrap(), base64::URL_SAFE_NO_PAD)?;
+                let last = y.len() - 1;
+                y[last] ^= 1;
+                let mut invalid = epk.clone();
+                invalid.insert(
+                    "y".to_string(),
+                    json!(base64::encode_config(&y, base64::URL_SAFE_NO_PAD)),
+                );
+                invalid_epks.push(invalid);
+
+                // The coordinates of the identity cannot be on the curve.
+                let zero = base64::encode_config([0u8; 32], base64::URL_SAFE_NO_PAD);
+                let mut invalid = epk.clone();
+                invalid.insert("x".to_string(), json!(zero));
+                invalid.insert("y".to_string(), json!(zero));
+                invalid_epks.push(invalid);
+            } else {
+                // A point of small order.
+                let zero = base64::encode_config([0u8; 32], base64::URL_SAFE_NO_PAD);
+                let mut invalid = epk.clone();
+                invalid.insert("x".to_string(), json!(zero));
+                invalid_epks.push(invalid);
+            }
+
+            for invalid in invalid_epks {
+                let mut invalid_header = header.clone();
+                invalid_header.set_claim("epk", Some(Value::Object(invalid)))?;
+                assert!(decrypter
+                    .decrypt(&invalid_header, encrypted_key.as_deref(), enc.key_len())
+                    .is_err());
+            }
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn encrypt_and_decrypt_ecdh_es_with_pem() -> Result<()> {
         let enc = AesCbcHmacJweEncryption::A128CbcHS256;
@@ -989,31 +1081,19 @@ mod tests {
             EcdhEsJweAlgorithm::EcdhEsA192Kw,
             EcdhEsJweAlgorithm::EcdhEsA256Kw,
         ] {
-            for key in vec![
-                EcdhEsKeyType::Ec(EcCurve::P256),
-                EcdhEsKeyType::Ec(EcCurve::P384),
-                EcdhEsKeyType::Ec(EcCurve::P521),
-                EcdhEsKeyType::Ec(EcCurve::Secp256K1),
-                EcdhEsKeyType::Ecx(EcxCurve::X25519),
-                EcdhEsKeyType::Ecx(EcxCurve::X448),
+            for (private_key, public_key) in vec![
+                ("pem/EC_P-256_private.pem", "pem/EC_P-256_public.pem"),
+                ("pem/EC_P-384_private.pem", "pem/EC_P-384_public.pem"),
+                ("pem/EC_P-521_private.pem", "pem/EC_P-521_public.pem"),
+                (
+                    "pem/EC_secp256k1_private.pem",
+                    "pem/EC_secp256k1_public.pem",
+                ),
+                ("pem/X25519_private.pem", "pem/X25519_public.pem"),
+                ("pem/X448_private.pem", "pem/X448_public.pem"),
             ] {
-                let private_key = load_file(match key {
-                    EcdhEsKeyType::Ec(EcCurve::P256) => "pem/EC_P-256_private.pem",
-                    EcdhEsKeyType::Ec(EcCurve::P384) => "pem/EC_P-384_private.pem",
-                    EcdhEsKeyType::Ec(EcCurve::P521) => "pem/EC_P-521_private.pem",
-                    EcdhEsKeyType::Ec(EcCurve::Secp256K1) => "pem/EC_secp256k1_private.pem",
-                    EcdhEsKeyType::Ecx(EcxCurve::X25519) => "pem/X25519_private.pem",
-                    EcdhEsKeyType::Ecx(EcxCurve::X448) => "pem/X448_private.pem",
-                })?;
-
-                let public_key = load_file(match key {
-                    EcdhEsKeyType::Ec(EcCurve::P256) => "pem/EC_P-256_public.pem",
-                    EcdhEsKeyType::Ec(EcCurve::P384) => "pem/EC_P-384_public.pem",
-                    EcdhEsKeyType::Ec(EcCurve::P521) => "pem/EC_P-521_public.pem",
-                    EcdhEsKeyType::Ec(EcCurve::Secp256K1) => "pem/EC_secp256k1_public.pem",
-                    EcdhEsKeyType::Ecx(EcxCurve::X25519) => "pem/X25519_public.pem",
-                    EcdhEsKeyType::Ecx(EcxCurve::X448) => "pem/X448_public.pem",
-                })?;
+                let private_key = load_file(private_key)?;
+                let public_key = load_file(public_key)?;
 
                 let mut header = JweHeader::new();
                 header.set_content_encryption(enc.name());
@@ -1042,33 +1122,31 @@ mod tests {
             EcdhEsJweAlgorithm::EcdhEsA192Kw,
             EcdhEsJweAlgorithm::EcdhEsA256Kw,
         ] {
-            for key in vec![
-                EcdhEsKeyType::Ec(EcCurve::P256),
-                EcdhEsKeyType::Ec(EcCurve::P384),
-                EcdhEsKeyType::Ec(EcCurve::P521),
-                EcdhEsKeyType::Ec(EcCurve::Secp256K1),
-                EcdhEsKeyType::Ecx(EcxCurve::X25519),
-                EcdhEsKeyType::Ecx(EcxCurve::X448),
+            for (private_key, public_key) in vec![
+                (
+                    "pem/EC_P-256_traditional_private.pem",
+                    "pem/EC_P-256_public.pem",
+                ),
+                (
+                    "pem/EC_P-384_traditional_private.pem",
+                    "pem/EC_P-384_public.pem",
+                ),
+                (
+                    "pem/EC_P-521_traditional_private.pem",
+                    "pem/EC_P-521_public.pem",
+                ),
+                (
+                    "pem/EC_secp256k1_traditional_private.pem",
+                    "pem/EC_secp256k1_public.pem",
+                ),
+                (
+                    "pem/X25519_traditional_private.pem",
+                    "pem/X25519_public.pem",
+                ),
+                ("pem/X448_traditional_private.pem", "pem/X448_public.pem"),
             ] {
-                let private_key = load_file(match key {
-                    EcdhEsKeyType::Ec(EcCurve::P256) => "pem/EC_P-256_traditional_private.pem",
-                    EcdhEsKeyType::Ec(EcCurve::P384) => "pem/EC_P-384_traditional_private.pem",
-                    EcdhEsKeyType::Ec(EcCurve::P521) => "pem/EC_P-521_traditional_private.pem",
-                    EcdhEsKeyType::Ec(EcCurve::Secp256K1) => {
-                        "pem/EC_secp256k1_traditional_private.pem"
-                    }
-                    EcdhEsKeyType::Ecx(EcxCurve::X25519) => "pem/X25519_traditional_private.pem",
-                    EcdhEsKeyType::Ecx(EcxCurve::X448) => "pem/X448_traditional_private.pem",
-                })?;
-
-                let public_key = load_file(match key {
-                    EcdhEsKeyType::Ec(EcCurve::P256) => "pem/EC_P-256_public.pem",
-                    EcdhEsKeyType::Ec(EcCurve::P384) => "pem/EC_P-384_public.pem",
-                    EcdhEsKeyType::Ec(EcCurve::P521) => "pem/EC_P-521_public.pem",
-                    EcdhEsKeyType::Ec(EcCurve::Secp256K1) => "pem/EC_secp256k1_public.pem",
-                    EcdhEsKeyType::Ecx(EcxCurve::X25519) => "pem/X25519_public.pem",
-                    EcdhEsKeyType::Ecx(EcxCurve::X448) => "pem/X448_public.pem",
-                })?;
+                let private_key = load_file(private_key)?;
+                let public_key = load_file(public_key)?;
 
                 let mut header = JweHeader::new();
                 header.set_content_encryption(enc.name());
@@ -1097,31 +1175,19 @@ mod tests {
             EcdhEsJweAlgorithm::EcdhEsA192Kw,
             EcdhEsJweAlgorithm::EcdhEsA256Kw,
         ] {
-            for key in vec![
-                EcdhEsKeyType::Ec(EcCurve::P256),
-                EcdhEsKeyType::Ec(EcCurve::P384),
-                EcdhEsKeyType::Ec(EcCurve::P521),
-                EcdhEsKeyType::Ec(EcCurve::Secp256K1),
-                EcdhEsKeyType::Ecx(EcxCurve::X25519),
-                EcdhEsKeyType::Ecx(EcxCurve::X448),
+            for (private_key, public_key) in vec![
+                ("jwk/EC_P-256_private.jwk", "jwk/EC_P-256_public.jwk"),
+                ("jwk/EC_P-384_private.jwk", "jwk/EC_P-384_public.jwk"),
+                ("jwk/EC_P-521_private.jwk", "jwk/EC_P-521_public.jwk"),
+                (
+                    "jwk/EC_secp256k1_private.jwk",
+                    "jwk/EC_secp256k1_public.jwk",
+                ),
+                ("jwk/OKP_X25519_private.jwk", "jwk/OKP_X25519_public.jwk"),
+                ("jwk/OKP_X448_private.jwk", "jwk/OKP_X448_public.jwk"),
             ] {
-                let private_key = load_file(match key {
-                    EcdhEsKeyType::Ec(EcCurve::P256) => "jwk/EC_P-256_private.jwk",
-                    EcdhEsKeyType::Ec(EcCurve::P384) => "jwk/EC_P-384_private.jwk",
-                    EcdhEsKeyType::Ec(EcCurve::P521) => "jwk/EC_P-521_private.jwk",
-                    EcdhEsKeyType::Ec(EcCurve::Secp256K1) => "jwk/EC_secp256k1_private.jwk",
-                    EcdhEsKeyType::Ecx(EcxCurve::X25519) => "jwk/OKP_X25519_private.jwk",
-                    EcdhEsKeyType::Ecx(EcxCurve::X448) => "jwk/OKP_X448_private.jwk",
-                })?;
-
-                let public_key = load_file(match key {
-                    EcdhEsKeyType::Ec(EcCurve::P256) => "jwk/EC_P-256_public.jwk",
-                    EcdhEsKeyType::Ec(EcCurve::P384) => "jwk/EC_P-384_public.jwk",
-                    EcdhEsKeyType::Ec(EcCurve::P521) => "jwk/EC_P-521_public.jwk",
-                    EcdhEsKeyType::Ec(EcCurve::Secp256K1) => "jwk/EC_secp256k1_public.jwk",
-                    EcdhEsKeyType::Ecx(EcxCurve::X25519) => "jwk/OKP_X25519_public.jwk",
-                    EcdhEsKeyType::Ecx(EcxCurve::X448) => "jwk/OKP_X448_public.jwk",
-                })?;
+                let private_key = load_file(private_key)?;
+                let public_key = load_file(public_key)?;
 
                 let mut header = JweHeader::new();
                 header.set_content_encryption(enc.name());
@@ -12,7 +12,7 @@ use serde_json::{Number, Value};
 use crate::jose::{JoseError, JoseHeader};
 use crate::jwe::{JweAlgorithm, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::Jwk;
-use crate::util;
+use crate::util::{self, SecretBytes};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Pbes2HmacJweAlgorithm {
@@ -38,7 +38,8 @@ impl Pbes2HmacJweAlgorithm {
 
             Ok(Pbes2HmacJweEncrypter {
                 algorithm: self.clone(),
-                private_key,
+                private_key: private_key.into(),
+                salt_len: 8,
                 key_id: None,
             })
         })()
@@ -78,7 +79,8 @@ impl Pbes2HmacJweAlgorithm {
 
             Ok(Pbes2HmacJweEncrypter {
                 algorithm: self.clone(),
-                private_key: k,
+                private_key: k.into(),
+                salt_len: 8,
                 key_id,
             })
         })()
@@ -98,7 +100,7 @@ impl Pbes2HmacJweAlgorithm {
 
             Ok(Pbes2HmacJweDecrypter {
                 algorithm: self.clone(),
-                private_key,
+                private_key: private_key.into(),
                 key_id: None,
             })
         })()
@@ -139,7 +141,7 @@ impl Pbes2HmacJweAlgorithm {
 
             Ok(Pbes2HmacJweDecrypter {
                 algorithm: self.clone(),
-                private_key: k,
+                private_key: k.into(),
                 key_id,
             })
         })()
@@ -194,11 +196,36 @@ impl Deref for Pbes2HmacJweAlgorithm {
 #[derive(Debug, Clone)]
 pub struct Pbes2HmacJweEncrypter {
     algorithm: Pbes2HmacJweAlgorithm,
-    private_key: Vec<u8>,
+    private_key: SecretBytes,
+    salt_len: usize,
     key_id: Option<String>,
 }
 
 impl Pbes2HmacJweEncrypter {
+    /// Set a length of the salt that is generated as p2s header claim.
+    ///
+    /// The default value is 8, and it must be 8 or more (RFC 7518 4.8.1.1).
+    ///
+    /// # Arguments
+    ///
+    /// * `salt_len` - A length of the salt.
+    pub fn set_salt_len(&mut self, salt_len: usize) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            if salt_len < 8 {
+                bail!("The salt length must be 8 or more: {}", salt_len);
+            }
+
+            self.salt_len = salt_len;
+            Ok(())
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+
+    /// Return the length of the salt that is generated as p2s header claim.
+    pub fn salt_len(&self) -> usize {
+        self.salt_len
+    }
+
     pub fn set_key_id(&mut self, key_id: Option<impl Into<String>>) {
         match key_id {
             Some(val) => {
@@ -228,12 +255,18 @@ impl JweEncrypter for Pbes2HmacJweEncrypter {
         header: &mut JweHeader,
         key_len: usize,
     ) -> Result<(Cow<[u8]>, Option<Vec<u8>>), JoseError> {
-        (|| -> anyhow::Result<(Cow<[u8]>, Option<Vec<u8>>)> {
+        let key = util::rand_bytes(key_len);
+        let encrypted_key = self.wrap_key(header, &key)?;
+        Ok((Cow::Owned(key), encrypted_key))
+    }
+
+    fn wrap_key(&self, header: &mut JweHeader, key: &[u8]) -> Result<Option<Vec<u8>>, JoseError> {
+        (|| -> anyhow::Result<Option<Vec<u8>>> {
             let p2s = match header.claim("p2s") {
                 Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
                 Some(_) => bail!("The p2s header claim must be string."),
                 None => {
-                    let p2s = util::rand_bytes(8);
+                    let p2s = util::rand_bytes(self.salt_len);
                     let p2s_b64 = base64::encode_config(&p2s, base64::URL_SAFE_NO_PAD);
                     header.set_claim("p2s", Some(Value::String(p2s_b64)))?;
                     p2s
@@ -261,9 +294,8 @@ impl JweEncrypter for Pbes2HmacJweEncrypter {
                 Err(_) => bail!("Failed to set encrypt key."),
             };
 
-            let key = util::rand_bytes(key_len);
-            let mut encrypted_key = vec![0; key_len + 8];
-            let len = match aes::wrap_key(&aes, None, &mut encrypted_key, &key) {
+            let mut encrypted_key = vec![0; key.len() + 8];
+            let len = match aes::wrap_key(&aes, None, &mut encrypted_key, key) {
                 Ok(val) => val,
                 Err(_) => bail!("Failed to wrap key."),
             };
@@ -272,7 +304,7 @@ impl JweEncrypter for Pbes2HmacJweEncrypter {
             }
 
             header.set_algorithm(self.algorithm.name());
-            Ok((Cow::Owned(key), Some(encrypted_key)))
+            Ok(Some(encrypted_key))
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
@@ -293,7 +325,7 @@ impl Deref for Pbes2HmacJweEncrypter {
 #[derive(Debug, Clone)]
 pub struct Pbes2HmacJweDecrypter {
     algorithm: Pbes2HmacJweAlgorithm,
-    private_key: Vec<u8>,
+    private_key: SecretBytes,
     key_id: Option<String>,
 }
 
@@ -388,9 +420,10 @@ impl Deref for Pbes2HmacJweDecrypter {
 mod tests {
     use anyhow::Result;
     use base64;
-    use serde_json::json;
+    use serde_json::{json, Value};
 
     use super::Pbes2HmacJweAlgorithm;
+    use crate::jose::JoseHeader;
     use crate::jwe::enc::aes_cbc_hmac::AesCbcHmacJweEncryption;
     use crate::jwe::JweHeader;
     use crate::jwk::Jwk;
@@ -430,4 +463,32 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encrypt_and_decrypt_pbes2_hmac_with_salt_len() -> Result<()> {
+        let enc = AesCbcHmacJweEncryption::A128CbcHS256;
+        let alg = Pbes2HmacJweAlgorithm::Pbes2HS256A128Kw;
+        let key = b"password";
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        let mut encrypter = alg.encrypter_from_slice(key)?;
+        assert_eq!(encrypter.salt_len(), 8);
+        assert!(encrypter.set_salt_len(7).is_err());
+        encrypter.set_salt_len(16)?;
+        let (src_key, encrypted_key) = encrypter.encrypt(&mut header, enc.key_len())?;
+
+        let p2s = match header.claim("p2s") {
+            Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
+            _ => unreachable!(),
+        };
+        assert_eq!(p2s.len(), 16);
+
+        let decrypter = alg.decrypter_from_slice(key)?;
+        let dst_key = decrypter.decrypt(&header, encrypted_key.as_deref(), enc.key_len())?;
+        assert_eq!(&src_key, &dst_key);
+
+        Ok(())
+    }
 }
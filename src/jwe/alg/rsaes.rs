@@ -3,8 +3,8 @@ use std::fmt::Display;
 use std::ops::Deref;
 
 use anyhow::bail;
+use openssl::encrypt::{Decrypter, Encrypter};
 use openssl::pkey::{PKey, Private, Public};
-use openssl::rand;
 use openssl::rsa::Padding;
 use serde_json::Value;
 
@@ -123,6 +123,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                oaep_label: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -156,6 +157,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                oaep_label: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -214,6 +216,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id,
+                oaep_label: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -228,6 +231,7 @@ impl RsaesJweAlgorithm {
             algorithm: self.clone(),
             private_key: keypair.into_private_key(),
             key_id: None,
+            oaep_label: None,
         })
     }
 
@@ -240,6 +244,7 @@ impl RsaesJweAlgorithm {
             algorithm: self.clone(),
             private_key: keypair.into_private_key(),
             key_id: None,
+            oaep_label: None,
         })
     }
 
@@ -271,6 +276,7 @@ impl RsaesJweAlgorithm {
                 algorithm: self.clone(),
                 private_key,
                 key_id,
+                oaep_label: None,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -313,6 +319,7 @@ pub struct RsaesJweEncrypter {
     algorithm: RsaesJweAlgorithm,
     public_key: PKey<Public>,
     key_id: Option<String>,
+    oaep_label: Option<Vec<u8>>,
 }
 
 impl RsaesJweEncrypter {
@@ -326,6 +333,17 @@ impl RsaesJweEncrypter {
             }
         }
     }
+
+    /// Set a label used by RSA-OAEP padding.
+    ///
+    /// The label is ignored by RSA1_5.
+    pub fn set_oaep_label(&mut self, label: Vec<u8>) {
+        self.oaep_label = Some(label);
+    }
+
+    pub fn oaep_label(&self) -> Option<&[u8]> {
+        self.oaep_label.as_deref()
+    }
 }
 
 impl JweEncrypter for RsaesJweEncrypter {
@@ -340,29 +358,37 @@ impl JweEncrypter for RsaesJweEncrypter {
         }
     }
 
-    #[allow(deprecated)]
     fn encrypt(
         &self,
         header: &mut JweHeader,
         key_len: usize,
     ) -> Result<(Cow<[u8]>, Option<Vec<u8>>), JoseError> {
-        (|| -> anyhow::Result<(Cow<[u8]>, Option<Vec<u8>>)> {
-            header.set_algorithm(self.algorithm.name());
+        let key = util::rand_bytes(key_len);
+        let encrypted_key = self.wrap_key(header, &key)?;
+        Ok((Cow::Owned(key), encrypted_key))
+    }
 
-            let mut key = vec![0; key_len];
-            rand::rand_bytes(&mut key)?;
+    #[allow(deprecated)]
+    fn wrap_key(&self, header: &mut JweHeader, key: &[u8]) -> Result<Option<Vec<u8>>, JoseError> {
+        (|| -> anyhow::Result<Option<Vec<u8>>> {
+            header.set_algorithm(self.algorithm.name());
 
             let rsa = self.public_key.rsa()?;
             let encrypted_key = match self.algorithm {
                 RsaesJweAlgorithm::Rsa1_5 => {
                     let mut encrypted_key = vec![0; rsa.size() as usize];
-                    let len = rsa.public_encrypt(&key, &mut encrypted_key, Padding::PKCS1)?;
+                    let len = rsa.public_encrypt(key, &mut encrypted_key, Padding::PKCS1)?;
                     encrypted_key.truncate(len);
                     encrypted_key
                 }
                 RsaesJweAlgorithm::RsaOaep => {
-                    let mut encrypted_key = vec![0; rsa.size() as usize];
-                    let len = rsa.public_encrypt(&key, &mut encrypted_key, Padding::PKCS1_OAEP)?;
+                    let mut encrypter = Encrypter::new(&self.public_key)?;
+                    encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                    if let Some(label) = &self.oaep_label {
+                        encrypter.set_rsa_oaep_label(label)?;
+                    }
+                    let mut encrypted_key = vec![0; encrypter.encrypt_len(key)?];
+                    let len = encrypter.encrypt(key, &mut encrypted_key)?;
                     encrypted_key.truncate(len);
                     encrypted_key
                 }
@@ -377,7 +403,7 @@ impl JweEncrypter for RsaesJweEncrypter {
                 }
             };
 
-            Ok((Cow::Owned(key), Some(encrypted_key)))
+            Ok(Some(encrypted_key))
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
@@ -400,6 +426,7 @@ pub struct RsaesJweDecrypter {
     algorithm: RsaesJweAlgorithm,
     private_key: PKey<Private>,
     key_id: Option<String>,
+    oaep_label: Option<Vec<u8>>,
 }
 
 impl RsaesJweDecrypter {
@@ -413,6 +440,17 @@ impl RsaesJweDecrypter {
             }
         }
     }
+
+    /// Set a label used by RSA-OAEP padding.
+    ///
+    /// The label is ignored by RSA1_5.
+    pub fn set_oaep_label(&mut self, label: Vec<u8>) {
+        self.oaep_label = Some(label);
+    }
+
+    pub fn oaep_label(&self) -> Option<&[u8]> {
+        self.oaep_label.as_deref()
+    }
 }
 
 impl JweDecrypter for RsaesJweDecrypter {
@@ -449,8 +487,13 @@ impl JweDecrypter for RsaesJweDecrypter {
                     key
                 }
                 RsaesJweAlgorithm::RsaOaep => {
-                    let mut key = vec![0; rsa.size() as usize];
-                    let len = rsa.private_decrypt(&encrypted_key, &mut key, Padding::PKCS1_OAEP)?;
+                    let mut decrypter = Decrypter::new(&self.private_key)?;
+                    decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                    if let Some(label) = &self.oaep_label {
+                        decrypter.set_rsa_oaep_label(label)?;
+                    }
+                    let mut key = vec![0; decrypter.decrypt_len(encrypted_key)?];
+                    let len = decrypter.decrypt(encrypted_key, &mut key)?;
                     key.truncate(len);
                     key
                 }
@@ -528,6 +571,43 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn encrypt_and_decrypt_rsaes_with_oaep_label() -> Result<()> {
+        let enc = AesCbcHmacJweEncryption::A128CbcHS256;
+        let alg = RsaesJweAlgorithm::RsaOaep;
+
+        let private_key = load_file("jwk/RSA_private.jwk")?;
+        let private_key = Jwk::from_slice(&private_key)?;
+
+        let public_key = load_file("jwk/RSA_public.jwk")?;
+        let public_key = Jwk::from_slice(&public_key)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption(enc.name());
+
+        let mut encrypter = alg.encrypter_from_jwk(&public_key)?;
+        encrypter.set_oaep_label(b"label".to_vec());
+        let (src_key, encrypted_key) = encrypter.encrypt(&mut header, enc.key_len())?;
+
+        let mut decrypter = alg.decrypter_from_jwk(&private_key)?;
+        decrypter.set_oaep_label(b"label".to_vec());
+        let dst_key = decrypter.decrypt(&header, encrypted_key.as_deref(), enc.key_len())?;
+        assert_eq!(&src_key, &dst_key);
+
+        let mut decrypter = alg.decrypter_from_jwk(&private_key)?;
+        decrypter.set_oaep_label(b"other".to_vec());
+        assert!(decrypter
+            .decrypt(&header, encrypted_key.as_deref(), enc.key_len())
+            .is_err());
+
+        let decrypter = alg.decrypter_from_jwk(&private_key)?;
+        assert!(decrypter
+            .decrypt(&header, encrypted_key.as_deref(), enc.key_len())
+            .is_err());
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
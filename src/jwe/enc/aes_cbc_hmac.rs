@@ -3,6 +3,7 @@ use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::hash::MessageDigest;
+use openssl::memcmp;
 use openssl::pkey::{PKey, Private};
 use openssl::sign::Signer;
 use openssl::symm::{self, Cipher};
@@ -29,6 +30,40 @@ impl AesCbcHmacJweEncryption {
         }
     }
 
+    /// Return the length of MAC_KEY that is the first part of the content encryption key.
+    pub fn mac_key_len(&self) -> usize {
+        match self {
+            Self::A128CbcHS256 => 16,
+            Self::A192CbcHS384 => 24,
+            Self::A256CbcHS512 => 32,
+        }
+    }
+
+    /// Return the length of ENC_KEY that is the second part of the content encryption key.
+    pub fn enc_key_len(&self) -> usize {
+        match self {
+            Self::A128CbcHS256 => 16,
+            Self::A192CbcHS384 => 24,
+            Self::A256CbcHS512 => 32,
+        }
+    }
+
+    fn split_key<'a>(&self, key: &'a [u8]) -> anyhow::Result<(&'a [u8], &'a [u8])> {
+        let expected_len = self.mac_key_len() + self.enc_key_len();
+        if key.len() != expected_len {
+            bail!(
+                "The length of content encryption key must be {} ({} bytes MAC key and {} bytes ENC key) for {}: {}",
+                expected_len,
+                self.mac_key_len(),
+                self.enc_key_len(),
+                self.name(),
+                key.len()
+            );
+        }
+
+        Ok(key.split_at(self.mac_key_len()))
+    }
+
     fn calcurate_tag(
         &self,
         aad: &[u8],
@@ -55,7 +90,7 @@ impl AesCbcHmacJweEncryption {
                 signer.update(val)?;
             }
             signer.update(ciphertext)?;
-            signer.update(&aad.len().to_be_bytes())?;
+            signer.update(&((aad.len() as u64) * 8).to_be_bytes())?;
             let mut signature = signer.sign_to_vec()?;
             signature.truncate(tlen);
             Ok(signature)
@@ -76,11 +111,7 @@ impl JweContentEncryption for AesCbcHmacJweEncryption {
     }
 
     fn key_len(&self) -> usize {
-        match self {
-            Self::A128CbcHS256 => 16 + 16,
-            Self::A192CbcHS384 => 16 + 24,
-            Self::A256CbcHS512 => 16 + 32,
-        }
+        self.mac_key_len() + self.enc_key_len()
     }
 
     fn iv_len(&self) -> usize {
@@ -95,17 +126,7 @@ impl JweContentEncryption for AesCbcHmacJweEncryption {
         aad: &[u8],
     ) -> Result<(Vec<u8>, Option<Vec<u8>>), JoseError> {
         let (encrypted_message, mac_key) = (|| -> anyhow::Result<(Vec<u8>, &[u8])> {
-            let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
-            }
-
-            let mac_key = &key[0..16];
-            let enc_key = &key[16..];
+            let (mac_key, enc_key) = self.split_key(key)?;
 
             let cipher = self.cipher();
             let encrypted_message = symm::encrypt(cipher, enc_key, iv, message)?;
@@ -113,7 +134,7 @@ impl JweContentEncryption for AesCbcHmacJweEncryption {
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))?;
 
-        let tag = self.calcurate_tag(aad, iv, message, mac_key)?;
+        let tag = self.calcurate_tag(aad, iv, &encrypted_message, mac_key)?;
 
         Ok((encrypted_message, Some(tag)))
     }
@@ -126,24 +147,7 @@ impl JweContentEncryption for AesCbcHmacJweEncryption {
         aad: &[u8],
         tag: Option<&[u8]>,
     ) -> Result<Vec<u8>, JoseError> {
-        let (message, mac_key) = (|| -> anyhow::Result<(Vec<u8>, &[u8])> {
-            let expected_len = self.key_len();
-            if key.len() != expected_len {
-                bail!(
-                    "The length of content encryption key must be {}: {}",
-                    expected_len,
-                    key.len()
-                );
-            }
-
-            let mac_key = &key[0..16];
-            let enc_key = &key[16..];
-
-            let cipher = self.cipher();
-            let message = symm::decrypt(cipher, enc_key, iv, encrypted_message)?;
-            Ok((message, mac_key))
-        })()
-        .map_err(|err| JoseError::InvalidKeyFormat(err))?;
+        let (mac_key, enc_key) = self.split_key(key).map_err(JoseError::InvalidKeyFormat)?;
 
         (|| -> anyhow::Result<()> {
             let tag = match tag {
@@ -151,8 +155,8 @@ impl JweContentEncryption for AesCbcHmacJweEncryption {
                 None => bail!("A tag value is required."),
             };
 
-            let calc_tag = self.calcurate_tag(aad, iv, &message, mac_key)?;
-            if calc_tag.as_slice() != tag {
+            let calc_tag = self.calcurate_tag(aad, iv, encrypted_message, mac_key)?;
+            if calc_tag.len() != tag.len() || !memcmp::eq(&calc_tag, tag) {
                 bail!("The tag doesn't match.");
             }
 
@@ -160,6 +164,13 @@ impl JweContentEncryption for AesCbcHmacJweEncryption {
         })()
         .map_err(|err| JoseError::InvalidSignature(err))?;
 
+        let message = (|| -> anyhow::Result<Vec<u8>> {
+            let cipher = self.cipher();
+            let message = symm::decrypt(cipher, enc_key, iv, encrypted_message)?;
+            Ok(message)
+        })()
+        .map_err(JoseError::InvalidKeyFormat)?;
+
         Ok(message)
     }
 
@@ -187,6 +198,8 @@ mod tests {
     use anyhow::Result;
 
     use super::AesCbcHmacJweEncryption;
+    use crate::jose::JoseError;
+    use crate::jwe::JweContentEncryption;
     use crate::util;
 
     #[test]
@@ -216,4 +229,113 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn validate_aes_cbc_hmac_key_len() -> Result<()> {
+        let message = b"abcde12345";
+        let aad = b"test";
+
+        for (enc, key_len) in &[
+            (AesCbcHmacJweEncryption::A128CbcHS256, 32),
+            (AesCbcHmacJweEncryption::A192CbcHS384, 48),
+            (AesCbcHmacJweEncryption::A256CbcHS512, 64),
+        ] {
+            assert_eq!(enc.key_len(), *key_len);
+            assert_eq!(enc.mac_key_len() + enc.enc_key_len(), *key_len);
+
+            let iv = util::rand_bytes(enc.iv_len());
+
+            let key = util::rand_bytes(*key_len);
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(&iv), message, aad)?;
+            enc.decrypt(&key, Some(&iv), &encrypted_message, aad, tag.as_deref())?;
+
+            for invalid_len in &[key_len - 1, key_len + 1, key_len - 16] {
+                let key = util::rand_bytes(*invalid_len);
+                let err = enc.encrypt(&key, Some(&iv), message, aad).unwrap_err();
+                assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+                assert!(enc
+                    .decrypt(&key, Some(&iv), &encrypted_message, aad, tag.as_deref())
+                    .is_err());
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_aes_cbc_hmac_rfc7518_test_vectors() -> Result<()> {
+        // RFC 7518 Appendix B.1 and B.3
+        let message = b"A cipher system must not be required to be secret, and it must be able to fall into the hands of the enemy without inconvenience";
+        let aad = b"The second principle of Auguste Kerckhoffs";
+        let iv = &[
+            0x1a, 0xf3, 0x8c, 0x2d, 0xc2, 0xb9, 0x6f, 0xfd, 0xd8, 0x66, 0x94, 0x09, 0x23, 0x41,
+            0xbc, 0x04,
+        ];
+
+        let vectors: Vec<(AesCbcHmacJweEncryption, &[u8], &[u8])> = vec![
+            (
+                AesCbcHmacJweEncryption::A128CbcHS256,
+                &[
+                    0xc8, 0x0e, 0xdf, 0xa3, 0x2d, 0xdf, 0x39, 0xd5, 0xef, 0x00, 0xc0, 0xb4, 0x68,
+                    0x83, 0x42, 0x79, 0xa2, 0xe4, 0x6a, 0x1b, 0x80, 0x49, 0xf7, 0x92, 0xf7, 0x6b,
+                    0xfe, 0x54, 0xb9, 0x03, 0xa9, 0xc9, 0xa9, 0x4a, 0xc9, 0xb4, 0x7a, 0xd2, 0x65,
+                    0x5c, 0x5f, 0x10, 0xf9, 0xae, 0xf7, 0x14, 0x27, 0xe2, 0xfc, 0x6f, 0x9b, 0x3f,
+                    0x39, 0x9a, 0x22, 0x14, 0x89, 0xf1, 0x63, 0x62, 0xc7, 0x03, 0x23, 0x36, 0x09,
+                    0xd4, 0x5a, 0xc6, 0x98, 0x64, 0xe3, 0x32, 0x1c, 0xf8, 0x29, 0x35, 0xac, 0x40,
+                    0x96, 0xc8, 0x6e, 0x13, 0x33, 0x14, 0xc5, 0x40, 0x19, 0xe8, 0xca, 0x79, 0x80,
+                    0xdf, 0xa4, 0xb9, 0xcf, 0x1b, 0x38, 0x4c, 0x48, 0x6f, 0x3a, 0x54, 0xc5, 0x10,
+                    0x78, 0x15, 0x8e, 0xe5, 0xd7, 0x9d, 0xe5, 0x9f, 0xbd, 0x34, 0xd8, 0x48, 0xb3,
+                    0xd6, 0x95, 0x50, 0xa6, 0x76, 0x46, 0x34, 0x44, 0x27, 0xad, 0xe5, 0x4b, 0x88,
+                    0x51, 0xff, 0xb5, 0x98, 0xf7, 0xf8, 0x00, 0x74, 0xb9, 0x47, 0x3c, 0x82, 0xe2,
+                    0xdb,
+                ],
+                &[
+                    0x65, 0x2c, 0x3f, 0xa3, 0x6b, 0x0a, 0x7c, 0x5b, 0x32, 0x19, 0xfa, 0xb3, 0xa3,
+                    0x0b, 0xc1, 0xc4,
+                ],
+            ),
+            (
+                AesCbcHmacJweEncryption::A256CbcHS512,
+                &[
+                    0x4a, 0xff, 0xaa, 0xad, 0xb7, 0x8c, 0x31, 0xc5, 0xda, 0x4b, 0x1b, 0x59, 0x0d,
+                    0x10, 0xff, 0xbd, 0x3d, 0xd8, 0xd5, 0xd3, 0x02, 0x42, 0x35, 0x26, 0x91, 0x2d,
+                    0xa0, 0x37, 0xec, 0xbc, 0xc7, 0xbd, 0x82, 0x2c, 0x30, 0x1d, 0xd6, 0x7c, 0x37,
+                    0x3b, 0xcc, 0xb5, 0x84, 0xad, 0x3e, 0x92, 0x79, 0xc2, 0xe6, 0xd1, 0x2a, 0x13,
+                    0x74, 0xb7, 0x7f, 0x07, 0x75, 0x53, 0xdf, 0x82, 0x94, 0x10, 0x44, 0x6b, 0x36,
+                    0xeb, 0xd9, 0x70, 0x66, 0x29, 0x6a, 0xe6, 0x42, 0x7e, 0xa7, 0x5c, 0x2e, 0x08,
+                    0x46, 0xa1, 0x1a, 0x09, 0xcc, 0xf5, 0x37, 0x0d, 0xc8, 0x0b, 0xfe, 0xcb, 0xad,
+                    0x28, 0xc7, 0x3f, 0x09, 0xb3, 0xa3, 0xb7, 0x5e, 0x66, 0x2a, 0x25, 0x94, 0x41,
+                    0x0a, 0xe4, 0x96, 0xb2, 0xe2, 0xe6, 0x60, 0x9e, 0x31, 0xe6, 0xe0, 0x2c, 0xc8,
+                    0x37, 0xf0, 0x53, 0xd2, 0x1f, 0x37, 0xff, 0x4f, 0x51, 0x95, 0x0b, 0xbe, 0x26,
+                    0x38, 0xd0, 0x9d, 0xd7, 0xa4, 0x93, 0x09, 0x30, 0x80, 0x6d, 0x07, 0x03, 0xb1,
+                    0xf6,
+                ],
+                &[
+                    0x4d, 0xd3, 0xb4, 0xc0, 0x88, 0xa7, 0xf4, 0x5c, 0x21, 0x68, 0x39, 0x64, 0x5b,
+                    0x20, 0x12, 0xbf, 0x2e, 0x62, 0x69, 0xa8, 0xc5, 0x6a, 0x81, 0x6d, 0xbc, 0x1b,
+                    0x26, 0x77, 0x61, 0x95, 0x5b, 0xc5,
+                ],
+            ),
+        ];
+
+        for (enc, expected_message, expected_tag) in vectors {
+            let key: Vec<u8> = (0..enc.key_len() as u8).collect();
+
+            let (encrypted_message, tag) = enc.encrypt(&key, Some(iv), message, aad)?;
+            assert_eq!(encrypted_message.as_slice(), expected_message);
+            assert_eq!(tag.as_deref(), Some(expected_tag));
+
+            let decrypted_message =
+                enc.decrypt(&key, Some(iv), &encrypted_message, aad, tag.as_deref())?;
+            assert_eq!(&decrypted_message[..], &message[..]);
+
+            let mut invalid_tag = expected_tag.to_vec();
+            invalid_tag[0] ^= 1;
+            assert!(enc
+                .decrypt(&key, Some(iv), &encrypted_message, aad, Some(&invalid_tag))
+                .is_err());
+        }
+
+        Ok(())
+    }
 }
@@ -10,6 +10,7 @@ pub use crate::jwk::key_pair::ecx::EcxCurve;
 pub use crate::jwk::key_pair::ecx::EcxKeyPair;
 pub use crate::jwk::key_pair::ed::EdCurve;
 pub use crate::jwk::key_pair::ed::EdKeyPair;
+pub use crate::jwk::key_pair::key_pair_from_pkcs12;
 pub use crate::jwk::key_pair::rsa::RsaKeyPair;
 pub use crate::jwk::key_pair::rsapss::RsaPssKeyPair;
 pub use crate::jwk::key_pair::KeyPair;
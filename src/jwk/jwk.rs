@@ -3,11 +3,13 @@ use std::io::Read;
 use std::string::ToString;
 
 use anyhow::bail;
+use openssl::hash::{hash, MessageDigest};
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 use serde_json::{Map, Value};
 
 use crate::jose::JoseError;
+use crate::jwk::EcCurve;
 
 /// Represents JWK object.
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -424,6 +426,143 @@ impl Jwk {
     pub fn parameter(&self, key: &str) -> Option<&Value> {
         self.map.get(key)
     }
+
+    /// Return the JWK thumbprint that is computed by SHA-256 (RFC 7638).
+    pub fn thumbprint(&self) -> Result<String, JoseError> {
+        (|| -> anyhow::Result<String> {
+            let keys: &[&str] = match self.key_type() {
+                "EC" => &["crv", "kty", "x", "y"],
+                "RSA" => &["e", "kty", "n"],
+                "oct" => &["k", "kty"],
+                "OKP" => &["crv", "kty", "x"],
+                val => bail!("The JWK kty parameter is unsupported: {}", val),
+            };
+
+            let mut map = Map::new();
+            for key in keys {
+                match self.map.get(*key) {
+                    Some(Value::String(val)) => {
+                        map.insert(key.to_string(), Value::String(val.clone()));
+                    }
+                    Some(_) => bail!("The JWK {} parameter must be a string.", key),
+                    None => bail!("The JWK {} parameter is required.", key),
+                }
+            }
+
+            let json = serde_json::to_vec(&map)?;
+            let digest = hash(MessageDigest::sha256(), &json)?;
+            Ok(base64::encode_config(digest, base64::URL_SAFE_NO_PAD))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwkFormat(err),
+        })
+    }
+
+    /// Validate the structure of this JWK for its key type.
+    ///
+    /// This checks that the required parameters for the kty are present, that the base64url
+    /// encoded parameters can be decoded and that the curve is recognized. No OpenSSL key is
+    /// constructed.
+    pub fn validate(&self) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            match self.key_type() {
+                "oct" => {
+                    self.decode_parameter("k")?;
+                }
+                "RSA" => {
+                    self.decode_parameter("n")?;
+                    self.decode_parameter("e")?;
+                    if self.map.contains_key("d") {
+                        self.decode_parameter("d")?;
+
+                        let crt_params = ["p", "q", "dp", "dq", "qi"];
+                        let count = crt_params
+                            .iter()
+                            .filter(|key| self.map.contains_key(**key))
+                            .count();
+                        if count != 0 && count != crt_params.len() {
+                            bail!("The JWK p, q, dp, dq and qi parameters must be all present or all absent.");
+                        }
+                        if count != 0 {
+                            for key in &crt_params {
+                                self.decode_parameter(key)?;
+                            }
+                        }
+                    }
+                }
+                "EC" => {
+                    let curve = match self.curve() {
+                        Some(val) => val,
+                        None => bail!("The JWK crv parameter is required."),
+                    };
+                    let curve = match [
+                        EcCurve::P256,
+                        EcCurve::P384,
+                        EcCurve::P521,
+                        EcCurve::Secp256K1,
+                        EcCurve::BrainpoolP256R1,
+                        EcCurve::BrainpoolP384R1,
+                        EcCurve::BrainpoolP512R1,
+                    ]
+                    .iter()
+                    .find(|val| val.name() == curve)
+                    {
+                        Some(val) => val,
+                        None => bail!("The JWK crv parameter is unsupported: {}", curve),
+                    };
+
+                    for key in &["x", "y"] {
+                        let val = self.decode_parameter(key)?;
+                        if val.len() != curve.coordinate_size() {
+                            bail!(
+                                "The JWK {} parameter must be {} bytes for {}: {}",
+                                key,
+                                curve.coordinate_size(),
+                                curve,
+                                val.len()
+                            );
+                        }
+                    }
+                    if self.map.contains_key("d") {
+                        self.decode_parameter("d")?;
+                    }
+                }
+                "OKP" => {
+                    let key_len = match self.curve() {
+                        Some("Ed25519") | Some("X25519") => 32,
+                        Some("Ed448") => 57,
+                        Some("X448") => 56,
+                        Some(val) => bail!("The JWK crv parameter is unsupported: {}", val),
+                        None => bail!("The JWK crv parameter is required."),
+                    };
+
+                    let x = self.decode_parameter("x")?;
+                    if x.len() != key_len {
+                        bail!("The JWK x parameter must be {} bytes: {}", key_len, x.len());
+                    }
+                    if self.map.contains_key("d") {
+                        self.decode_parameter("d")?;
+                    }
+                }
+                val => bail!("The JWK kty parameter is unsupported: {}", val),
+            }
+
+            Ok(())
+        })()
+        .map_err(JoseError::InvalidJwkFormat)
+    }
+
+    fn decode_parameter(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        match self.map.get(key) {
+            Some(Value::String(val)) => match base64::decode_config(val, base64::URL_SAFE_NO_PAD) {
+                Ok(val) => Ok(val),
+                Err(_) => bail!("The JWK {} parameter must be a base64 encoded string.", key),
+            },
+            Some(_) => bail!("The JWK {} parameter must be a string.", key),
+            None => bail!("The JWK {} parameter is required.", key),
+        }
+    }
 }
 
 impl AsRef<Map<String, Value>> for Jwk {
@@ -457,3 +596,59 @@ impl Display for Jwk {
         fmt.write_str(&val)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::Result;
+
+    #[test]
+    fn test_jwk_thumbprint() -> Result<()> {
+        // RFC 7638 3.1. Example JWK Thumbprint Computation
+        let jwk = Jwk::from_slice(
+            br#"{
+                "kty": "RSA",
+                "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+                "e": "AQAB",
+                "alg": "RS256",
+                "kid": "2011-04-29"
+            }"#,
+        )?;
+
+        assert_eq!(
+            jwk.thumbprint()?,
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwk_validate() -> Result<()> {
+        let jwk = Jwk::from_slice(
+            br#"{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+                "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0"
+            }"#,
+        )?;
+        jwk.validate()?;
+
+        let jwk = Jwk::from_slice(
+            br#"{
+                "kty": "RSA",
+                "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw"
+            }"#,
+        )?;
+        let err = jwk.validate().unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJwkFormat(_)));
+        assert_eq!(
+            format!("{}", err),
+            "Invalid JWK format: The JWK e parameter is required."
+        );
+
+        Ok(())
+    }
+}
@@ -21,9 +21,11 @@ pub struct JwkSet {
 
 impl JwkSet {
     pub fn new() -> Self {
+        let mut params = Map::new();
+        params.insert("keys".to_string(), Value::Array(Vec::new()));
         Self {
             keys: Vec::new(),
-            params: Map::new(),
+            params,
             kid_map: BTreeMap::new(),
         }
     }
@@ -88,16 +90,44 @@ impl JwkSet {
         })
     }
 
-    pub fn get(&self, key_id: &str) -> Vec<&Jwk> {
-        let mut vec = Vec::new();
-        for (_, val) in self.kid_map.range((
-            Included((key_id.to_string(), 0)),
-            Included((key_id.to_string(), usize::MAX)),
-        )) {
-            let jwk: &Jwk = &val;
-            vec.push(jwk);
+    /// Return an iterator over the keys that have the key ID.
+    ///
+    /// Multiple keys can share a key ID (e.g. keys of different algorithms).
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A key ID
+    pub fn get<'a>(&'a self, key_id: &str) -> impl Iterator<Item = &'a Jwk> {
+        self.kid_map
+            .range((
+                Included((key_id.to_string(), 0)),
+                Included((key_id.to_string(), usize::MAX)),
+            ))
+            .map(|(_, val)| val.as_ref())
+    }
+
+    /// Return the first key that has the key ID and is usable for the algorithm.
+    ///
+    /// A key without alg parameter matches any algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A key ID
+    /// * `alg` - An algorithm name
+    pub fn get_by_kid_and_alg(&self, key_id: &str, alg: &str) -> Option<&Jwk> {
+        let mut fallback = None;
+        for jwk in self.get(key_id) {
+            match jwk.algorithm() {
+                Some(val) if val == alg => return Some(jwk),
+                Some(_) => {}
+                None => {
+                    if fallback.is_none() {
+                        fallback = Some(jwk);
+                    }
+                }
+            }
         }
-        vec
+        fallback
     }
 
     pub fn keys(&self) -> Vec<&Jwk> {
@@ -120,16 +150,42 @@ impl JwkSet {
         self.keys.push(jwk);
     }
 
+    /// Add a key, and remove the oldest keys so that at most `max` keys remain.
+    ///
+    /// The keys are ordered by insertion, so the keys that were pushed first are removed first.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - A key to add
+    /// * `max` - The maximum number of keys
+    pub fn add_key_with_limit(&mut self, jwk: Jwk, max: usize) {
+        self.push_key(jwk);
+        while self.keys.len() > max {
+            self.remove_at(0);
+        }
+    }
+
     pub fn remove_key(&mut self, jwk: &Jwk) {
         let index = self.keys.iter().position(|e| e.as_ref() == jwk);
         if let Some(index) = index {
-            match self.params.get_mut("keys") {
-                Some(Value::Array(keys)) => {
-                    keys.remove(index);
-                }
-                _ => unreachable!(),
+            self.remove_at(index);
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) {
+        match self.params.get_mut("keys") {
+            Some(Value::Array(keys)) => {
+                keys.remove(index);
+            }
+            _ => unreachable!(),
+        }
+        self.keys.remove(index);
+
+        self.kid_map.clear();
+        for (i, jwk) in self.keys.iter().enumerate() {
+            if let Some(kid) = jwk.key_id() {
+                self.kid_map.insert((kid.to_string(), i), Arc::clone(jwk));
             }
-            self.keys.remove(index);
         }
     }
 }
@@ -171,13 +227,79 @@ mod tests {
         let mut file = load_file("jwks/test.jwks")?;
         let jwks = JwkSet::from_reader(&mut file)?;
 
-        assert_eq!(jwks.get("1").len(), 1);
-        let key_id = jwks.get("1")[0].key_id();
+        assert_eq!(jwks.get("1").count(), 1);
+        let key_id = jwks.get("1").next().unwrap().key_id();
         assert!(matches!(key_id, Some("1")));
 
         Ok(())
     }
 
+    #[test]
+    fn test_push_key_to_new_jwk_set() -> Result<()> {
+        let mut jwks = JwkSet::new();
+        let mut jwk = Jwk::new("oct");
+        jwk.set_key_id("1");
+        jwks.push_key(jwk);
+
+        assert_eq!(jwks.keys().len(), 1);
+        assert_eq!(jwks.get("1").count(), 1);
+        assert!(matches!(jwks.as_ref().get("keys"), Some(Value::Array(vals)) if vals.len() == 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_by_kid_and_alg() -> Result<()> {
+        let mut jwk1 = Jwk::new("oct");
+        jwk1.set_key_id("shared");
+        jwk1.set_algorithm("HS256");
+        let mut jwk2 = Jwk::new("oct");
+        jwk2.set_key_id("shared");
+        jwk2.set_algorithm("HS512");
+        let mut jwk3 = Jwk::new("oct");
+        jwk3.set_key_id("other");
+
+        let mut jwks = JwkSet::new();
+        jwks.push_key(jwk1.clone());
+        jwks.push_key(jwk2.clone());
+        jwks.push_key(jwk3.clone());
+
+        assert_eq!(jwks.get("shared").collect::<Vec<_>>(), vec![&jwk1, &jwk2]);
+        assert_eq!(jwks.get_by_kid_and_alg("shared", "HS256"), Some(&jwk1));
+        assert_eq!(jwks.get_by_kid_and_alg("shared", "HS512"), Some(&jwk2));
+        assert_eq!(jwks.get_by_kid_and_alg("shared", "HS384"), None);
+        assert_eq!(jwks.get_by_kid_and_alg("other", "HS384"), Some(&jwk3));
+        assert_eq!(jwks.get_by_kid_and_alg("unknown", "HS256"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_key_with_limit() -> Result<()> {
+        let mut jwks = JwkSet::new();
+        for kid in &["key-1", "key-2", "key-3", "key-4"] {
+            let mut jwk = Jwk::new("oct");
+            jwk.set_key_id(*kid);
+            jwks.add_key_with_limit(jwk, 3);
+        }
+
+        let kids: Vec<_> = jwks.keys().iter().map(|jwk| jwk.key_id()).collect();
+        assert_eq!(kids, vec![Some("key-2"), Some("key-3"), Some("key-4")]);
+        assert_eq!(jwks.get("key-1").count(), 0);
+        assert_eq!(jwks.get("key-4").count(), 1);
+        match jwks.as_ref().get("keys") {
+            Some(Value::Array(keys)) => assert_eq!(keys.len(), 3),
+            _ => unreachable!(),
+        }
+
+        let jwk = jwks.get("key-3").next().unwrap().clone();
+        jwks.remove_key(&jwk);
+        assert_eq!(jwks.get("key-3").count(), 0);
+        assert_eq!(jwks.get("key-4").count(), 1);
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<File> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
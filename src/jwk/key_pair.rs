@@ -6,7 +6,12 @@ pub mod rsapss;
 
 use std::fmt::Debug;
 
-use crate::jwk::Jwk;
+use anyhow::bail;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::Id;
+
+use crate::jose::JoseError;
+use crate::jwk::{EcKeyPair, EcxKeyPair, EdKeyPair, Jwk, RsaKeyPair};
 
 pub trait KeyPair: Debug + Send + Sync {
     fn algorithm(&self) -> Option<&str>;
@@ -15,6 +20,17 @@ pub trait KeyPair: Debug + Send + Sync {
     fn to_der_public_key(&self) -> Vec<u8>;
     fn to_pem_private_key(&self) -> Vec<u8>;
     fn to_pem_public_key(&self) -> Vec<u8>;
+
+    /// Return the private key formatted by PEM with LF line endings.
+    fn to_pem_private_key_lf(&self) -> Vec<u8> {
+        to_lf_line_endings(self.to_pem_private_key())
+    }
+
+    /// Return the public key formatted by PEM with LF line endings.
+    fn to_pem_public_key_lf(&self) -> Vec<u8> {
+        to_lf_line_endings(self.to_pem_public_key())
+    }
+
     fn to_jwk_private_key(&self) -> Jwk;
     fn to_jwk_public_key(&self) -> Jwk;
     fn to_jwk_keypair(&self) -> Jwk;
@@ -35,3 +51,116 @@ impl Clone for Box<dyn KeyPair> {
         self.box_clone()
     }
 }
+
+fn to_lf_line_endings(pem: Vec<u8>) -> Vec<u8> {
+    let mut result = Vec::with_capacity(pem.len());
+    let mut iter = pem.into_iter().peekable();
+    while let Some(c) = iter.next() {
+        if c == b'\r' && iter.peek() == Some(&b'\n') {
+            continue;
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// A key pair and its DER encoded certificate chain.
+type KeyPairWithChain = (Box<dyn KeyPair>, Vec<Vec<u8>>);
+
+/// Return a key pair and a certificate chain that is loaded from a PKCS#12 bundle.
+///
+/// The chain is a list of DER encoded certificates ordered from the end-entity certificate,
+/// so it can be used as a value of x5c parameter.
+///
+/// # Arguments
+///
+/// * `der` - A PKCS#12 bundle that is formatted by DER.
+/// * `password` - A password to decrypt the bundle.
+pub fn key_pair_from_pkcs12(der: &[u8], password: &str) -> Result<KeyPairWithChain, JoseError> {
+    (|| -> anyhow::Result<KeyPairWithChain> {
+        let parsed = Pkcs12::from_der(der)?.parse2(password)?;
+
+        let pkey = match parsed.pkey {
+            Some(val) => val,
+            None => bail!("A private key is not found in the PKCS#12 bundle."),
+        };
+        let pkcs8 = pkey.private_key_to_pkcs8()?;
+        let key_pair: Box<dyn KeyPair> = match pkey.id() {
+            Id::RSA => Box::new(RsaKeyPair::from_der(&pkcs8)?),
+            Id::EC => Box::new(EcKeyPair::from_der(&pkcs8, None)?),
+            Id::ED25519 | Id::ED448 => Box::new(EdKeyPair::from_der(&pkcs8, None)?),
+            Id::X25519 | Id::X448 => Box::new(EcxKeyPair::from_der(&pkcs8, None)?),
+            _ => bail!("The PKCS#12 private key type is not supported."),
+        };
+
+        let mut chain = Vec::new();
+        if let Some(cert) = parsed.cert {
+            chain.push(cert.to_der()?);
+        }
+        if let Some(ca) = parsed.ca {
+            for cert in &ca {
+                chain.push(cert.to_der()?);
+            }
+        }
+
+        Ok((key_pair, chain))
+    })()
+    .map_err(|err| match err.downcast::<JoseError>() {
+        Ok(err) => err,
+        Err(err) => JoseError::InvalidKeyFormat(err),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use openssl::pkcs12::Pkcs12;
+    use openssl::pkey::PKey;
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use super::key_pair_from_pkcs12;
+    use crate::jws::{JwsHeader, ES256};
+    use crate::util;
+
+    #[test]
+    fn load_key_pair_from_pkcs12() -> Result<()> {
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let pkey = PKey::private_key_from_pem(&private_key)?;
+
+        let cert = util::self_signed_certificate(&pkey)?;
+
+        let pkcs12 = Pkcs12::builder()
+            .name("test")
+            .pkey(&pkey)
+            .cert(&cert)
+            .build2("password")?;
+        let der = pkcs12.to_der()?;
+
+        let (key_pair, chain) = key_pair_from_pkcs12(&der, "password")?;
+        assert_eq!(chain, vec![cert.to_der()?]);
+
+        let signer = ES256.signer_from_der(key_pair.to_der_private_key())?;
+        let verifier = ES256.verifier_from_pem(load_file("pem/EC_P-256_public.pem")?)?;
+        let header = JwsHeader::new();
+        let jws = crate::jws::serialize_compact(b"test payload!", &header, &signer)?;
+        let (payload, _) = crate::jws::deserialize_compact(&jws, &verifier)?;
+        assert_eq!(payload, b"test payload!");
+
+        assert!(key_pair_from_pkcs12(&der, "wrong").is_err());
+
+        Ok(())
+    }
+
+    fn load_file(path: &str) -> Result<Vec<u8>> {
+        let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pb.push("data");
+        pb.push(path);
+
+        let mut file = File::open(&pb)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
@@ -9,8 +9,8 @@ use openssl::pkey::{PKey, Private};
 use serde_json::Value;
 
 use crate::der::oid::{
-    ObjectIdentifier, OID_ID_EC_PUBLIC_KEY, OID_PRIME256V1, OID_SECP256K1, OID_SECP384R1,
-    OID_SECP521R1,
+    ObjectIdentifier, OID_BRAINPOOL_P256R1, OID_BRAINPOOL_P384R1, OID_BRAINPOOL_P512R1,
+    OID_ID_EC_PUBLIC_KEY, OID_PRIME256V1, OID_SECP256K1, OID_SECP384R1, OID_SECP521R1,
 };
 use crate::der::{DerBuilder, DerClass, DerReader, DerType};
 use crate::jose::JoseError;
@@ -23,6 +23,18 @@ pub enum EcCurve {
     P384,
     P521,
     Secp256K1,
+    /// brainpoolP256r1 (RFC 5639).
+    ///
+    /// This curve is not registered for JOSE by RFC 7518 or RFC 8812.
+    BrainpoolP256R1,
+    /// brainpoolP384r1 (RFC 5639).
+    ///
+    /// This curve is not registered for JOSE by RFC 7518 or RFC 8812.
+    BrainpoolP384R1,
+    /// brainpoolP512r1 (RFC 5639).
+    ///
+    /// This curve is not registered for JOSE by RFC 7518 or RFC 8812.
+    BrainpoolP512R1,
 }
 
 impl EcCurve {
@@ -32,6 +44,9 @@ impl EcCurve {
             Self::P384 => "P-384",
             Self::P521 => "P-521",
             Self::Secp256K1 => "secp256k1",
+            Self::BrainpoolP256R1 => "brainpoolP256r1",
+            Self::BrainpoolP384R1 => "brainpoolP384r1",
+            Self::BrainpoolP512R1 => "brainpoolP512r1",
         }
     }
 
@@ -41,22 +56,29 @@ impl EcCurve {
             Self::P384 => &OID_SECP384R1,
             Self::P521 => &OID_SECP521R1,
             Self::Secp256K1 => &OID_SECP256K1,
+            Self::BrainpoolP256R1 => &OID_BRAINPOOL_P256R1,
+            Self::BrainpoolP384R1 => &OID_BRAINPOOL_P384R1,
+            Self::BrainpoolP512R1 => &OID_BRAINPOOL_P512R1,
         }
     }
 
-    fn nid(&self) -> Nid {
+    pub(crate) fn nid(&self) -> Nid {
         match self {
             Self::P256 => Nid::X9_62_PRIME256V1,
             Self::P384 => Nid::SECP384R1,
             Self::P521 => Nid::SECP521R1,
             Self::Secp256K1 => Nid::SECP256K1,
+            Self::BrainpoolP256R1 => Nid::BRAINPOOL_P256R1,
+            Self::BrainpoolP384R1 => Nid::BRAINPOOL_P384R1,
+            Self::BrainpoolP512R1 => Nid::BRAINPOOL_P512R1,
         }
     }
 
-    fn coordinate_size(&self) -> usize {
+    pub(crate) fn coordinate_size(&self) -> usize {
         match self {
-            Self::P256 | Self::Secp256K1 => 32,
-            Self::P384 => 48,
+            Self::P256 | Self::Secp256K1 | Self::BrainpoolP256R1 => 32,
+            Self::P384 | Self::BrainpoolP384R1 => 48,
+            Self::BrainpoolP512R1 => 64,
             Self::P521 => 66,
         }
     }
@@ -159,6 +181,9 @@ impl EcKeyPair {
                         "P-384" => EcCurve::P384,
                         "P-521" => EcCurve::P521,
                         "secp256k1" => EcCurve::Secp256K1,
+                        "brainpoolP256r1" => EcCurve::BrainpoolP256R1,
+                        "brainpoolP384r1" => EcCurve::BrainpoolP384R1,
+                        "brainpoolP512r1" => EcCurve::BrainpoolP512R1,
                         _ => bail!("A parameter crv is unrecognized: {}", val),
                     },
                 },
@@ -383,6 +408,9 @@ impl EcKeyPair {
                         Ok(val) if val == *OID_SECP384R1 => EcCurve::P384,
                         Ok(val) if val == *OID_SECP521R1 => EcCurve::P521,
                         Ok(val) if val == *OID_SECP256K1 => EcCurve::Secp256K1,
+                        Ok(val) if val == *OID_BRAINPOOL_P256R1 => EcCurve::BrainpoolP256R1,
+                        Ok(val) if val == *OID_BRAINPOOL_P384R1 => EcCurve::BrainpoolP384R1,
+                        Ok(val) if val == *OID_BRAINPOOL_P512R1 => EcCurve::BrainpoolP512R1,
                         _ => return None,
                     },
                     _ => return None,
@@ -432,6 +460,9 @@ impl EcKeyPair {
                         Ok(val) if val == *OID_SECP384R1 => EcCurve::P384,
                         Ok(val) if val == *OID_SECP521R1 => EcCurve::P521,
                         Ok(val) if val == *OID_SECP256K1 => EcCurve::Secp256K1,
+                        Ok(val) if val == *OID_BRAINPOOL_P256R1 => EcCurve::BrainpoolP256R1,
+                        Ok(val) if val == *OID_BRAINPOOL_P384R1 => EcCurve::BrainpoolP384R1,
+                        Ok(val) if val == *OID_BRAINPOOL_P512R1 => EcCurve::BrainpoolP512R1,
                         _ => return None,
                     },
                     _ => return None,
@@ -86,6 +86,41 @@ impl EdKeyPair {
         .map_err(|err| JoseError::InvalidKeyFormat(err))
     }
 
+    /// Create a EdDSA key pair from a raw private key (the seed of RFC 8032).
+    ///
+    /// # Arguments
+    /// * `curve` - EdDSA curve algorithm
+    /// * `seed` - A raw private key that is 32 bytes for Ed25519 or 57 bytes for Ed448.
+    pub fn from_raw_private_key(curve: EdCurve, seed: &[u8]) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let seed_len = match curve {
+                EdCurve::Ed25519 => 32,
+                EdCurve::Ed448 => 57,
+            };
+            if seed.len() != seed_len {
+                bail!(
+                    "The raw private key size must be {} for {}: {}",
+                    seed_len,
+                    curve,
+                    seed.len()
+                );
+            }
+
+            let mut builder = DerBuilder::new();
+            builder.append_octed_string_from_slice(seed);
+
+            let pkcs8 = Self::to_pkcs8(&builder.build(), false, curve);
+            let private_key = PKey::private_key_from_der(&pkcs8)?;
+
+            Ok(Self {
+                private_key,
+                curve,
+                alg: None,
+            })
+        })()
+        .map_err(JoseError::InvalidKeyFormat)
+    }
+
     /// Create a EdDSA key pair from a private key that is a DER encoded PKCS#8 PrivateKeyInfo.
     ///
     /// # Arguments
@@ -192,6 +227,19 @@ impl EdKeyPair {
                 val if val == "OKP" => {}
                 val => bail!("A parameter kty must be OKP: {}", val),
             }
+            match jwk.key_use() {
+                Some("sig") => {}
+                None => {}
+                Some(val) => bail!("A parameter use must be sig: {}", val),
+            }
+            if !jwk.is_for_key_operation("sign") {
+                bail!("A parameter key_ops must contains sign.");
+            }
+            match jwk.algorithm() {
+                Some("EdDSA") => {}
+                None => {}
+                Some(val) => bail!("A parameter alg must be EdDSA but {}", val),
+            }
             let curve = match jwk.parameter("crv") {
                 Some(Value::String(val)) => match curve {
                     Some(val2) if val2.name() == val => val2,
@@ -260,6 +308,8 @@ impl EdKeyPair {
             if public {
                 key_ops.push("verify");
             }
+            // Keep a canonical order so that generated JWKs are byte-stable.
+            key_ops.sort_unstable();
             key_ops
         });
         if let Some(val) = &self.alg {
@@ -492,7 +542,11 @@ impl Deref for EdKeyPair {
 mod tests {
     use anyhow::Result;
 
-    use crate::jwk::{EdCurve, EdKeyPair};
+    use serde_json::Value;
+
+    use crate::jwk::{EdCurve, EdKeyPair, KeyPair};
+    use crate::jws::{EdDSA, JwsSigner, JwsVerifier};
+    use crate::util;
 
     #[test]
     fn test_ed_jwt() -> Result<()> {
@@ -513,4 +567,75 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_ed_jwk_key_ops_order() -> Result<()> {
+        let keypair = EdKeyPair::generate(EdCurve::Ed25519)?;
+
+        let jwk1 = keypair.to_jwk_keypair();
+        let jwk2 = keypair.to_jwk_keypair();
+        assert_eq!(jwk1.key_operations(), Some(vec!["sign", "verify"]));
+        assert_eq!(jwk1.key_operations(), jwk2.key_operations());
+        assert_eq!(jwk1.to_string(), jwk2.to_string());
+
+        assert_eq!(
+            keypair.to_jwk_private_key().key_operations(),
+            Some(vec!["sign"])
+        );
+        assert_eq!(
+            keypair.to_jwk_public_key().key_operations(),
+            Some(vec!["verify"])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ed_jwk_alg() -> Result<()> {
+        let keypair = EdKeyPair::generate(EdCurve::Ed25519)?;
+
+        let mut jwk = keypair.to_jwk_private_key();
+        EdKeyPair::from_jwk(&jwk, None)?;
+
+        jwk.set_algorithm("EdDSA");
+        EdKeyPair::from_jwk(&jwk, None)?;
+
+        jwk.set_algorithm("ES256");
+        assert!(EdKeyPair::from_jwk(&jwk, None).is_err());
+
+        let mut jwk = keypair.to_jwk_private_key();
+        jwk.set_key_use("enc");
+        assert!(EdKeyPair::from_jwk(&jwk, None).is_err());
+
+        let mut jwk = keypair.to_jwk_private_key();
+        jwk.set_key_operations(vec!["verify"]);
+        assert!(EdKeyPair::from_jwk(&jwk, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ed_raw_private_key() -> Result<()> {
+        for (curve, seed_len) in &[(EdCurve::Ed25519, 32), (EdCurve::Ed448, 57)] {
+            let seed = util::rand_bytes(*seed_len);
+            let keypair = EdKeyPair::from_raw_private_key(*curve, &seed)?;
+            assert_eq!(keypair.curve(), *curve);
+
+            let jwk = keypair.to_jwk_private_key();
+            let d = match jwk.parameter("d") {
+                Some(Value::String(val)) => base64::decode_config(val, base64::URL_SAFE_NO_PAD)?,
+                _ => unreachable!(),
+            };
+            assert_eq!(d, seed);
+
+            let signer = EdDSA.signer_from_jwk(&jwk)?;
+            let verifier = EdDSA.verifier_from_jwk(&keypair.to_jwk_public_key())?;
+            let signature = signer.sign(b"abcde12345")?;
+            verifier.verify(b"abcde12345", &signature)?;
+
+            assert!(EdKeyPair::from_raw_private_key(*curve, &seed[1..]).is_err());
+        }
+
+        Ok(())
+    }
 }
@@ -67,7 +67,7 @@ impl RsaPssKeyPair {
     /// * `input` - A private key that is a DER encoded PKCS#8 PrivateKeyInfo or PKCS#1 RSAPrivateKey.
     /// * `hash` A hash algorithm for signing
     /// * `mgf1_hash` A hash algorithm for MGF1
-    /// * `salt_len` A salt length
+    /// * `salt_len` A salt length. If None, the salt length of the key or the hash output size is used.
     pub fn from_der(
         input: impl AsRef<[u8]>,
         hash: Option<HashAlgorithm>,
@@ -114,7 +114,7 @@ impl RsaPssKeyPair {
 
                         let salt_len = match salt_len {
                             Some(val) => val,
-                            None => bail!("The salt length is required."),
+                            None => hash.output_len() as u8,
                         };
 
                         pkcs8_der_vec =
@@ -674,8 +674,9 @@ impl Deref for RsaPssKeyPair {
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use openssl::pkey::PKey;
 
-    use crate::jwk::RsaPssKeyPair;
+    use crate::jwk::{KeyPair, RsaPssKeyPair};
     use crate::util::HashAlgorithm;
 
     #[test]
@@ -703,4 +704,34 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_rsa_pem_with_lf_line_endings() -> Result<()> {
+        let hash = HashAlgorithm::Sha256;
+        let keypair = RsaPssKeyPair::generate(2048, hash, hash, 20)?;
+
+        for (crlf, lf) in [
+            (
+                keypair.to_pem_private_key(),
+                keypair.to_pem_private_key_lf(),
+            ),
+            (keypair.to_pem_public_key(), keypair.to_pem_public_key_lf()),
+        ] {
+            assert!(crlf.windows(2).any(|w| w == b"\r\n"));
+            assert!(!lf.contains(&b'\r'));
+            assert_eq!(
+                String::from_utf8(crlf)?.replace("\r\n", "\n").into_bytes(),
+                lf
+            );
+        }
+
+        let keypair2 = RsaPssKeyPair::from_pem(keypair.to_pem_private_key_lf(), hash, hash, 20)?;
+        assert_eq!(keypair.to_der_private_key(), keypair2.to_der_private_key());
+
+        let public_key1 = PKey::public_key_from_pem(&keypair.to_pem_public_key())?;
+        let public_key2 = PKey::public_key_from_pem(&keypair.to_pem_public_key_lf())?;
+        assert!(public_key1.public_eq(&public_key2));
+
+        Ok(())
+    }
 }
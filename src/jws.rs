@@ -1,17 +1,24 @@
 pub mod alg;
 
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::convert::TryInto;
 use std::fmt::{Debug, Display};
-use std::ops::{Deref, DerefMut};
+use std::io::Read;
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::{Arc, Mutex};
 
 use anyhow::bail;
 use once_cell::sync::Lazy;
+use openssl::hash::{hash, MessageDigest};
 use serde_json::{Map, Value};
 
-use crate::jose::{JoseError, JoseHeader};
+use crate::jose::{Base64Alphabet, JoseError, JoseHeader, COMMON_HEADER_CLAIMS};
+use crate::jwe::JweHeader;
 use crate::jwk::Jwk;
-use crate::util::{self, SourceValue};
+use crate::util::{self, SharedFn, SourceValue};
 
 pub use crate::jws::alg::hmac::HmacJwsAlgorithm::HS256;
 pub use crate::jws::alg::hmac::HmacJwsAlgorithm::HS384;
@@ -26,23 +33,34 @@ pub use crate::jws::alg::rsassa_pss::RsassaPssJwsAlgorithm::PS384;
 pub use crate::jws::alg::rsassa_pss::RsassaPssJwsAlgorithm::PS512;
 
 pub use crate::jws::alg::ecdsa::EcdsaJwsAlgorithm::ES256;
+pub use crate::jws::alg::ecdsa::EcdsaJwsAlgorithm::ES256B;
 pub use crate::jws::alg::ecdsa::EcdsaJwsAlgorithm::ES256K;
 pub use crate::jws::alg::ecdsa::EcdsaJwsAlgorithm::ES384;
+pub use crate::jws::alg::ecdsa::EcdsaJwsAlgorithm::ES384B;
 pub use crate::jws::alg::ecdsa::EcdsaJwsAlgorithm::ES512;
+pub use crate::jws::alg::ecdsa::EcdsaJwsAlgorithm::ES512B;
 
 pub use crate::jws::alg::eddsa::EddsaJwsAlgorithm::EdDSA;
 
 static DEFAULT_CONTEXT: Lazy<JwsContext> = Lazy::new(|| JwsContext::new());
 
+/// A context for encoding and decoding JWS.
+///
+/// A context is `Send + Sync`. It can be configured once and then shared read-only across
+/// threads, for example behind an `Arc`.
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct JwsContext {
     acceptable_criticals: BTreeSet<String>,
+    critical_validators: BTreeMap<String, CriticalValidator>,
+    base64_alphabet: Base64Alphabet,
 }
 
 impl JwsContext {
     pub fn new() -> Self {
         Self {
             acceptable_criticals: BTreeSet::new(),
+            critical_validators: BTreeMap::new(),
+            base64_alphabet: Base64Alphabet::UrlSafeNoPad,
         }
     }
 
@@ -73,6 +91,62 @@ impl JwsContext {
         self.acceptable_criticals.remove(name);
     }
 
+    /// Add a validator for a critical header claim, and make the name acceptable.
+    ///
+    /// The validator is called with the header claim value when the name is listed in the
+    /// crit header claim of a decoded JWS, and can reject it by returning an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a critical header claim name
+    /// * `validator` - a function for validating the header claim value
+    pub fn add_critical_validator<F>(&mut self, name: &str, validator: F)
+    where
+        F: Fn(&Value) -> Result<(), JoseError> + Send + Sync + 'static,
+    {
+        self.acceptable_criticals.insert(name.to_string());
+        self.critical_validators.insert(
+            name.to_string(),
+            CriticalValidator::new(Arc::new(validator)),
+        );
+    }
+
+    /// Remove a validator for a critical header claim. The name stays acceptable.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a critical header claim name
+    pub fn remove_critical_validator(&mut self, name: &str) {
+        self.critical_validators.remove(name);
+    }
+
+    /// Set the base64 alphabet used for the segments of compact serialization.
+    ///
+    /// The default is `Base64Alphabet::UrlSafeNoPad` as required by RFC 7515. Any other
+    /// alphabet produces and accepts non-standard tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a base64 alphabet
+    pub fn set_base64_alphabet(&mut self, value: Base64Alphabet) {
+        self.base64_alphabet = value;
+    }
+
+    /// Return the base64 alphabet used for the segments of compact serialization.
+    pub fn base64_alphabet(&self) -> Base64Alphabet {
+        self.base64_alphabet
+    }
+
+    fn validate_critical(&self, name: &str, value: Option<&Value>) -> anyhow::Result<()> {
+        if let Some(validator) = self.critical_validators.get(name) {
+            match value {
+                Some(val) => (validator.0)(val)?,
+                None => bail!("The critical header claim '{}' is missing.", name),
+            }
+        }
+        Ok(())
+    }
+
     /// Return a representation of the data that is formatted by compact serialization.
     ///
     /// # Arguments
@@ -106,73 +180,150 @@ impl JwsContext {
         F: Fn(&JwsHeader) -> Option<&'a dyn JwsSigner>,
     {
         (|| -> anyhow::Result<String> {
-            let mut b64 = true;
-            if let Some(vals) = header.critical() {
-                if vals.iter().any(|e| e == "b64") {
-                    if let Some(val) = header.base64url_encode_payload() {
-                        b64 = *val;
-                    }
-                }
-            }
-
             let signer = match selector(header) {
                 Some(val) => val,
                 None => bail!("A signer is not found."),
             };
 
-            let mut header = header.claims_set().clone();
-            header.insert(
-                "alg".to_string(),
-                Value::String(signer.algorithm().name().to_string()),
-            );
+            let mut header = header.clone();
+            header.set_algorithm(signer.algorithm().name());
             if let Some(key_id) = signer.key_id() {
-                header.insert("kid".to_string(), Value::String(key_id.to_string()));
+                header.set_key_id(key_id);
             }
-            let header_bytes = serde_json::to_vec(&header)?;
 
-            let mut capacity = 2;
-            capacity += util::ceiling(header_bytes.len() * 4, 3);
-            capacity += if b64 {
-                util::ceiling(payload.len() * 4, 3)
-            } else {
-                payload.len()
-            };
-            capacity += util::ceiling(signer.signature_len() * 4, 3);
+            let (_, message) = self.signing_input(&header, payload)?;
+            let mut message = String::from_utf8(message)?;
+            message.reserve(self.base64_alphabet.encoded_len(signer.signature_len()) + 1);
+
+            let signature = signer.sign(message.as_bytes())?;
 
-            let mut message = String::with_capacity(capacity);
-            base64::encode_config_buf(header_bytes, base64::URL_SAFE_NO_PAD, &mut message);
             message.push_str(".");
+            base64::encode_config_buf(signature, self.base64_alphabet.config(), &mut message);
+
+            Ok(message)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
+    /// Return the length of the signing input (`BASE64URL(header) || '.' || BASE64URL(payload)`)
+    /// that is built by compact serialization.
+    ///
+    /// The header must already contain the claims the signer adds (alg and kid).
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The JWS header claims.
+    /// * `payload_len` - The length of the payload data.
+    pub fn signing_input_len(&self, header: &JwsHeader, payload_len: usize) -> usize {
+        let mut b64 = true;
+        if let Some(vals) = header.critical() {
+            if vals.iter().any(|e| e == "b64") {
+                if let Some(val) = header.base64url_encode_payload() {
+                    b64 = *val;
+                }
+            }
+        }
+
+        let header_len = header.to_string().len();
+        let mut len = self.base64_alphabet.encoded_len(header_len) + 1;
+        len += if b64 {
+            self.base64_alphabet.encoded_len(payload_len)
+        } else {
+            payload_len
+        };
+        len
+    }
+
+    /// Return the base64url encoded header and the signing input
+    /// (`BASE64URL(header) || '.' || BASE64URL(payload)`) of compact serialization,
+    /// so that it can be signed outside of this library (e.g. by a HSM).
+    ///
+    /// The header must already contain the claims the signer adds (alg and kid).
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The JWS header claims.
+    /// * `payload` - The payload data.
+    pub fn signing_input(
+        &self,
+        header: &JwsHeader,
+        payload: &[u8],
+    ) -> Result<(String, Vec<u8>), JoseError> {
+        (|| -> anyhow::Result<(String, Vec<u8>)> {
+            if header.algorithm().is_none() {
+                bail!("The JWS alg header claim is required.");
+            }
+
+            let mut b64 = true;
+            if let Some(vals) = header.critical() {
+                if vals.iter().any(|e| e == "b64") {
+                    if let Some(val) = header.base64url_encode_payload() {
+                        b64 = *val;
+                    }
+                }
+            }
+
+            let config = self.base64_alphabet.config();
+            let header_bytes = serde_json::to_vec(header.claims_set())?;
+            let header_b64 = base64::encode_config(header_bytes, config);
+
+            let mut message = String::with_capacity(self.signing_input_len(header, payload.len()));
+            message.push_str(&header_b64);
+            message.push('.');
             if b64 {
-                base64::encode_config_buf(payload, base64::URL_SAFE_NO_PAD, &mut message);
+                base64::encode_config_buf(payload, config, &mut message);
             } else {
                 let payload = std::str::from_utf8(payload)?;
-                if payload.contains(".") {
+                if payload.contains('.') {
                     bail!("A JWS payload cannot contain dot.");
                 }
                 message.push_str(payload);
             }
 
-            let signature = signer.sign(message.as_bytes())?;
-
-            message.push_str(".");
-            base64::encode_config_buf(signature, base64::URL_SAFE_NO_PAD, &mut message);
-
-            Ok(message)
+            Ok((header_b64, message.into_bytes()))
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
             Ok(err) => err,
-            Err(err) => JoseError::InvalidJwtFormat(err),
+            Err(err) => JoseError::InvalidJwsFormat(err),
         })
     }
 
-    /// Return a representation of the data that is formatted by flattened json serialization.
+    /// Return a representation of the data that is formatted by compact serialization
+    /// from the parts of the signing input and a signature that is made outside of this library.
+    ///
+    /// # Arguments
+    ///
+    /// * `header_b64` - The base64url encoded header.
+    /// * `payload_b64` - The base64url encoded payload (or the payload itself when b64 is false).
+    /// * `signature` - The signature of the signing input.
+    pub fn assemble_with_signature(
+        &self,
+        header_b64: &str,
+        payload_b64: &str,
+        signature: &[u8],
+    ) -> Result<String, JoseError> {
+        (|| -> anyhow::Result<String> {
+            if header_b64.contains('.') || payload_b64.contains('.') {
+                bail!("The parts of the signing input cannot contain dot.");
+            }
+
+            let signature_b64 = base64::encode_config(signature, self.base64_alphabet.config());
+            Ok(assemble_compact(header_b64, payload_b64, &signature_b64))
+        })()
+        .map_err(JoseError::InvalidJwsFormat)
+    }
+
+    /// Return a representation of the data that is formatted by general json serialization.
+    ///
+    /// The payload is encoded only once and shared by all of the signatures.
     ///
     /// # Arguments
     ///
-    /// * `protected` - The JWS protected header claims.
-    /// * `header` - The JWS unprotected header claims.
     /// * `payload` - The payload data.
-    /// * `signer` - The JWS signer.
+    /// * `signer` - The JWS signers.
     pub fn serialize_general_json(
         &self,
         payload: &[u8],
@@ -180,6 +331,7 @@ impl JwsContext {
     ) -> Result<String, JoseError> {
         (|| -> anyhow::Result<String> {
             let payload_b64 = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
+            let mut message = String::new();
 
             let mut json = String::new();
             json.push_str("{\"signatures\":[");
@@ -202,7 +354,10 @@ impl JwsContext {
                 let protected_b64 =
                     base64::encode_config(&protected_bytes, base64::URL_SAFE_NO_PAD);
 
-                let message = format!("{}.{}", &protected_b64, &payload_b64);
+                message.clear();
+                message.push_str(&protected_b64);
+                message.push('.');
+                message.push_str(&payload_b64);
                 let signature = signer.sign(message.as_bytes())?;
 
                 json.push_str("{\"protected\":\"");
@@ -267,15 +422,58 @@ impl JwsContext {
         header: Option<&JwsHeader>,
         selector: F,
     ) -> Result<String, JoseError>
+    where
+        F: Fn(&JwsHeader) -> Option<&'a dyn JwsSigner>,
+    {
+        self.serialize_flattened_json_core(payload, protected, header, selector, false)
+    }
+
+    /// Return a representation of the data that is formatted by flattened json serialization
+    /// without the payload member (RFC 7797 detached content).
+    ///
+    /// The protected header claims must contain "b64" in the critical header claim (crit).
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data that is transported out-of-band.
+    /// * `protected` - The JWS protected header claims.
+    /// * `header` - The JWS unprotected header claims.
+    /// * `signer` - The JWS signer.
+    pub fn serialize_flattened_json_detached(
+        &self,
+        payload: &[u8],
+        protected: &JwsHeader,
+        header: Option<&JwsHeader>,
+        signer: &dyn JwsSigner,
+    ) -> Result<String, JoseError> {
+        self.serialize_flattened_json_core(
+            payload,
+            Some(protected),
+            header,
+            |_header| Some(signer),
+            true,
+        )
+    }
+
+    fn serialize_flattened_json_core<'a, F>(
+        &self,
+        payload: &[u8],
+        protected: Option<&JwsHeader>,
+        header: Option<&JwsHeader>,
+        selector: F,
+        detached: bool,
+    ) -> Result<String, JoseError>
     where
         F: Fn(&JwsHeader) -> Option<&'a dyn JwsSigner>,
     {
         (|| -> anyhow::Result<String> {
             let mut b64 = true;
+            let mut b64_critical = false;
 
             let mut protected_map = if let Some(val) = protected {
                 if let Some(vals) = val.critical() {
                     if vals.iter().any(|e| e == "b64") {
+                        b64_critical = true;
                         if let Some(val) = val.base64url_encode_payload() {
                             b64 = *val;
                         }
@@ -287,6 +485,10 @@ impl JwsContext {
                 Map::new()
             };
 
+            if detached && !b64_critical {
+                bail!("The b64 header claim name must be in critical for detached content.");
+            }
+
             let mut map = protected_map.clone();
 
             if let Some(val) = header {
@@ -318,13 +520,18 @@ impl JwsContext {
             let payload_b64;
             let payload = if b64 {
                 payload_b64 = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
-                &payload_b64
+                payload_b64.as_bytes()
+            } else if detached {
+                payload
             } else {
-                std::str::from_utf8(payload)?
+                std::str::from_utf8(payload)?.as_bytes()
             };
 
-            let message = format!("{}.{}", &protected_b64, payload);
-            let signature = signer.sign(message.as_bytes())?;
+            let mut message = Vec::with_capacity(protected_b64.len() + 1 + payload.len());
+            message.extend_from_slice(protected_b64.as_bytes());
+            message.push(b'.');
+            message.extend_from_slice(payload);
+            let signature = signer.sign(&message)?;
 
             let mut json = String::new();
             json.push_str("{\"protected\":\"");
@@ -337,9 +544,11 @@ impl JwsContext {
                 json.push_str(&header);
             }
 
-            json.push_str(",\"payload\":\"");
-            json.push_str(&payload);
-            json.push_str("\"");
+            if !detached {
+                json.push_str(",\"payload\":\"");
+                json.push_str(std::str::from_utf8(payload)?);
+                json.push('"');
+            }
 
             json.push_str(",\"signature\":\"");
             base64::encode_config_buf(&signature, base64::URL_SAFE_NO_PAD, &mut json);
@@ -384,23 +593,34 @@ impl JwsContext {
         F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
     {
         (|| -> anyhow::Result<(Vec<u8>, JwsHeader)> {
+            let input = input.trim_matches(|c: char| c.is_ascii_whitespace());
+            if input.contains(|c: char| c.is_ascii_whitespace()) {
+                bail!("The compact serialization form of JWS must not contain whitespace.");
+            }
+
             let indexies: Vec<usize> = input
                 .char_indices()
                 .filter(|(_, c)| c == &'.')
                 .map(|(i, _)| i)
                 .collect();
-            if indexies.len() != 2 {
-                bail!(
-                    "The compact serialization form of JWS must be three parts separated by colon."
-                );
+            match indexies.len() + 1 {
+                3 => {}
+                5 => bail!(
+                    "The compact serialization form of JWS must be three parts separated by colon, but found 5 parts (the input may be a JWE)."
+                ),
+                len => bail!(
+                    "The compact serialization form of JWS must be three parts separated by colon, but found {} parts.",
+                    len
+                ),
             }
 
             let header = &input[0..indexies[0]];
             let payload = &input[(indexies[0] + 1)..(indexies[1])];
             let signature = &input[(indexies[1] + 1)..];
 
-            let header = base64::decode_config(header, base64::URL_SAFE_NO_PAD)?;
-            let header: Map<String, Value> = serde_json::from_slice(&header)?;
+            let config = self.base64_alphabet.config();
+            let header = base64::decode_config(header, config)?;
+            let header = util::parse_json_object(&header)?;
             let header = JwsHeader::from_map(header)?;
 
             let verifier = match selector(&header)? {
@@ -412,7 +632,11 @@ impl JwsContext {
                 Some(val) => {
                     let expected_alg = verifier.algorithm().name();
                     if val != expected_alg {
-                        bail!("The JWS alg header claim is not {}: {}", expected_alg, val);
+                        return Err(JoseError::AlgorithmMismatch {
+                            expected: expected_alg.to_string(),
+                            actual: val.to_string(),
+                        }
+                        .into());
                     }
                 }
                 None => bail!("The JWS alg header claim is required."),
@@ -433,6 +657,7 @@ impl JwsContext {
                     if !self.is_acceptable_critical(name) {
                         bail!("The critical name '{}' is not supported.", name);
                     }
+                    self.validate_critical(name, header.claim(name))?;
 
                     if name == "b64" {
                         if let Some(val) = header.base64url_encode_payload() {
@@ -443,11 +668,11 @@ impl JwsContext {
             }
 
             let message = &input[..(indexies[1])];
-            let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)?;
+            let signature = base64::decode_config(signature, config)?;
             verifier.verify(message.as_bytes(), &signature)?;
 
             let payload = if b64 {
-                base64::decode_config(payload, base64::URL_SAFE_NO_PAD)?
+                base64::decode_config(payload, config)?
             } else {
                 payload.to_string().into_bytes()
             };
@@ -507,15 +732,90 @@ impl JwsContext {
         input: &str,
         selector: F,
     ) -> Result<(Vec<u8>, JwsHeader), JoseError>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        self.deserialize_json_core(input, None, selector)
+    }
+
+    /// Deserialize the input that is formatted by flattened json serialization
+    /// with detached content (RFC 7797).
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `payload` - The payload data that is transported out-of-band.
+    /// * `verifier` - The JWS verifier.
+    pub fn deserialize_json_detached(
+        &self,
+        input: &str,
+        payload: &[u8],
+        verifier: &dyn JwsVerifier,
+    ) -> Result<JwsHeader, JoseError> {
+        self.deserialize_json_detached_with_selector(input, payload, |header| {
+            match header.algorithm() {
+                Some(val) => {
+                    let expected_alg = verifier.algorithm().name();
+                    if val != expected_alg {
+                        return Ok(None);
+                    }
+                }
+                _ => return Ok(None),
+            }
+
+            if let Some(expected) = verifier.key_id() {
+                match header.key_id() {
+                    Some(actual) if expected == actual => {}
+                    _ => return Ok(None),
+                }
+            }
+
+            Ok(Some(verifier))
+        })
+    }
+
+    /// Deserialize the input that is formatted by flattened json serialization
+    /// with detached content (RFC 7797).
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input data.
+    /// * `payload` - The payload data that is transported out-of-band.
+    /// * `selector` - a function for selecting the verifying algorithm.
+    pub fn deserialize_json_detached_with_selector<'a, F>(
+        &self,
+        input: &str,
+        payload: &[u8],
+        selector: F,
+    ) -> Result<JwsHeader, JoseError>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        let (_, header) = self.deserialize_json_core(input, Some(payload), selector)?;
+        Ok(header)
+    }
+
+    fn deserialize_json_core<'a, F>(
+        &self,
+        input: &str,
+        detached: Option<&[u8]>,
+        selector: F,
+    ) -> Result<(Vec<u8>, JwsHeader), JoseError>
     where
         F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
     {
         (|| -> anyhow::Result<(Vec<u8>, JwsHeader)> {
-            let mut map: Map<String, Value> = serde_json::from_str(input)?;
+            let mut map = util::parse_json_object(input.as_bytes())?;
 
             let payload_b64 = match map.remove("payload") {
-                Some(Value::String(val)) => val,
+                Some(Value::String(val)) => {
+                    if detached.is_some() && !val.is_empty() {
+                        bail!("The payload field must not exist for detached content.");
+                    }
+                    val
+                }
                 Some(_) => bail!("The payload field must be string."),
+                None if detached.is_some() => String::new(),
                 None => bail!("The payload field is required."),
             };
 
@@ -545,7 +845,7 @@ impl JwsContext {
                 let (protected, protected_b64) = match sig.get("protected") {
                     Some(Value::String(val)) => {
                         let vec = base64::decode_config(&val, base64::URL_SAFE_NO_PAD)?;
-                        let json: Map<String, Value> = serde_json::from_slice(&vec)?;
+                        let json = util::parse_json_object(&vec)?;
                         (json, val)
                     }
                     Some(_) => bail!("The protected field must be a string."),
@@ -559,7 +859,7 @@ impl JwsContext {
                 let mut merged = match header {
                     Some(Value::Object(val)) => val,
                     Some(_) => bail!("The protected field must be a object."),
-                    None => protected.clone(),
+                    None => Map::new(),
                 };
 
                 for (key, value) in &protected {
@@ -588,7 +888,11 @@ impl JwsContext {
                     Some(Value::String(val)) => {
                         let expected_alg = verifier.algorithm().name();
                         if val != expected_alg {
-                            bail!("The JWS alg header claim is not {}: {}", expected_alg, val);
+                            return Err(JoseError::AlgorithmMismatch {
+                                expected: expected_alg.to_string(),
+                                actual: val.to_string(),
+                            }
+                            .into());
                         }
                     }
                     Some(_) => bail!("The JWS alg header claim must be a string."),
@@ -605,15 +909,18 @@ impl JwsContext {
                 }
 
                 let mut b64 = true;
-                if let Some(Value::Array(vals)) = protected.get("critical") {
+                let mut b64_critical = false;
+                if let Some(Value::Array(vals)) = protected.get("crit") {
                     for val in vals {
                         match val {
                             Value::String(name) => {
                                 if !self.is_acceptable_critical(name) {
                                     bail!("The critical name '{}' is not supported.", name);
                                 }
+                                self.validate_critical(name, protected.get(name))?;
 
                                 if name == "b64" {
+                                    b64_critical = true;
                                     match protected.get("b64") {
                                         Some(Value::Bool(b64_val)) => {
                                             b64 = *b64_val;
@@ -628,6 +935,31 @@ impl JwsContext {
                     }
                 }
 
+                if let Some(payload) = detached {
+                    if !b64_critical {
+                        bail!(
+                            "The b64 header claim name must be in critical for detached content."
+                        );
+                    }
+
+                    let payload_b64;
+                    let payload_part = if b64 {
+                        payload_b64 = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
+                        payload_b64.as_bytes()
+                    } else {
+                        payload
+                    };
+
+                    let mut message =
+                        Vec::with_capacity(protected_b64.len() + 1 + payload_part.len());
+                    message.extend_from_slice(protected_b64.as_bytes());
+                    message.push(b'.');
+                    message.extend_from_slice(payload_part);
+                    verifier.verify(&message, &signature)?;
+
+                    return Ok((payload.to_vec(), merged));
+                }
+
                 let message = format!("{}.{}", &protected_b64, &payload_b64);
                 verifier.verify(message.as_bytes(), &signature)?;
 
@@ -682,6 +1014,44 @@ where
     DEFAULT_CONTEXT.serialize_compact_with_selector(payload, header, selector)
 }
 
+/// Return the length of the signing input (`BASE64URL(header) || '.' || BASE64URL(payload)`)
+/// that is built by compact serialization.
+///
+/// # Arguments
+///
+/// * `header` - The JWS header claims.
+/// * `payload_len` - The length of the payload data.
+pub fn signing_input_len(header: &JwsHeader, payload_len: usize) -> usize {
+    DEFAULT_CONTEXT.signing_input_len(header, payload_len)
+}
+
+/// Return the base64url encoded header and the signing input
+/// (`BASE64URL(header) || '.' || BASE64URL(payload)`) of compact serialization.
+///
+/// # Arguments
+///
+/// * `header` - The JWS header claims.
+/// * `payload` - The payload data.
+pub fn signing_input(header: &JwsHeader, payload: &[u8]) -> Result<(String, Vec<u8>), JoseError> {
+    DEFAULT_CONTEXT.signing_input(header, payload)
+}
+
+/// Return a representation of the data that is formatted by compact serialization
+/// from the parts of the signing input and a signature that is made outside of this library.
+///
+/// # Arguments
+///
+/// * `header_b64` - The base64url encoded header.
+/// * `payload_b64` - The base64url encoded payload.
+/// * `signature` - The signature of the signing input.
+pub fn assemble_with_signature(
+    header_b64: &str,
+    payload_b64: &str,
+    signature: &[u8],
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.assemble_with_signature(header_b64, payload_b64, signature)
+}
+
 /// Return a representation of the data that is formatted by flattened json serialization.
 ///
 /// # Arguments
@@ -734,6 +1104,24 @@ where
     DEFAULT_CONTEXT.serialize_flattened_json_with_selector(payload, protected, header, selector)
 }
 
+/// Return a representation of the data that is formatted by flattened json serialization
+/// without the payload member (RFC 7797 detached content).
+///
+/// # Arguments
+///
+/// * `payload` - The payload data that is transported out-of-band.
+/// * `protected` - The JWS protected header claims.
+/// * `header` - The JWS unprotected header claims.
+/// * `signer` - The JWS signer.
+pub fn serialize_flattened_json_detached(
+    payload: &[u8],
+    protected: &JwsHeader,
+    header: Option<&JwsHeader>,
+    signer: &dyn JwsSigner,
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.serialize_flattened_json_detached(payload, protected, header, signer)
+}
+
 /// Deserialize the input that is formatted by compact serialization.
 ///
 /// # Arguments
@@ -796,29 +1184,152 @@ where
     DEFAULT_CONTEXT.deserialize_json_with_selector(input, selector)
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
-pub struct JwsHeader {
-    claims: Map<String, Value>,
-    sources: HashMap<String, SourceValue>,
+/// Deserialize the input that is formatted by flattened json serialization
+/// with detached content (RFC 7797).
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `payload` - The payload data that is transported out-of-band.
+/// * `verifier` - The JWS verifier.
+pub fn deserialize_json_detached(
+    input: &str,
+    payload: &[u8],
+    verifier: &dyn JwsVerifier,
+) -> Result<JwsHeader, JoseError> {
+    DEFAULT_CONTEXT.deserialize_json_detached(input, payload, verifier)
 }
 
-impl JwsHeader {
-    /// Return a JwsHeader instance.
-    pub fn new() -> Self {
-        Self {
-            claims: Map::new(),
-            sources: HashMap::new(),
-        }
-    }
-
-    /// Return a new header instance from json style header.
-    ///
+/// Deserialize the input that is formatted by flattened json serialization
+/// with detached content (RFC 7797).
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+/// * `payload` - The payload data that is transported out-of-band.
+/// * `selector` - a function for selecting the verifying algorithm.
+pub fn deserialize_json_detached_with_selector<'a, F>(
+    input: &str,
+    payload: &[u8],
+    selector: F,
+) -> Result<JwsHeader, JoseError>
+where
+    F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+{
+    DEFAULT_CONTEXT.deserialize_json_detached_with_selector(input, payload, selector)
+}
+
+/// Return a signature of the raw signing input without constructing a JWS.
+///
+/// # Arguments
+///
+/// * `message` - The message data to sign.
+/// * `signer` - The JWS signer.
+pub fn sign_raw(message: &[u8], signer: &dyn JwsSigner) -> Result<Vec<u8>, JoseError> {
+    signer.sign(message)
+}
+
+/// Verify the signature over the raw signing input without constructing a JWS.
+///
+/// # Arguments
+///
+/// * `signing_input` - The message data to verify.
+/// * `signature` - The signature data.
+/// * `verifier` - The JWS verifier.
+pub fn verify_raw(
+    signing_input: &[u8],
+    signature: &[u8],
+    verifier: &dyn JwsVerifier,
+) -> Result<(), JoseError> {
+    verifier.verify(signing_input, signature)
+}
+
+/// Return a compact serialization that is assembled from already base64url encoded parts.
+///
+/// This function doesn't sign or validate anything. It is a low-level utility
+/// for building boundary or malformed tokens in tests.
+///
+/// # Arguments
+///
+/// * `header_b64` - The base64url encoded header.
+/// * `payload_b64` - The base64url encoded payload.
+/// * `signature_b64` - The base64url encoded signature.
+pub fn assemble_compact(header_b64: &str, payload_b64: &str, signature_b64: &str) -> String {
+    let mut message =
+        String::with_capacity(header_b64.len() + payload_b64.len() + signature_b64.len() + 2);
+    message.push_str(header_b64);
+    message.push('.');
+    message.push_str(payload_b64);
+    message.push('.');
+    message.push_str(signature_b64);
+    message
+}
+
+/// Return the byte ranges of the header, payload and signature of the input that is formatted
+/// by compact serialization.
+///
+/// The ranges index the input as it is, so that the exact bytes can be forwarded without
+/// copying. Nothing is decoded or verified.
+///
+/// # Arguments
+///
+/// * `input` - The input data.
+pub fn segment_spans(input: &str) -> Result<[Range<usize>; 3], JoseError> {
+    (|| -> anyhow::Result<[Range<usize>; 3]> {
+        let spans = util::segment_spans(input);
+        let len = spans.len();
+        match spans.try_into() {
+            Ok(val) => Ok(val),
+            Err(_) => bail!(
+                "The compact serialization form of JWS must be three parts separated by colon, but found {} parts.",
+                len
+            ),
+        }
+    })()
+    .map_err(JoseError::InvalidJwsFormat)
+}
+
+#[derive(Debug, Clone)]
+pub struct JwsHeader {
+    claims: Map<String, Value>,
+    sources: HashMap<String, SourceValue>,
+}
+
+impl JwsHeader {
+    /// Return a JwsHeader instance.
+    pub fn new() -> Self {
+        Self {
+            claims: Map::new(),
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Return a new header instance with the common header claims of a JWE header.
+    ///
+    /// Only the claims that are not specific to JWE algorithms (jku, jwk, kid, x5u, x5c,
+    /// x5t, x5t#S256, typ and cty) are copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The JWE header
+    pub fn from_jwe_header_common(header: &JweHeader) -> Result<Self, JoseError> {
+        let mut claims = Map::new();
+        for key in &COMMON_HEADER_CLAIMS {
+            if let Some(value) = header.claim(key) {
+                claims.insert(key.to_string(), value.clone());
+            }
+        }
+        Self::from_map(claims)
+    }
+
+    /// Return a new header instance from json style header.
+    ///
     /// # Arguments
     ///
     /// * `value` - The json style header claims
     pub fn from_slice(value: &[u8]) -> Result<Self, JoseError> {
         (|| -> anyhow::Result<Self> {
-            let claims: Map<String, Value> = serde_json::from_slice(value)?;
+            let claims = util::parse_json_object(value)?;
             Ok(Self::from_map(claims)?)
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
@@ -840,10 +1351,14 @@ impl JwsHeader {
             let mut sources = HashMap::new();
             for (key, value) in &claims {
                 match key.as_ref() {
-                    "alg" | "jku" | "x5u" | "kid" | "typ" | "cty" | "url" => match value {
+                    "alg" | "jku" | "x5u" | "typ" | "cty" | "url" => match value {
                         Value::String(_) => {},
                         _ => bail!("The JWT {} header claim must be a string.", key),
                     },
+                    "kid" => match value {
+                        Value::String(_) => {},
+                        _ => bail!("The JWT kid header claim must be a string, but it is: {}", value),
+                    },
                     "b64" => match value {
                         Value::Bool(_) => {},
                         _ => bail!("The JWT {} header claim must be a bool.", key),
@@ -853,7 +1368,7 @@ impl JwsHeader {
                             let vals = Jwk::from_map(vals.clone())?;
                             sources.insert(key.clone(), SourceValue::Jwk(vals));
                         },
-                        _ => bail!("The JWT {} header claim must be a string.", key),
+                        _ => bail!("The JWT {} header claim must be a object.", key),
                     },
                     "x5t" => match value {
                         Value::String(val) => {
@@ -1059,6 +1574,74 @@ impl JwsHeader {
         }
     }
 
+    /// Set values for X.509 certificate SHA-1 and SHA-256 thumbprint header claims
+    /// (x5t and x5t#S256) that are computed from the leaf certificate of a chain.
+    ///
+    /// # Arguments
+    ///
+    /// * `chain` - X.509 certificate chain that is formatted by DER. The first one is the leaf certificate.
+    pub fn set_x5t_from_chain(&mut self, chain: &[Vec<u8>]) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            let leaf = match chain.first() {
+                Some(val) => val,
+                None => bail!("The X.509 certificate chain must not be empty."),
+            };
+
+            let sha1 = hash(MessageDigest::sha1(), leaf)?;
+            let sha256 = hash(MessageDigest::sha256(), leaf)?;
+            self.set_x509_certificate_sha1_thumbprint(sha1.to_vec());
+            self.set_x509_certificate_sha256_thumbprint(sha256.to_vec());
+            Ok(())
+        })()
+        .map_err(JoseError::InvalidClaim)
+    }
+
+    /// Verify that the X.509 certificate thumbprint header claims (x5t#S256 and x5t) match the
+    /// leaf certificate of X.509 certificate chain header claim (x5c).
+    ///
+    /// Nothing is checked if x5c or both thumbprint claims are absent.
+    pub fn verify_x5t_consistency(&self) -> Result<(), JoseError> {
+        (|| -> anyhow::Result<()> {
+            let chain = match self.x509_certificate_chain() {
+                Some(val) => val,
+                None => return Ok(()),
+            };
+
+            let thumbprints = [
+                (
+                    "x5t#S256",
+                    MessageDigest::sha256(),
+                    self.x509_certificate_sha256_thumbprint(),
+                ),
+                (
+                    "x5t",
+                    MessageDigest::sha1(),
+                    self.x509_certificate_sha1_thumbprint(),
+                ),
+            ];
+            for (name, md, expected) in &thumbprints {
+                let expected = match expected {
+                    Some(val) => val,
+                    None => continue,
+                };
+                let leaf = match chain.first() {
+                    Some(val) => val,
+                    None => bail!("The JWS x5c header claim must not be empty."),
+                };
+                let actual = hash(*md, leaf)?;
+                if actual.as_ref() != expected.as_slice() {
+                    bail!(
+                        "The JWS {} header claim does not match the leaf certificate of x5c.",
+                        name
+                    );
+                }
+            }
+
+            Ok(())
+        })()
+        .map_err(JoseError::InvalidClaim)
+    }
+
     /// Set a value for key ID header claim (kid).
     ///
     /// # Arguments
@@ -1095,6 +1678,21 @@ impl JwsHeader {
         }
     }
 
+    /// Test the token type header claim (typ) is the expected media type.
+    ///
+    /// Both values are compared after they are normalized to a media type, case-insensitively.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - a token type (e.g. "at+jwt" or "application/at+jwt")
+    pub fn is_token_type(&self, expected: &str) -> bool {
+        match self.token_type() {
+            Some(val) => util::normalize_media_type(val)
+                .eq_ignore_ascii_case(&util::normalize_media_type(expected)),
+            None => false,
+        }
+    }
+
     /// Set a value for content type header claim (cty).
     ///
     /// # Arguments
@@ -1113,6 +1711,13 @@ impl JwsHeader {
         }
     }
 
+    /// Return the media type of content type header claim (cty).
+    ///
+    /// If the value does not contain "/", "application/" is prepended.
+    pub fn content_type_media_type(&self) -> Option<String> {
+        self.content_type().map(util::normalize_media_type)
+    }
+
     /// Set values for critical header claim (crit).
     ///
     /// # Arguments
@@ -1140,6 +1745,33 @@ impl JwsHeader {
         }
     }
 
+    /// Set a value for a header claim and add the name to critical header claim (crit).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a critical claim name
+    /// * `value` - a typed value of the header claim
+    pub fn add_critical(&mut self, name: &str, value: Value) -> Result<(), JoseError> {
+        if name == "alg" || name == "crit" || COMMON_HEADER_CLAIMS.contains(&name) {
+            return Err(JoseError::InvalidJwsFormat(anyhow::Error::msg(format!(
+                "The JWS {} header claim cannot be critical.",
+                name
+            ))));
+        }
+
+        self.set_claim(name, Some(value))?;
+
+        let mut values = match self.critical() {
+            Some(vals) => vals.clone(),
+            None => Vec::new(),
+        };
+        if !values.iter().any(|e| e == name) {
+            values.push(name.to_string());
+        }
+        self.set_critical(values);
+        Ok(())
+    }
+
     /// Set a value for base64url-encode payload header claim (b64).
     ///
     /// # Arguments
@@ -1195,6 +1827,18 @@ impl JwsHeader {
             _ => unreachable!(),
         }
     }
+
+    /// Remove a header claim of a specified key, including its typed value.
+    ///
+    /// Returns the removed value if the claim was present.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key name of header claim
+    pub fn remove_claim(&mut self, key: &str) -> Option<Value> {
+        self.sources.remove(key);
+        self.claims.remove(key)
+    }
 }
 
 impl JoseHeader for JwsHeader {
@@ -1225,7 +1869,7 @@ impl JoseHeader for JwsHeader {
                         self.claims.remove(key);
                         self.sources.remove(key);
                     }
-                    _ => bail!("The JWS {} header claim must be a string.", key),
+                    _ => bail!("The JWS {} header claim must be a object.", key),
                 },
                 "x5t" => match &value {
                     Some(Value::String(val)) => {
@@ -1334,6 +1978,8 @@ impl JoseHeader for JwsHeader {
     }
 }
 
+util::impl_eq_by_claims!(JwsHeader);
+
 impl AsRef<Map<String, Value>> for JwsHeader {
     fn as_ref(&self) -> &Map<String, Value> {
         &self.claims
@@ -1473,6 +2119,23 @@ pub trait JwsVerifier: Debug + Send + Sync {
     /// * `signature` - a signature data.
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError>;
 
+    /// Verify the data that is read from a reader by the signature.
+    ///
+    /// The default implementation reads all data into memory. The verifiers that
+    /// support incremental hashing override it to process the data chunk by chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - a reader of the message data to verify.
+    /// * `signature` - a signature data.
+    fn verify_reader(&self, message: &mut dyn Read, signature: &[u8]) -> Result<(), JoseError> {
+        let mut buf = Vec::new();
+        message
+            .read_to_end(&mut buf)
+            .map_err(|err| JoseError::InvalidSignature(err.into()))?;
+        self.verify(&buf, signature)
+    }
+
     fn box_clone(&self) -> Box<dyn JwsVerifier>;
 }
 
@@ -1482,15 +2145,145 @@ impl Clone for Box<dyn JwsVerifier> {
     }
 }
 
+/// A function for validating a critical header claim value that is registered in JwsContext.
+type CriticalValidatorFn = dyn Fn(&Value) -> Result<(), JoseError> + Send + Sync;
+
+type CriticalValidator = SharedFn<CriticalValidatorFn>;
+
+/// Represents a cache of verifiers that are keyed by the JWK thumbprint
+/// and the alg, use, key_ops and kid parameters.
+///
+/// When the number of cached verifiers exceeds the capacity,
+/// the least recently used verifier is evicted.
+#[derive(Debug)]
+pub struct VerifierCache {
+    capacity: usize,
+    entries: Mutex<VecDeque<(VerifierCacheKey, Arc<dyn JwsVerifier>)>>,
+}
+
+impl VerifierCache {
+    /// Return a new VerifierCache instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - the maximum number of cached verifiers
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Return the maximum number of cached verifiers.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Return the number of cached verifiers.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Return whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().is_empty()
+    }
+
+    /// Remove all cached verifiers.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Return the cached verifier for the JWK, or create and cache a new one.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwk` - a JWK
+    /// * `factory` - a function for creating a verifier from the JWK
+    pub fn get_or_insert_with<F>(
+        &self,
+        jwk: &Jwk,
+        factory: F,
+    ) -> Result<Option<Arc<dyn JwsVerifier>>, JoseError>
+    where
+        F: FnOnce(&Jwk) -> Result<Option<Box<dyn JwsVerifier>>, JoseError>,
+    {
+        let cache_key = VerifierCacheKey::new(jwk)?;
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(pos) = entries.iter().position(|(key, _)| key == &cache_key) {
+                let entry = entries.remove(pos).unwrap();
+                let verifier = Arc::clone(&entry.1);
+                entries.push_back(entry);
+                return Ok(Some(verifier));
+            }
+        }
+
+        let verifier: Arc<dyn JwsVerifier> = match factory(jwk)? {
+            Some(val) => Arc::from(val),
+            None => return Ok(None),
+        };
+
+        if self.capacity > 0 {
+            let mut entries = self.entries.lock().unwrap();
+            while entries.len() >= self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back((cache_key, Arc::clone(&verifier)));
+        }
+
+        Ok(Some(verifier))
+    }
+}
+
+/// The JWK parameters that a cached verifier depends on.
+#[derive(Debug, Eq, PartialEq)]
+struct VerifierCacheKey {
+    thumbprint: String,
+    algorithm: Option<String>,
+    key_use: Option<String>,
+    key_operations: Option<Vec<String>>,
+    key_id: Option<String>,
+}
+
+impl VerifierCacheKey {
+    fn new(jwk: &Jwk) -> Result<Self, JoseError> {
+        Ok(Self {
+            thumbprint: jwk.thumbprint()?,
+            algorithm: jwk.algorithm().map(|val| val.to_string()),
+            key_use: jwk.key_use().map(|val| val.to_string()),
+            key_operations: jwk
+                .key_operations()
+                .map(|vals| vals.iter().map(|val| val.to_string()).collect()),
+            key_id: jwk.key_id().map(|val| val.to_string()),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::jws::{self, EdDSA, JwsHeader, JwsMultiSigner, ES256, RS256};
+    use crate::jose::{Base64Alphabet, JoseError, JoseHeader};
+    use crate::jwk::Jwk;
+    use crate::jws::{
+        self, EdDSA, JwsContext, JwsHeader, JwsMultiSigner, JwsSigner, JwsVerifier, ES256, HS256,
+        PS256, RS256,
+    };
+    use crate::util;
     use anyhow::Result;
-    use serde_json::Value;
+    use openssl::pkey::PKey;
+    use openssl::sha::{sha1, sha256};
+    use serde_json::{json, Map, Value};
     use std::fs::File;
     use std::io::Read;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_jws_context_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<JwsContext>();
+    }
+
     #[test]
     fn test_jws_compact_serialization() -> Result<()> {
         let alg = RS256;
@@ -1545,6 +2338,593 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jws_json_serialization_with_critical() -> Result<()> {
+        let alg = ES256;
+
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let src_payload = b"test payload!";
+        let mut src_protected = JwsHeader::new();
+        src_protected.set_critical(vec!["exp"]);
+        src_protected.set_claim("exp", Some(Value::Number(1.into())))?;
+
+        let mut context = JwsContext::new();
+        context.add_acceptable_critical("exp");
+
+        let signer = alg.signer_from_pem(&private_key)?;
+        let jws =
+            context.serialize_flattened_json(src_payload, Some(&src_protected), None, &signer)?;
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        let (dst_payload, dst_header) = context.deserialize_json(&jws, &verifier)?;
+        assert_eq!(dst_header.critical(), Some(&vec!["exp".to_string()]));
+        assert_eq!(src_payload.to_vec(), dst_payload);
+
+        // A critical header claim that the context does not accept is rejected.
+        assert!(JwsContext::new().deserialize_json(&jws, &verifier).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_eq_ignores_sources() -> Result<()> {
+        let mut header1 = JwsHeader::new();
+        header1.set_algorithm("ES256");
+        header1.set_x509_certificate_chain(vec![b"cert1".to_vec(), b"cert2".to_vec()]);
+        header1.set_x509_certificate_sha1_thumbprint(b"thumbprint".to_vec());
+        header1.set_nonce(b"nonce".to_vec());
+        header1.set_critical(vec!["exp"]);
+
+        let mut claims = Map::new();
+        for key in &["crit", "nonce", "x5t", "x5c", "alg"] {
+            claims.insert(key.to_string(), header1.claim(key).unwrap().clone());
+        }
+        let header2 = JwsHeader::from_map(claims)?;
+        assert_eq!(header1, header2);
+
+        header1.set_key_id("key-1");
+        assert_ne!(header1, header2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_sources_after_decode() -> Result<()> {
+        let alg = ES256;
+
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+        let jwk = Jwk::from_slice(&load_file("jwk/EC_P-256_public.jwk")?)?;
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_jwk(jwk.clone());
+        src_header.set_critical(vec!["exp"]);
+        src_header.set_claim("exp", Some(Value::Number(1.into())))?;
+        src_header.set_x509_certificate_chain(vec![b"cert1".to_vec(), b"cert2".to_vec()]);
+        src_header.set_x509_certificate_sha1_thumbprint(b"thumbprint".to_vec());
+        src_header.set_nonce(b"nonce".to_vec());
+
+        let mut context = JwsContext::new();
+        context.add_acceptable_critical("exp");
+
+        let signer = alg.signer_from_pem(&private_key)?;
+        let jws = context.serialize_compact(b"test payload!", &src_header, &signer)?;
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        let (_, dst_header) = context.deserialize_compact(&jws, &verifier)?;
+
+        assert_eq!(dst_header.jwk(), Some(&jwk));
+        assert_eq!(dst_header.critical(), Some(&vec!["exp".to_string()]));
+        assert_eq!(
+            dst_header.x509_certificate_chain(),
+            Some(&vec![b"cert1".to_vec(), b"cert2".to_vec()])
+        );
+        assert_eq!(
+            dst_header.x509_certificate_sha1_thumbprint(),
+            Some(&b"thumbprint".to_vec())
+        );
+        assert_eq!(dst_header.nonce(), Some(&b"nonce".to_vec()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_deserialization_with_invalid_parts() -> Result<()> {
+        let alg = RS256;
+
+        let public_key = load_file("pem/RSA_2048bit_public.pem")?;
+        let verifier = alg.verifier_from_pem(&public_key)?;
+
+        for (input, parts) in &[
+            ("eyJhbGciOiJSUzI1NiJ9.e30", 2),
+            ("eyJhbGciOiJSUzI1NiJ9.e30.AAAA.AAAA", 4),
+            ("eyJhbGciOiJSUzI1NiJ9.AAAA.AAAA.e30.AAAA", 5),
+        ] {
+            let message = match jws::deserialize_compact(input, &verifier) {
+                Ok(_) => unreachable!(),
+                Err(err) => err.to_string(),
+            };
+            assert!(message.contains(&format!("found {} parts", parts)));
+            assert_eq!(message.contains("JWE"), *parts == 5);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_verify_with_original_header_bytes() -> Result<()> {
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = HS256.signer_from_slice(key)?;
+        let verifier = HS256.verifier_from_slice(key)?;
+
+        // The keys are not sorted and contain whitespace, so re-serializing
+        // the header would produce different bytes.
+        let header = "{\"typ\":\"JWT\", \"kid\":\"key-1\",\n \"alg\":\"HS256\"}";
+        let header_b64 = base64::encode_config(header, base64::URL_SAFE_NO_PAD);
+        let payload_b64 = base64::encode_config(b"test payload!", base64::URL_SAFE_NO_PAD);
+        let message = format!("{}.{}", header_b64, payload_b64);
+        let signature = signer.sign(message.as_bytes())?;
+        let signature_b64 = base64::encode_config(&signature, base64::URL_SAFE_NO_PAD);
+
+        let input = format!("{}.{}", message, signature_b64);
+        let (payload, dst_header) = jws::deserialize_compact(&input, &verifier)?;
+        assert_eq!(payload, b"test payload!");
+        assert_eq!(dst_header.key_id(), Some("key-1"));
+        assert_ne!(
+            base64::encode_config(dst_header.to_string(), base64::URL_SAFE_NO_PAD),
+            header_b64
+        );
+
+        let input = format!(
+            "{{\"protected\":\"{}\",\"payload\":\"{}\",\"signature\":\"{}\"}}",
+            header_b64, payload_b64, signature_b64
+        );
+        let (payload, _) = jws::deserialize_json(&input, &verifier)?;
+        assert_eq!(payload, b"test payload!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_external_signing() -> Result<()> {
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let payload = b"test payload!";
+
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        header.set_algorithm("HS256");
+
+        let (header_b64, signing_input) = jws::signing_input(&header, payload)?;
+        assert_eq!(
+            signing_input.len(),
+            jws::signing_input_len(&header, payload.len())
+        );
+
+        // Sign outside of the serializer, as a HSM would.
+        let signature = signer.sign(&signing_input)?;
+        let signing_input = String::from_utf8(signing_input)?;
+        let payload_b64 = &signing_input[(header_b64.len() + 1)..];
+        let jws = jws::assemble_with_signature(&header_b64, payload_b64, &signature)?;
+        assert_eq!(jws, jws::serialize_compact(payload, &header, &signer)?);
+
+        assert!(jws::signing_input(&JwsHeader::new(), payload).is_err());
+        assert!(jws::assemble_with_signature("a.b", payload_b64, &signature).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_raw_signature() -> Result<()> {
+        let alg = ES256;
+
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+        let message = b"arbitrary data";
+
+        let signer = alg.signer_from_pem(&private_key)?;
+        let signature = jws::sign_raw(message, &signer)?;
+        assert_eq!(signature.len(), signer.signature_len());
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        jws::verify_raw(message, &signature, &verifier)?;
+        assert!(jws::verify_raw(b"tampered data", &signature, &verifier).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_assemble_compact() -> Result<()> {
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let verifier = HS256.verifier_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+
+        let jws = jws::serialize_compact(b"test payload!", &JwsHeader::new(), &signer)?;
+        let parts: Vec<&str> = jws.split('.').collect();
+
+        let assembled = jws::assemble_compact(parts[0], parts[1], parts[2]);
+        assert_eq!(assembled, jws);
+        assert_eq!(assembled.split('.').collect::<Vec<&str>>(), parts);
+        jws::deserialize_compact(&assembled, &verifier)?;
+
+        let assembled = jws::assemble_compact(parts[0], "", "");
+        assert_eq!(
+            assembled.split('.').collect::<Vec<&str>>(),
+            vec![parts[0], "", ""]
+        );
+        assert!(jws::deserialize_compact(&assembled, &verifier).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_segment_spans() -> Result<()> {
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let input = jws::serialize_compact(b"test payload!", &JwsHeader::new(), &signer)?;
+        let parts: Vec<&str> = input.split('.').collect();
+
+        let spans = jws::segment_spans(&input)?;
+        for (span, part) in spans.iter().zip(&parts) {
+            assert_eq!(&input[span.clone()], *part);
+        }
+        assert_eq!(
+            &input[spans[0].start..spans[1].end],
+            &input[..input.rfind('.').unwrap()]
+        );
+
+        assert_eq!(jws::segment_spans("a..")?, [0..1, 2..2, 3..3]);
+        assert!(jws::segment_spans("a.b").is_err());
+        assert!(jws::segment_spans("a.b.c.d.e").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_verify_reader() -> Result<()> {
+        let message: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        let (chunk1, rest) = message.split_at(10);
+        let (chunk2, chunk3) = rest.split_at(50_000);
+
+        let hmac_key = b"0123456789ABCDEF0123456789ABCDEF";
+        let rsa_private_key = load_file("pem/RSA_2048bit_private.pem")?;
+        let rsa_public_key = load_file("pem/RSA_2048bit_public.pem")?;
+        let pss_private_key = load_file("pem/RSA-PSS_2048bit_SHA-256_private.pem")?;
+        let pss_public_key = load_file("pem/RSA-PSS_2048bit_SHA-256_public.pem")?;
+        let ec_private_key = load_file("pem/EC_P-256_private.pem")?;
+        let ec_public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let pairs: Vec<(Box<dyn JwsSigner>, Box<dyn JwsVerifier>)> = vec![
+            (
+                Box::new(HS256.signer_from_slice(hmac_key)?),
+                Box::new(HS256.verifier_from_slice(hmac_key)?),
+            ),
+            (
+                Box::new(RS256.signer_from_pem(&rsa_private_key)?),
+                Box::new(RS256.verifier_from_pem(&rsa_public_key)?),
+            ),
+            (
+                Box::new(PS256.signer_from_pem(&pss_private_key)?),
+                Box::new(PS256.verifier_from_pem(&pss_public_key)?),
+            ),
+            (
+                Box::new(ES256.signer_from_pem(&ec_private_key)?),
+                Box::new(ES256.verifier_from_pem(&ec_public_key)?),
+            ),
+        ];
+
+        for (signer, verifier) in &pairs {
+            let signature = signer.sign(&message)?;
+            verifier.verify(&message, &signature)?;
+
+            let mut reader = chunk1.chain(chunk2).chain(chunk3);
+            verifier.verify_reader(&mut reader, &signature)?;
+
+            let mut reader = chunk1.chain(chunk3);
+            assert!(verifier.verify_reader(&mut reader, &signature).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_critical_validator() -> Result<()> {
+        let alg = HS256;
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = alg.signer_from_slice(key)?;
+        let verifier = alg.verifier_from_slice(key)?;
+
+        let mut context = JwsContext::new();
+        context.add_critical_validator("exp_policy", |value| match value {
+            Value::String(val) if val == "strict" => Ok(()),
+            _ => Err(JoseError::InvalidClaim(anyhow::Error::msg(
+                "The exp_policy header claim must be strict.",
+            ))),
+        });
+        assert!(context.is_acceptable_critical("exp_policy"));
+
+        let mut header = JwsHeader::new();
+        header.set_critical(vec!["exp_policy"]);
+        header.set_claim("exp_policy", Some(json!("strict")))?;
+        let jws = context.serialize_compact(b"payload", &header, &signer)?;
+        let (payload, _) = context.deserialize_compact(&jws, &verifier)?;
+        assert_eq!(payload, b"payload");
+
+        header.set_claim("exp_policy", Some(json!("lenient")))?;
+        let jws = context.serialize_compact(b"payload", &header, &signer)?;
+        let err = context.deserialize_compact(&jws, &verifier).unwrap_err();
+        assert!(matches!(err, JoseError::InvalidClaim(_)));
+
+        let json = context.serialize_flattened_json(b"payload", Some(&header), None, &signer)?;
+        assert!(context.deserialize_json(&json, &verifier).is_err());
+
+        context.remove_critical_validator("exp_policy");
+        context.deserialize_compact(&jws, &verifier)?;
+        context.deserialize_json(&json, &verifier)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_remove_claim() -> Result<()> {
+        let mut header = JwsHeader::new();
+        header.set_key_id("key-1");
+        header.set_nonce(b"nonce".to_vec());
+
+        assert_eq!(header.remove_claim("kid"), Some(json!("key-1")));
+        assert_eq!(header.key_id(), None);
+        assert_eq!(header.claim("kid"), None);
+        assert_eq!(header.remove_claim("kid"), None);
+
+        assert!(header.remove_claim("nonce").is_some());
+        assert_eq!(header.nonce(), None);
+        assert!(header.claims_set().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_is_token_type() -> Result<()> {
+        let mut header = JwsHeader::new();
+        assert!(!header.is_token_type("at+jwt"));
+
+        header.set_token_type("AT+JWT");
+        assert!(header.is_token_type("at+jwt"));
+        assert!(header.is_token_type("Application/At+Jwt"));
+        assert!(!header.is_token_type("JWT"));
+        assert_eq!(header.token_type(), Some("AT+JWT"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_add_critical() -> Result<()> {
+        let mut header = JwsHeader::new();
+        header.add_critical("exp", json!(1363284000))?;
+        header.add_critical("http://example.invalid/UNDEFINED", json!(true))?;
+        header.add_critical("exp", json!(1363284001))?;
+
+        assert_eq!(
+            header.critical(),
+            Some(&vec![
+                "exp".to_string(),
+                "http://example.invalid/UNDEFINED".to_string()
+            ])
+        );
+        assert_eq!(
+            header.claim("crit"),
+            Some(&json!(["exp", "http://example.invalid/UNDEFINED"]))
+        );
+        assert_eq!(header.claim("exp"), Some(&json!(1363284001)));
+        assert_eq!(
+            header.claim("http://example.invalid/UNDEFINED"),
+            Some(&json!(true))
+        );
+
+        assert!(header.add_critical("kid", json!("key-1")).is_err());
+        assert!(header.add_critical("crit", json!(["exp"])).is_err());
+        assert_eq!(header.critical().map(|vals| vals.len()), Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_numeric_key_id() -> Result<()> {
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let verifier = HS256.verifier_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+
+        let header_b64 =
+            base64::encode_config(br#"{"alg":"HS256","kid":123}"#, base64::URL_SAFE_NO_PAD);
+        let payload_b64 = base64::encode_config(b"test payload!", base64::URL_SAFE_NO_PAD);
+        let signing_input = format!("{}.{}", header_b64, payload_b64);
+        let signature = signer.sign(signing_input.as_bytes())?;
+        let jws = format!(
+            "{}.{}",
+            signing_input,
+            base64::encode_config(&signature, base64::URL_SAFE_NO_PAD)
+        );
+
+        let err = jws::deserialize_compact(&jws, &verifier).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("kid header claim must be a string, but it is: 123"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_verify_x5t_consistency() -> Result<()> {
+        let pkey = PKey::private_key_from_pem(&load_file("pem/EC_P-256_private.pem")?)?;
+        let leaf = util::self_signed_certificate(&pkey)?.to_der()?;
+
+        let mut header = JwsHeader::new();
+        header.verify_x5t_consistency()?;
+
+        header.set_x509_certificate_chain(vec![leaf.clone()]);
+        header.verify_x5t_consistency()?;
+
+        header.set_x509_certificate_sha256_thumbprint(sha256(&leaf).to_vec());
+        header.verify_x5t_consistency()?;
+
+        header.set_x509_certificate_sha1_thumbprint(sha1(&leaf).to_vec());
+        header.verify_x5t_consistency()?;
+
+        let mut tampered = header.clone();
+        tampered.set_x509_certificate_sha256_thumbprint(sha256(b"tampered").to_vec());
+        let err = tampered.verify_x5t_consistency().unwrap_err();
+        assert!(matches!(err, JoseError::InvalidClaim(_)));
+
+        let mut tampered = header.clone();
+        tampered.set_x509_certificate_chain(vec![b"tampered".to_vec()]);
+        assert!(tampered.verify_x5t_consistency().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_header_x5t_from_chain() -> Result<()> {
+        let pkey = PKey::private_key_from_pem(&load_file("pem/EC_P-256_private.pem")?)?;
+        let leaf = util::self_signed_certificate(&pkey)?.to_der()?;
+
+        let chain = vec![leaf.clone(), b"intermediate".to_vec()];
+        let mut header = JwsHeader::new();
+        header.set_x509_certificate_chain(chain.clone());
+        header.set_x5t_from_chain(&chain)?;
+
+        assert_eq!(
+            header.x509_certificate_sha256_thumbprint(),
+            Some(&sha256(&leaf).to_vec())
+        );
+        assert_eq!(
+            header.x509_certificate_sha1_thumbprint(),
+            Some(&sha1(&leaf).to_vec())
+        );
+        assert_eq!(
+            header.claim("x5t#S256"),
+            Some(&Value::String(base64::encode_config(
+                sha256(&leaf),
+                base64::URL_SAFE_NO_PAD
+            )))
+        );
+
+        assert!(JwsHeader::new().set_x5t_from_chain(&[]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_compact_deserialization_with_duplicate_members() -> Result<()> {
+        let alg = HS256;
+        let key = b"0123456789ABCDEF0123456789ABCDEF";
+        let signer = alg.signer_from_slice(key)?;
+        let verifier = alg.verifier_from_slice(key)?;
+
+        let header = br#"{"alg":"HS256","alg":"none"}"#;
+        let mut input = String::new();
+        base64::encode_config_buf(header, base64::URL_SAFE_NO_PAD, &mut input);
+        input.push('.');
+        base64::encode_config_buf(b"test payload!", base64::URL_SAFE_NO_PAD, &mut input);
+        let signature = signer.sign(input.as_bytes())?;
+        input.push('.');
+        base64::encode_config_buf(&signature, base64::URL_SAFE_NO_PAD, &mut input);
+
+        let err = jws::deserialize_compact(&input, &verifier).unwrap_err();
+        assert!(format!("{}", err).contains("duplicate member: alg"));
+
+        let header = br#"{"alg":"HS256","kid":"a"}"#;
+        assert!(JwsHeader::from_slice(header).is_ok());
+        let header = br#"{"alg":"HS256","jwk":{"kty":"oct","kty":"EC"}}"#;
+        assert!(JwsHeader::from_slice(header).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_signing_input_len() -> Result<()> {
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+
+        let mut header = JwsHeader::new();
+        header.set_algorithm("HS256");
+        header.set_token_type("JWT");
+        header.set_key_id("key-1");
+
+        for payload_len in 0..8 {
+            let payload = vec![b'a'; payload_len];
+            let jws = jws::serialize_compact(&payload, &header, &signer)?;
+            let signing_input = &jws[..jws.rfind('.').unwrap()];
+
+            assert_eq!(
+                jws::signing_input_len(&header, payload_len),
+                signing_input.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_signing_input_len_with_standard_alphabet() -> Result<()> {
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+
+        let mut header = JwsHeader::new();
+        header.set_algorithm("HS256");
+        header.set_token_type("JWT");
+        header.set_key_id("key-1");
+
+        let mut context = JwsContext::new();
+        context.set_base64_alphabet(Base64Alphabet::Standard);
+
+        for payload_len in 0..8 {
+            let payload = vec![b'a'; payload_len];
+            let jws = context.serialize_compact(&payload, &header, &signer)?;
+            let signing_input = &jws[..jws.rfind('.').unwrap()];
+
+            assert_eq!(
+                context.signing_input_len(&header, payload_len),
+                signing_input.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_json_detached_serialization() -> Result<()> {
+        let alg = ES256;
+
+        let private_key = load_file("pem/EC_P-256_private.pem")?;
+        let public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let src_payload = b"$.02";
+        let mut src_protected = JwsHeader::new();
+        src_protected.set_base64url_encode_payload(false);
+        src_protected.set_critical(vec!["b64"]);
+
+        let mut context = JwsContext::new();
+        context.add_acceptable_critical("b64");
+
+        let signer = alg.signer_from_pem(&private_key)?;
+        let json = context.serialize_flattened_json_detached(
+            src_payload,
+            &src_protected,
+            None,
+            &signer,
+        )?;
+
+        let map: Map<String, Value> = serde_json::from_str(&json)?;
+        assert_eq!(map.get("payload"), None);
+
+        let verifier = alg.verifier_from_pem(&public_key)?;
+        let dst_header = context.deserialize_json_detached(&json, src_payload, &verifier)?;
+        assert_eq!(dst_header.algorithm(), Some("ES256"));
+        assert_eq!(dst_header.base64url_encode_payload(), Some(&false));
+
+        assert!(context
+            .deserialize_json_detached(&json, b"$.03", &verifier)
+            .is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_jws_general_json_serialization() -> Result<()> {
         let private_key_1 = load_file("pem/RSA_2048bit_private.pem")?;
@@ -1591,6 +2971,84 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jws_general_json_serialization_shares_payload() -> Result<()> {
+        let src_payload = b"test payload!";
+
+        let signer_1 = RS256.signer_from_pem(load_file("pem/RSA_2048bit_private.pem")?)?;
+        let signer_2 = ES256.signer_from_pem(load_file("pem/EC_P-256_private.pem")?)?;
+        let signer_3 = EdDSA.signer_from_pem(load_file("pem/ED25519_private.pem")?)?;
+
+        let mut multi_signer = JwsMultiSigner::new();
+        multi_signer.add_signer(None, None, &signer_1)?;
+        multi_signer.add_signer(None, None, &signer_2)?;
+        multi_signer.add_signer(None, None, &signer_3)?;
+
+        let json = jws::serialize_general_json(src_payload, &multi_signer)?;
+
+        // A duplicate top-level payload member is rejected rather than collapsed by this parser.
+        let map = util::parse_json_object(json.as_bytes())?;
+        assert_eq!(
+            map.get("payload"),
+            Some(&Value::String(base64::encode_config(
+                src_payload,
+                base64::URL_SAFE_NO_PAD
+            )))
+        );
+        match map.get("signatures") {
+            Some(Value::Array(vals)) => {
+                assert_eq!(vals.len(), 3);
+                for val in vals {
+                    match val {
+                        Value::Object(signature) => assert!(!signature.contains_key("payload")),
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        let verifier_1 = RS256.verifier_from_pem(load_file("pem/RSA_2048bit_public.pem")?)?;
+        let verifier_2 = ES256.verifier_from_pem(load_file("pem/EC_P-256_public.pem")?)?;
+        let verifier_3 = EdDSA.verifier_from_pem(load_file("pem/ED25519_public.pem")?)?;
+        for verifier in &[
+            &verifier_1 as &dyn JwsVerifier,
+            &verifier_2 as &dyn JwsVerifier,
+            &verifier_3 as &dyn JwsVerifier,
+        ] {
+            let (dst_payload, dst_header) = jws::deserialize_json(&json, *verifier)?;
+            assert_eq!(dst_header.algorithm(), Some(verifier.algorithm().name()));
+            assert_eq!(src_payload.to_vec(), dst_payload);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_algorithm_mismatch() -> Result<()> {
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let verifier = RS256.verifier_from_pem(load_file("pem/RSA_2048bit_public.pem")?)?;
+
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        let jws = jws::serialize_compact(b"test payload!", &header, &signer)?;
+        match jws::deserialize_compact(&jws, &verifier) {
+            Err(JoseError::AlgorithmMismatch { expected, actual }) => {
+                assert_eq!(expected, "RS256");
+                assert_eq!(actual, "HS256");
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let json = jws::serialize_flattened_json(b"test payload!", Some(&header), None, &signer)?;
+        assert!(matches!(
+            jws::deserialize_json_with_selector(&json, |_header| Ok(Some(&verifier))),
+            Err(JoseError::AlgorithmMismatch { .. })
+        ));
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::io::{self, Read};
 use std::ops::Deref;
 
 use anyhow::bail;
@@ -22,6 +23,18 @@ pub enum EcdsaJwsAlgorithm {
     ES512,
     /// ECDSA using secp256k1 curve and SHA-256
     ES256K,
+    /// ECDSA using brainpoolP256r1 curve and SHA-256
+    ///
+    /// This algorithm is not registered by RFC 7518 or RFC 8812.
+    ES256B,
+    /// ECDSA using brainpoolP384r1 curve and SHA-384
+    ///
+    /// This algorithm is not registered by RFC 7518 or RFC 8812.
+    ES384B,
+    /// ECDSA using brainpoolP512r1 curve and SHA-512
+    ///
+    /// This algorithm is not registered by RFC 7518 or RFC 8812.
+    ES512B,
 }
 
 impl EcdsaJwsAlgorithm {
@@ -110,6 +123,8 @@ impl EcdsaJwsAlgorithm {
                 Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
             }
 
+            util::check_private_jwk(jwk)?;
+
             let keypair = EcKeyPair::from_jwk(jwk, Some(self.curve()))?;
             let private_key = keypair.into_private_key();
             let key_id = jwk.key_id().map(|val| val.to_string());
@@ -254,6 +269,9 @@ impl EcdsaJwsAlgorithm {
             Self::ES384 => EcCurve::P384,
             Self::ES512 => EcCurve::P521,
             Self::ES256K => EcCurve::Secp256K1,
+            Self::ES256B => EcCurve::BrainpoolP256R1,
+            Self::ES384B => EcCurve::BrainpoolP384R1,
+            Self::ES512B => EcCurve::BrainpoolP512R1,
         }
     }
 
@@ -263,6 +281,9 @@ impl EcdsaJwsAlgorithm {
             Self::ES384 => HashAlgorithm::Sha384,
             Self::ES512 => HashAlgorithm::Sha512,
             Self::ES256K => HashAlgorithm::Sha256,
+            Self::ES256B => HashAlgorithm::Sha256,
+            Self::ES384B => HashAlgorithm::Sha384,
+            Self::ES512B => HashAlgorithm::Sha512,
         }
     }
 }
@@ -274,6 +295,9 @@ impl JwsAlgorithm for EcdsaJwsAlgorithm {
             Self::ES384 => "ES384",
             Self::ES512 => "ES512",
             Self::ES256K => "ES256K",
+            Self::ES256B => "ES256B",
+            Self::ES384B => "ES384B",
+            Self::ES512B => "ES512B",
         }
     }
 
@@ -325,8 +349,11 @@ impl JwsSigner for EcdsaJwsSigner {
         match self.algorithm {
             EcdsaJwsAlgorithm::ES256 => 64,
             EcdsaJwsAlgorithm::ES384 => 96,
-            EcdsaJwsAlgorithm::ES512 => 131,
+            EcdsaJwsAlgorithm::ES512 => 132,
             EcdsaJwsAlgorithm::ES256K => 64,
+            EcdsaJwsAlgorithm::ES256B => 64,
+            EcdsaJwsAlgorithm::ES384B => 96,
+            EcdsaJwsAlgorithm::ES512B => 128,
         }
     }
 
@@ -345,25 +372,24 @@ impl JwsSigner for EcdsaJwsSigner {
             signer.update(message)?;
             let signature = signer.sign_to_vec()?;
 
-            let mut der_signature = Vec::with_capacity(6 + 32 + 32);
+            let coordinate_size = self.algorithm.curve().coordinate_size();
+            let mut raw_signature = Vec::with_capacity(coordinate_size * 2);
             let mut reader = DerReader::from_bytes(&signature);
             match reader.next()? {
                 Some(DerType::Sequence) => {}
                 _ => unreachable!("A generated signature is invalid."),
             }
-            match reader.next()? {
-                Some(DerType::Integer) => {
-                    der_signature.extend_from_slice(&reader.to_be_bytes(false));
-                }
-                _ => unreachable!("A generated signature is invalid."),
-            }
-            match reader.next()? {
-                Some(DerType::Integer) => {
-                    der_signature.extend_from_slice(&reader.to_be_bytes(false));
+            for _ in 0..2 {
+                match reader.next()? {
+                    Some(DerType::Integer) => {
+                        let val = reader.to_be_bytes(false);
+                        raw_signature.resize(raw_signature.len() + coordinate_size - val.len(), 0);
+                        raw_signature.extend_from_slice(&val);
+                    }
+                    _ => unreachable!("A generated signature is invalid."),
                 }
-                _ => unreachable!("A generated signature is invalid."),
             }
-            Ok(der_signature)
+            Ok(raw_signature)
         })()
         .map_err(|err| JoseError::InvalidSignature(err))
     }
@@ -399,6 +425,27 @@ impl EcdsaJwsVerifier {
             }
         }
     }
+
+    fn to_der_signature(&self, signature: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let coordinate_size = self.algorithm.curve().coordinate_size();
+        if signature.len() != coordinate_size * 2 {
+            bail!(
+                "The signature size must be {}: {}",
+                coordinate_size * 2,
+                signature.len()
+            );
+        }
+
+        let mut der_builder = DerBuilder::new();
+        der_builder.begin(DerType::Sequence);
+        {
+            let (r, s) = signature.split_at(coordinate_size);
+            der_builder.append_integer_from_be_slice(trim_leading_zeros(r), false);
+            der_builder.append_integer_from_be_slice(trim_leading_zeros(s), false);
+        }
+        der_builder.end();
+        Ok(der_builder.build())
+    }
 }
 
 impl JwsVerifier for EcdsaJwsVerifier {
@@ -414,24 +461,23 @@ impl JwsVerifier for EcdsaJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        self.verify_reader(&mut &message[..], signature)
+    }
+
+    fn verify_reader(&self, message: &mut dyn Read, signature: &[u8]) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
-            let mut der_builder = DerBuilder::new(); // 6 + 33 + 33
-            der_builder.begin(DerType::Sequence);
-            {
-                der_builder.append_integer_from_be_slice(&signature[..32], false);
-                der_builder.append_integer_from_be_slice(&signature[32..], false);
-            }
-            der_builder.end();
-            let der_signature = der_builder.build();
+            let der_signature = self.to_der_signature(signature)?;
 
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut verifier = Verifier::new(md, &self.public_key)?;
-            verifier.update(message)?;
-            verifier.verify(&der_signature)?;
+            io::copy(message, &mut verifier)?;
+            if !verifier.verify(&der_signature)? {
+                bail!("Failed to verify.");
+            }
             Ok(())
         })()
-        .map_err(|err| JoseError::InvalidSignature(err))
+        .map_err(JoseError::InvalidSignature)
     }
 
     fn box_clone(&self) -> Box<dyn JwsVerifier> {
@@ -447,11 +493,19 @@ impl Deref for EcdsaJwsVerifier {
     }
 }
 
+fn trim_leading_zeros(value: &[u8]) -> &[u8] {
+    match value.iter().position(|val| *val != 0) {
+        Some(pos) => &value[pos..],
+        None => &value[(value.len() - 1)..],
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use anyhow::Result;
+    use openssl::ec::EcGroup;
     use std::fs::File;
     use std::io::Read;
     use std::path::PathBuf;
@@ -478,6 +532,98 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn verify_ecdsa_tampered_signature() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            EcdsaJwsAlgorithm::ES256,
+            EcdsaJwsAlgorithm::ES384,
+            EcdsaJwsAlgorithm::ES512,
+            EcdsaJwsAlgorithm::ES256K,
+        ] {
+            let keypair = alg.generate_keypair()?;
+
+            let signer = alg.signer_from_der(keypair.to_der_private_key())?;
+            let mut signature = signer.sign(input)?;
+            let last = signature.len() - 1;
+            signature[last] ^= 0x01;
+
+            let verifier = alg.verifier_from_der(keypair.to_der_public_key())?;
+            assert!(verifier.verify(input, &signature).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_ecdsa_signature_len() -> Result<()> {
+        let input = b"abcde12345";
+
+        for (alg, len) in &[
+            (EcdsaJwsAlgorithm::ES256, 64),
+            (EcdsaJwsAlgorithm::ES384, 96),
+            (EcdsaJwsAlgorithm::ES512, 132),
+            (EcdsaJwsAlgorithm::ES256K, 64),
+        ] {
+            let keypair = alg.generate_keypair()?;
+            let signer = alg.signer_from_der(keypair.to_der_private_key())?;
+            assert_eq!(signer.signature_len(), *len);
+
+            // r and s are padded to the coordinate size even when they have leading zeros.
+            for _ in 0..16 {
+                let signature = signer.sign(input)?;
+                assert_eq!(signature.len(), *len);
+            }
+
+            let verifier = alg.verifier_from_der(keypair.to_der_public_key())?;
+            assert!(verifier.verify(input, &vec![0; *len - 1]).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_ecdsa_brainpool() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            EcdsaJwsAlgorithm::ES256B,
+            EcdsaJwsAlgorithm::ES384B,
+            EcdsaJwsAlgorithm::ES512B,
+        ] {
+            // Some OpenSSL builds leave out the brainpool curves.
+            if EcGroup::from_curve_name(alg.curve().nid()).is_err() {
+                continue;
+            }
+
+            let keypair = alg.generate_keypair()?;
+
+            let signer = alg.signer_from_der(keypair.to_der_private_key())?;
+            let signature = signer.sign(input)?;
+            let verifier = alg.verifier_from_pem(keypair.to_pem_public_key())?;
+            verifier.verify(input, &signature)?;
+
+            let private_jwk = keypair.to_jwk_private_key();
+            let public_jwk = keypair.to_jwk_public_key();
+            assert_eq!(
+                public_jwk.parameter("crv"),
+                Some(&Value::String(alg.curve().name().to_string()))
+            );
+
+            let signer = alg.signer_from_jwk(&private_jwk)?;
+            let signature = signer.sign(input)?;
+            let verifier = alg.verifier_from_jwk(&public_jwk)?;
+            verifier.verify(input, &signature)?;
+
+            let keypair = EcKeyPair::from_jwk(&keypair.to_jwk_keypair(), None)?;
+            let verifier = alg.verifier_from_der(keypair.to_der_public_key())?;
+            verifier.verify(input, &signature)?;
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_ecdsa_generated_raw() -> Result<()> {
         let input = b"abcde12345";
@@ -570,24 +716,30 @@ mod tests {
     fn sign_and_verify_ecdsa_jwt() -> Result<()> {
         let input = b"abcde12345";
 
-        for alg in &[
-            EcdsaJwsAlgorithm::ES256,
-            EcdsaJwsAlgorithm::ES384,
-            EcdsaJwsAlgorithm::ES512,
-            EcdsaJwsAlgorithm::ES256K,
+        for (alg, private_key, public_key) in &[
+            (
+                EcdsaJwsAlgorithm::ES256,
+                "jwk/EC_P-256_private.jwk",
+                "jwk/EC_P-256_public.jwk",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES384,
+                "jwk/EC_P-384_private.jwk",
+                "jwk/EC_P-384_public.jwk",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES512,
+                "jwk/EC_P-521_private.jwk",
+                "jwk/EC_P-521_public.jwk",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES256K,
+                "jwk/EC_secp256k1_private.jwk",
+                "jwk/EC_secp256k1_public.jwk",
+            ),
         ] {
-            let private_key = load_file(match alg {
-                EcdsaJwsAlgorithm::ES256 => "jwk/EC_P-256_private.jwk",
-                EcdsaJwsAlgorithm::ES384 => "jwk/EC_P-384_private.jwk",
-                EcdsaJwsAlgorithm::ES512 => "jwk/EC_P-521_private.jwk",
-                EcdsaJwsAlgorithm::ES256K => "jwk/EC_secp256k1_private.jwk",
-            })?;
-            let public_key = load_file(match alg {
-                EcdsaJwsAlgorithm::ES256 => "jwk/EC_P-256_public.jwk",
-                EcdsaJwsAlgorithm::ES384 => "jwk/EC_P-384_public.jwk",
-                EcdsaJwsAlgorithm::ES512 => "jwk/EC_P-521_public.jwk",
-                EcdsaJwsAlgorithm::ES256K => "jwk/EC_secp256k1_public.jwk",
-            })?;
+            let private_key = load_file(private_key)?;
+            let public_key = load_file(public_key)?;
 
             let signer = alg.signer_from_jwk(&Jwk::from_slice(&private_key)?)?;
             let signature = signer.sign(input)?;
@@ -599,28 +751,48 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reject_ecdsa_signer_from_public_jwk() -> Result<()> {
+        let public_key = load_file("jwk/EC_P-256_public.jwk")?;
+        let public_key = Jwk::from_slice(&public_key)?;
+
+        let err = EcdsaJwsAlgorithm::ES256
+            .signer_from_jwk(&public_key)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert!(format!("{}", err).contains("signing requires a private key"));
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_ecdsa_pkcs8_pem() -> Result<()> {
         let input = b"abcde12345";
 
-        for alg in &[
-            EcdsaJwsAlgorithm::ES256,
-            EcdsaJwsAlgorithm::ES384,
-            EcdsaJwsAlgorithm::ES512,
-            EcdsaJwsAlgorithm::ES256K,
+        for (alg, private_key, public_key) in &[
+            (
+                EcdsaJwsAlgorithm::ES256,
+                "pem/EC_P-256_private.pem",
+                "pem/EC_P-256_public.pem",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES384,
+                "pem/EC_P-384_private.pem",
+                "pem/EC_P-384_public.pem",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES512,
+                "pem/EC_P-521_private.pem",
+                "pem/EC_P-521_public.pem",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES256K,
+                "pem/EC_secp256k1_private.pem",
+                "pem/EC_secp256k1_public.pem",
+            ),
         ] {
-            let private_key = load_file(match alg {
-                EcdsaJwsAlgorithm::ES256 => "pem/EC_P-256_private.pem",
-                EcdsaJwsAlgorithm::ES384 => "pem/EC_P-384_private.pem",
-                EcdsaJwsAlgorithm::ES512 => "pem/EC_P-521_private.pem",
-                EcdsaJwsAlgorithm::ES256K => "pem/EC_secp256k1_private.pem",
-            })?;
-            let public_key = load_file(match alg {
-                EcdsaJwsAlgorithm::ES256 => "pem/EC_P-256_public.pem",
-                EcdsaJwsAlgorithm::ES384 => "pem/EC_P-384_public.pem",
-                EcdsaJwsAlgorithm::ES512 => "pem/EC_P-521_public.pem",
-                EcdsaJwsAlgorithm::ES256K => "pem/EC_secp256k1_public.pem",
-            })?;
+            let private_key = load_file(private_key)?;
+            let public_key = load_file(public_key)?;
 
             let signer = alg.signer_from_pem(&private_key)?;
             let signature = signer.sign(input)?;
@@ -636,24 +808,30 @@ mod tests {
     fn sign_and_verify_ecdsa_pkcs8_der() -> Result<()> {
         let input = b"abcde12345";
 
-        for alg in &[
-            EcdsaJwsAlgorithm::ES256,
-            EcdsaJwsAlgorithm::ES384,
-            EcdsaJwsAlgorithm::ES512,
-            EcdsaJwsAlgorithm::ES256K,
+        for (alg, private_key, public_key) in &[
+            (
+                EcdsaJwsAlgorithm::ES256,
+                "der/EC_P-256_pkcs8_private.der",
+                "der/EC_P-256_spki_public.der",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES384,
+                "der/EC_P-384_pkcs8_private.der",
+                "der/EC_P-384_spki_public.der",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES512,
+                "der/EC_P-521_pkcs8_private.der",
+                "der/EC_P-521_spki_public.der",
+            ),
+            (
+                EcdsaJwsAlgorithm::ES256K,
+                "der/EC_secp256k1_pkcs8_private.der",
+                "der/EC_secp256k1_spki_public.der",
+            ),
         ] {
-            let private_key = load_file(match alg {
-                EcdsaJwsAlgorithm::ES256 => "der/EC_P-256_pkcs8_private.der",
-                EcdsaJwsAlgorithm::ES384 => "der/EC_P-384_pkcs8_private.der",
-                EcdsaJwsAlgorithm::ES512 => "der/EC_P-521_pkcs8_private.der",
-                EcdsaJwsAlgorithm::ES256K => "der/EC_secp256k1_pkcs8_private.der",
-            })?;
-            let public_key = load_file(match alg {
-                EcdsaJwsAlgorithm::ES256 => "der/EC_P-256_spki_public.der",
-                EcdsaJwsAlgorithm::ES384 => "der/EC_P-384_spki_public.der",
-                EcdsaJwsAlgorithm::ES512 => "der/EC_P-521_spki_public.der",
-                EcdsaJwsAlgorithm::ES256K => "der/EC_secp256k1_spki_public.der",
-            })?;
+            let private_key = load_file(private_key)?;
+            let public_key = load_file(public_key)?;
 
             let signer = alg.signer_from_der(&private_key)?;
             let signature = signer.sign(input)?;
@@ -1,8 +1,8 @@
 use std::fmt::Display;
 use std::ops::Deref;
 
-use anyhow::bail;
-use openssl::pkey::{PKey, Private, Public};
+use anyhow::{anyhow, bail};
+use openssl::pkey::{Id, PKey, Private, Public};
 use openssl::sign::{Signer, Verifier};
 use serde_json::Value;
 
@@ -65,6 +65,8 @@ impl EddsaJwsAlgorithm {
             curve: keypair.curve(),
             private_key: keypair.into_private_key(),
             key_id: None,
+            context: None,
+            prehash: false,
         })
     }
 
@@ -85,6 +87,8 @@ impl EddsaJwsAlgorithm {
             curve: keypair.curve(),
             private_key: keypair.into_private_key(),
             key_id: None,
+            context: None,
+            prehash: false,
         })
     }
 
@@ -108,6 +112,8 @@ impl EddsaJwsAlgorithm {
                 Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
             }
 
+            util::check_private_jwk(jwk)?;
+
             let keypair = EdKeyPair::from_jwk(jwk, None)?;
             let curve = keypair.curve();
             let private_key = keypair.into_private_key();
@@ -118,6 +124,8 @@ impl EddsaJwsAlgorithm {
                 curve,
                 private_key,
                 key_id,
+                context: None,
+                prehash: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -143,6 +151,8 @@ impl EddsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                context: None,
+                prehash: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -177,6 +187,8 @@ impl EddsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id: None,
+                context: None,
+                prehash: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -226,6 +238,8 @@ impl EddsaJwsAlgorithm {
                 algorithm: self.clone(),
                 public_key,
                 key_id,
+                context: None,
+                prehash: false,
             })
         })()
         .map_err(|err| JoseError::InvalidKeyFormat(err))
@@ -262,6 +276,8 @@ pub struct EddsaJwsSigner {
     curve: EdCurve,
     private_key: PKey<Private>,
     key_id: Option<String>,
+    context: Option<Vec<u8>>,
+    prehash: bool,
 }
 
 impl EddsaJwsSigner {
@@ -275,6 +291,40 @@ impl EddsaJwsSigner {
             }
         }
     }
+
+    /// Set a context string (Ed25519ctx, Ed25519ph, Ed448 and Ed448ph).
+    ///
+    /// This option requires OpenSSL 3.2 or later, otherwise signing and verification
+    /// fail with `JoseError::UnsupportedSignatureAlgorithm`.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - a context string up to 255 bytes
+    pub fn set_context(&mut self, context: Option<impl Into<Vec<u8>>>) {
+        self.context = context.map(|val| val.into());
+    }
+
+    /// Return the context string.
+    pub fn context(&self) -> Option<&[u8]> {
+        self.context.as_deref()
+    }
+
+    /// Set whether the message is prehashed (Ed25519ph and Ed448ph).
+    ///
+    /// This option requires OpenSSL 3.2 or later, otherwise signing and verification
+    /// fail with `JoseError::UnsupportedSignatureAlgorithm`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prehash` - true if the message is prehashed
+    pub fn set_prehash(&mut self, prehash: bool) {
+        self.prehash = prehash;
+    }
+
+    /// Return whether the message is prehashed.
+    pub fn is_prehash(&self) -> bool {
+        self.prehash
+    }
 }
 
 impl JwsSigner for EddsaJwsSigner {
@@ -297,7 +347,15 @@ impl JwsSigner for EddsaJwsSigner {
     }
 
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
+        let context = self.context.as_deref().unwrap_or(&[]);
+        let instance = instance_name(self.private_key.id(), context, self.prehash)?;
+
         (|| -> anyhow::Result<Vec<u8>> {
+            if let Some(instance) = instance {
+                let signature = instance::sign(&self.private_key, instance, context, message)?;
+                return Ok(signature);
+            }
+
             let mut signer = Signer::new_without_digest(&self.private_key)?;
             let mut signature = vec![0; signer.len()?];
             signer.sign_oneshot(&mut signature, message)?;
@@ -324,6 +382,8 @@ pub struct EddsaJwsVerifier {
     algorithm: EddsaJwsAlgorithm,
     public_key: PKey<Public>,
     key_id: Option<String>,
+    context: Option<Vec<u8>>,
+    prehash: bool,
 }
 
 impl EddsaJwsVerifier {
@@ -337,6 +397,40 @@ impl EddsaJwsVerifier {
             }
         }
     }
+
+    /// Set a context string (Ed25519ctx, Ed25519ph, Ed448 and Ed448ph).
+    ///
+    /// This option requires OpenSSL 3.2 or later, otherwise signing and verification
+    /// fail with `JoseError::UnsupportedSignatureAlgorithm`.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - a context string up to 255 bytes
+    pub fn set_context(&mut self, context: Option<impl Into<Vec<u8>>>) {
+        self.context = context.map(|val| val.into());
+    }
+
+    /// Return the context string.
+    pub fn context(&self) -> Option<&[u8]> {
+        self.context.as_deref()
+    }
+
+    /// Set whether the message is prehashed (Ed25519ph and Ed448ph).
+    ///
+    /// This option requires OpenSSL 3.2 or later, otherwise signing and verification
+    /// fail with `JoseError::UnsupportedSignatureAlgorithm`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prehash` - true if the message is prehashed
+    pub fn set_prehash(&mut self, prehash: bool) {
+        self.prehash = prehash;
+    }
+
+    /// Return whether the message is prehashed.
+    pub fn is_prehash(&self) -> bool {
+        self.prehash
+    }
 }
 
 impl JwsVerifier for EddsaJwsVerifier {
@@ -352,9 +446,21 @@ impl JwsVerifier for EddsaJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        let context = self.context.as_deref().unwrap_or(&[]);
+        let instance = instance_name(self.public_key.id(), context, self.prehash)?;
+
         (|| -> anyhow::Result<()> {
+            if let Some(instance) = instance {
+                if !instance::verify(&self.public_key, instance, context, message, signature)? {
+                    bail!("Failed to verify.");
+                }
+                return Ok(());
+            }
+
             let mut verifier = Verifier::new_without_digest(&self.public_key)?;
-            verifier.verify_oneshot(signature, message)?;
+            if !verifier.verify_oneshot(signature, message)? {
+                bail!("Failed to verify.");
+            }
             Ok(())
         })()
         .map_err(|err| JoseError::InvalidSignature(err))
@@ -373,6 +479,136 @@ impl Deref for EddsaJwsVerifier {
     }
 }
 
+/// Return the name of OpenSSL EdDSA instance, or None for plain Ed25519 and Ed448.
+fn instance_name(
+    id: Id,
+    context: &[u8],
+    prehash: bool,
+) -> Result<Option<&'static [u8]>, JoseError> {
+    if context.len() > 255 {
+        return Err(JoseError::InvalidSignature(anyhow!(
+            "The context string must be 255 bytes or less: {}",
+            context.len()
+        )));
+    }
+
+    let instance: &'static [u8] = match (id, prehash) {
+        (Id::ED25519, true) => b"Ed25519ph\0",
+        (Id::ED25519, false) if !context.is_empty() => b"Ed25519ctx\0",
+        (Id::ED448, true) => b"Ed448ph\0",
+        (Id::ED448, false) if !context.is_empty() => b"Ed448\0",
+        _ => return Ok(None),
+    };
+
+    if !cfg!(ossl300) || openssl::version::number() < 0x3020_0000 {
+        return Err(JoseError::UnsupportedSignatureAlgorithm(anyhow!(
+            "The EdDSA context string and prehash require OpenSSL 3.2 or later: {}",
+            openssl::version::version()
+        )));
+    }
+
+    Ok(Some(instance))
+}
+
+#[cfg(ossl300)]
+mod instance {
+    use std::os::raw::{c_char, c_uint, c_void};
+
+    use openssl::error::ErrorStack;
+    use openssl::md_ctx::MdCtx;
+    use openssl::pkey::{HasPublic, PKeyRef, Private};
+    use openssl::pkey_ctx::PkeyCtxRef;
+    use openssl_sys::{
+        EVP_PKEY_CTX_set_params, OSSL_PARAM_construct_end, OSSL_PARAM_construct_octet_string,
+        EVP_PKEY_CTX, OSSL_PARAM,
+    };
+
+    const OSSL_PARAM_UTF8_STRING: c_uint = 4;
+
+    pub(super) fn sign(
+        private_key: &PKeyRef<Private>,
+        instance: &'static [u8],
+        context: &[u8],
+        message: &[u8],
+    ) -> Result<Vec<u8>, ErrorStack> {
+        let mut ctx = MdCtx::new()?;
+        set_params(ctx.digest_sign_init(None, private_key)?, instance, context)?;
+        let mut signature = Vec::new();
+        ctx.digest_sign_to_vec(message, &mut signature)?;
+        Ok(signature)
+    }
+
+    pub(super) fn verify<T: HasPublic>(
+        public_key: &PKeyRef<T>,
+        instance: &'static [u8],
+        context: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, ErrorStack> {
+        let mut ctx = MdCtx::new()?;
+        set_params(ctx.digest_verify_init(None, public_key)?, instance, context)?;
+        ctx.digest_verify(message, signature)
+    }
+
+    /// Select the EdDSA instance and context string. The openssl crate has no
+    /// safe wrapper for these parameters.
+    fn set_params<T>(
+        pctx: &mut PkeyCtxRef<T>,
+        instance: &'static [u8],
+        context: &[u8],
+    ) -> Result<(), ErrorStack> {
+        unsafe {
+            let params = [
+                OSSL_PARAM {
+                    key: b"instance\0".as_ptr() as *const c_char,
+                    data_type: OSSL_PARAM_UTF8_STRING,
+                    data: instance.as_ptr() as *mut c_void,
+                    data_size: instance.len() - 1,
+                    return_size: usize::MAX,
+                },
+                OSSL_PARAM_construct_octet_string(
+                    b"context-string\0".as_ptr() as *const c_char,
+                    context.as_ptr() as *mut c_void,
+                    context.len(),
+                ),
+                OSSL_PARAM_construct_end(),
+            ];
+
+            // A PkeyCtxRef is an opaque reference to the underlying EVP_PKEY_CTX.
+            let pctx = pctx as *mut PkeyCtxRef<T> as *mut EVP_PKEY_CTX;
+            if EVP_PKEY_CTX_set_params(pctx, params.as_ptr()) <= 0 {
+                return Err(ErrorStack::get());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(ossl300))]
+mod instance {
+    use openssl::error::ErrorStack;
+    use openssl::pkey::{HasPublic, PKeyRef, Private};
+
+    pub(super) fn sign(
+        _private_key: &PKeyRef<Private>,
+        _instance: &'static [u8],
+        _context: &[u8],
+        _message: &[u8],
+    ) -> Result<Vec<u8>, ErrorStack> {
+        unreachable!("instance_name() rejects EdDSA instances before OpenSSL 3.0")
+    }
+
+    pub(super) fn verify<T: HasPublic>(
+        _public_key: &PKeyRef<T>,
+        _instance: &'static [u8],
+        _context: &[u8],
+        _message: &[u8],
+        _signature: &[u8],
+    ) -> Result<bool, ErrorStack> {
+        unreachable!("instance_name() rejects EdDSA instances before OpenSSL 3.0")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -400,6 +636,71 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn verify_eddsa_tampered_signature() -> Result<()> {
+        let input = b"abcde12345";
+
+        for curve in &[EdCurve::Ed25519, EdCurve::Ed448] {
+            let alg = EddsaJwsAlgorithm::EdDSA;
+            let keypair = alg.generate_keypair(*curve)?;
+
+            let signer = alg.signer_from_der(keypair.to_der_private_key())?;
+            let mut signature = signer.sign(input)?;
+            signature[0] ^= 0x01;
+
+            let verifier = alg.verifier_from_der(keypair.to_der_public_key())?;
+            assert!(verifier.verify(input, &signature).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_and_verify_eddsa_with_context() -> Result<()> {
+        let input = b"abcde12345";
+
+        let alg = EddsaJwsAlgorithm::EdDSA;
+        let keypair = alg.generate_keypair(EdCurve::Ed448)?;
+
+        let mut signer = alg.signer_from_der(keypair.to_der_private_key())?;
+        signer.set_context(Some(b"context"));
+
+        if !cfg!(ossl300) || openssl::version::number() < 0x3020_0000 {
+            assert!(matches!(
+                signer.sign(input),
+                Err(JoseError::UnsupportedSignatureAlgorithm(_))
+            ));
+            return Ok(());
+        }
+
+        let signature = signer.sign(input)?;
+
+        let mut verifier = alg.verifier_from_der(keypair.to_der_public_key())?;
+        assert!(verifier.verify(input, &signature).is_err());
+
+        verifier.set_context(Some(b"context"));
+        verifier.verify(input, &signature)?;
+
+        verifier.set_context(Some(b"another"));
+        assert!(verifier.verify(input, &signature).is_err());
+
+        for curve in [EdCurve::Ed25519, EdCurve::Ed448] {
+            let keypair = alg.generate_keypair(curve)?;
+
+            let mut signer = alg.signer_from_der(keypair.to_der_private_key())?;
+            signer.set_prehash(true);
+            let signature = signer.sign(input)?;
+
+            let mut verifier = alg.verifier_from_der(keypair.to_der_public_key())?;
+            assert!(verifier.verify(input, &signature).is_err());
+
+            verifier.set_prehash(true);
+            verifier.verify(input, &signature)?;
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_eddsa_generated_pem() -> Result<()> {
         let input = b"abcde12345";
@@ -472,6 +773,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reject_eddsa_signer_from_public_jwk() -> Result<()> {
+        let public_key = load_file("jwk/OKP_Ed25519_public.jwk")?;
+        let public_key = Jwk::from_slice(&public_key)?;
+
+        let err = EddsaJwsAlgorithm::EdDSA
+            .signer_from_jwk(&public_key)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert!(format!("{}", err).contains("signing requires a private key"));
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_eddsa_pkcs8_pem() -> Result<()> {
         let input = b"abcde12345";
@@ -1,10 +1,21 @@
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
+use std::io::{self, Read};
 use std::ops::Deref;
 
 use anyhow::bail;
+#[cfg(feature = "pure-rust-hmac")]
+use hmac::{Hmac, Mac};
+#[cfg(not(feature = "pure-rust-hmac"))]
+use openssl::hash::MessageDigest;
+#[cfg(not(feature = "pure-rust-hmac"))]
+use openssl::memcmp;
+#[cfg(not(feature = "pure-rust-hmac"))]
 use openssl::pkey::{PKey, Private};
+#[cfg(not(feature = "pure-rust-hmac"))]
 use openssl::sign::Signer;
 use serde_json::Value;
+#[cfg(feature = "pure-rust-hmac")]
+use sha2::{Sha256, Sha384, Sha512};
 
 use crate::jose::JoseError;
 use crate::jwk::Jwk;
@@ -46,11 +57,11 @@ impl HmacJwsAlgorithm {
     /// * `data` - A secret key.
     pub fn signer_from_slice(&self, input: impl AsRef<[u8]>) -> Result<HmacJwsSigner, JoseError> {
         (|| -> anyhow::Result<HmacJwsSigner> {
-            let pkey = PKey::hmac(input.as_ref())?;
+            let context = self.context(input.as_ref())?;
 
             Ok(HmacJwsSigner {
                 algorithm: self.clone(),
-                private_key: pkey,
+                context,
                 key_id: None,
             })
         })()
@@ -86,12 +97,12 @@ impl HmacJwsAlgorithm {
                 None => bail!("A parameter k is required."),
             };
 
-            let private_key = PKey::hmac(&k)?;
+            let context = self.context(&k)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
 
             Ok(HmacJwsSigner {
                 algorithm: self.clone(),
-                private_key,
+                context,
                 key_id,
             })
         })()
@@ -112,11 +123,11 @@ impl HmacJwsAlgorithm {
                 bail!("Secret key size must be larger than or equal to the hash output size.");
             }
 
-            let private_key = PKey::hmac(input)?;
+            let context = self.context(input)?;
 
             Ok(HmacJwsVerifier {
                 algorithm: self.clone(),
-                private_key,
+                context,
                 key_id: None,
             })
         })()
@@ -153,12 +164,12 @@ impl HmacJwsAlgorithm {
                 None => bail!("A parameter k is required."),
             };
 
-            let private_key = PKey::hmac(&k)?;
+            let context = self.context(&k)?;
             let key_id = jwk.key_id().map(|val| val.to_string());
 
             Ok(HmacJwsVerifier {
                 algorithm: self.clone(),
-                private_key,
+                context,
                 key_id,
             })
         })()
@@ -172,6 +183,10 @@ impl HmacJwsAlgorithm {
             Self::HS512 => HashAlgorithm::Sha512,
         }
     }
+
+    fn context(&self, key: &[u8]) -> anyhow::Result<HmacContext> {
+        HmacContext::new(self.hash_algorithm(), key)
+    }
 }
 
 impl JwsAlgorithm for HmacJwsAlgorithm {
@@ -205,7 +220,7 @@ impl Deref for HmacJwsAlgorithm {
 #[derive(Debug, Clone)]
 pub struct HmacJwsSigner {
     algorithm: HmacJwsAlgorithm,
-    private_key: PKey<Private>,
+    context: HmacContext,
     key_id: Option<String>,
 }
 
@@ -240,11 +255,7 @@ impl JwsSigner for HmacJwsSigner {
 
     fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JoseError> {
         (|| -> anyhow::Result<Vec<u8>> {
-            let md = self.algorithm.hash_algorithm().message_digest();
-
-            let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
-            let signature = signer.sign_to_vec()?;
+            let signature = self.context.sign(message)?;
             Ok(signature)
         })()
         .map_err(|err| JoseError::InvalidSignature(err))
@@ -266,7 +277,7 @@ impl Deref for HmacJwsSigner {
 #[derive(Debug, Clone)]
 pub struct HmacJwsVerifier {
     algorithm: HmacJwsAlgorithm,
-    private_key: PKey<Private>,
+    context: HmacContext,
     key_id: Option<String>,
 }
 
@@ -296,18 +307,13 @@ impl JwsVerifier for HmacJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
-        (|| -> anyhow::Result<()> {
-            let md = self.algorithm.hash_algorithm().message_digest();
-
-            let mut signer = Signer::new(md, &self.private_key)?;
-            signer.update(message)?;
-            let new_signature = signer.sign_to_vec()?;
-            if new_signature.as_slice() != signature {
-                bail!("Failed to verify.");
-            }
-            Ok(())
-        })()
-        .map_err(|err| JoseError::InvalidSignature(err))
+        self.verify_reader(&mut &message[..], signature)
+    }
+
+    fn verify_reader(&self, message: &mut dyn Read, signature: &[u8]) -> Result<(), JoseError> {
+        self.context
+            .verify_reader(message, signature)
+            .map_err(JoseError::InvalidSignature)
     }
 
     fn box_clone(&self) -> Box<dyn JwsVerifier> {
@@ -323,11 +329,130 @@ impl Deref for HmacJwsVerifier {
     }
 }
 
+/// A HMAC key of the OpenSSL backend. Every message is signed by a new OpenSSL signer.
+#[cfg(not(feature = "pure-rust-hmac"))]
+#[derive(Clone)]
+struct HmacContext {
+    md: MessageDigest,
+    private_key: PKey<Private>,
+}
+
+#[cfg(not(feature = "pure-rust-hmac"))]
+impl HmacContext {
+    fn new(hash_algorithm: HashAlgorithm, key: &[u8]) -> anyhow::Result<Self> {
+        let private_key = PKey::hmac(key)?;
+        Ok(Self {
+            md: hash_algorithm.message_digest(),
+            private_key,
+        })
+    }
+
+    fn sign(&self, message: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut signer = Signer::new(self.md, &self.private_key)?;
+        signer.update(message)?;
+        Ok(signer.sign_to_vec()?)
+    }
+
+    fn sign_reader(&self, message: &mut dyn Read) -> anyhow::Result<Vec<u8>> {
+        let mut signer = Signer::new(self.md, &self.private_key)?;
+        io::copy(message, &mut signer)?;
+        Ok(signer.sign_to_vec()?)
+    }
+
+    fn verify_reader(&self, message: &mut dyn Read, signature: &[u8]) -> anyhow::Result<()> {
+        let new_signature = self.sign_reader(message)?;
+        if new_signature.len() != signature.len() || !memcmp::eq(&new_signature, signature) {
+            bail!("Failed to verify.");
+        }
+        Ok(())
+    }
+}
+
+/// A HMAC (RFC 2104) state of the pure Rust implementation that has absorbed the key in advance.
+///
+/// This is used instead of OpenSSL when the pure-rust-hmac feature is enabled.
+#[cfg(feature = "pure-rust-hmac")]
+#[derive(Clone)]
+enum HmacContext {
+    Sha256(Hmac<Sha256>),
+    Sha384(Hmac<Sha384>),
+    Sha512(Hmac<Sha512>),
+}
+
+#[cfg(feature = "pure-rust-hmac")]
+impl HmacContext {
+    fn new(hash_algorithm: HashAlgorithm, key: &[u8]) -> anyhow::Result<Self> {
+        let context = match hash_algorithm {
+            HashAlgorithm::Sha256 => Self::Sha256(Hmac::new_from_slice(key)?),
+            HashAlgorithm::Sha384 => Self::Sha384(Hmac::new_from_slice(key)?),
+            HashAlgorithm::Sha512 => Self::Sha512(Hmac::new_from_slice(key)?),
+        };
+        Ok(context)
+    }
+
+    fn sign(&self, message: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut context = self.clone();
+        context.update(message);
+        Ok(context.finish())
+    }
+
+    fn verify_reader(&self, message: &mut dyn Read, signature: &[u8]) -> anyhow::Result<()> {
+        let result = match self.read(message)? {
+            Self::Sha256(mac) => mac.verify_slice(signature),
+            Self::Sha384(mac) => mac.verify_slice(signature),
+            Self::Sha512(mac) => mac.verify_slice(signature),
+        };
+        if result.is_err() {
+            bail!("Failed to verify.");
+        }
+        Ok(())
+    }
+
+    fn read(&self, message: &mut dyn Read) -> anyhow::Result<Self> {
+        let mut context = self.clone();
+        let mut buf = [0; 8192];
+        loop {
+            let len = match message.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            context.update(&buf[..len]);
+        }
+        Ok(context)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(mac) => mac.update(data),
+            Self::Sha384(mac) => mac.update(data),
+            Self::Sha512(mac) => mac.update(data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Self::Sha256(mac) => mac.finalize().into_bytes().to_vec(),
+            Self::Sha384(mac) => mac.finalize().into_bytes().to_vec(),
+            Self::Sha512(mac) => mac.finalize().into_bytes().to_vec(),
+        }
+    }
+}
+
+impl Debug for HmacContext {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        fmt.write_str("HmacContext")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use anyhow::Result;
+    use openssl::pkey::PKey;
+    use openssl::sign::Signer;
     use std::fs::File;
     use std::io::Read;
     use std::path::PathBuf;
@@ -395,6 +520,99 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_hmac_same_as_openssl() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            HmacJwsAlgorithm::HS256,
+            HmacJwsAlgorithm::HS384,
+            HmacJwsAlgorithm::HS512,
+        ] {
+            let md = alg.hash_algorithm().message_digest();
+            // Shorter than, equal to and longer than the block size of the hash function.
+            for key_len in &[1, 32, md.block_size(), md.block_size() + 1, 300] {
+                let private_key = util::rand_bytes(*key_len);
+
+                let pkey = PKey::hmac(&private_key)?;
+                let mut expected = Signer::new(md, &pkey)?;
+                expected.update(input)?;
+                let expected = expected.sign_to_vec()?;
+
+                let signer = alg.signer_from_slice(&private_key)?;
+                assert_eq!(signer.sign(input)?, expected);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_hmac_invalid_signature() -> Result<()> {
+        let private_key = util::rand_bytes(64);
+        let input = b"abcde12345";
+
+        for alg in &[
+            HmacJwsAlgorithm::HS256,
+            HmacJwsAlgorithm::HS384,
+            HmacJwsAlgorithm::HS512,
+        ] {
+            let signer = alg.signer_from_slice(&private_key)?;
+            let verifier = alg.verifier_from_slice(&private_key)?;
+
+            let mut signature = signer.sign(input)?;
+            verifier.verify(input, &signature)?;
+            assert!(verifier.verify(input, &signature[1..]).is_err());
+            signature[0] ^= 1;
+            assert!(verifier.verify(input, &signature).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_hmac_known_answer() -> Result<()> {
+        // RFC 4231 test case 2
+        let key = b"Jefe";
+        let input = b"what do ya want for nothing?";
+
+        for (alg, expected) in &[
+            (
+                HmacJwsAlgorithm::HS256,
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                HmacJwsAlgorithm::HS384,
+                "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47e42ec373632244\
+                 5e8e2240ca5e69e2c78b3239ecfab21649",
+            ),
+            (
+                HmacJwsAlgorithm::HS512,
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505\
+                 549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            ),
+        ] {
+            let signer = alg.signer_from_slice(key)?;
+            let signature = signer.sign(input)?;
+            let actual: String = signature.iter().map(|b| format!("{:02x}", b)).collect();
+            assert_eq!(&actual, expected);
+
+            let md = alg.hash_algorithm().message_digest();
+            let pkey = PKey::hmac(key)?;
+            let mut openssl_signer = Signer::new(md, &pkey)?;
+            openssl_signer.update(input)?;
+            assert_eq!(signature, openssl_signer.sign_to_vec()?);
+        }
+
+        Ok(())
+    }
+
+    /// Compare the time of a HMAC that loads the key into OpenSSL for each message against
+    /// the signer that keeps the loaded key.
+    ///
+    /// Each variant signs the same 300 bytes message 100,000 times after a warm-up of 1,000
+    /// messages, and the signer must not be slower on average. Run it in release mode:
+    /// `cargo test --release sign_hmac_benchmark -- --ignored`
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::io::{self, Read};
 use std::ops::Deref;
 
 use anyhow::bail;
@@ -145,6 +146,8 @@ impl RsassaJwsAlgorithm {
                 Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
             }
 
+            util::check_private_jwk(jwk)?;
+
             let keypair = RsaKeyPair::from_jwk(jwk)?;
             if keypair.key_len() * 8 < 2048 {
                 bail!("key length must be 2048 or more.");
@@ -434,15 +437,21 @@ impl JwsVerifier for RsassaJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        self.verify_reader(&mut &message[..], signature)
+    }
+
+    fn verify_reader(&self, message: &mut dyn Read, signature: &[u8]) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut verifier = Verifier::new(md, &self.public_key)?;
-            verifier.update(message)?;
-            verifier.verify(signature)?;
+            io::copy(message, &mut verifier)?;
+            if !verifier.verify(signature)? {
+                bail!("Failed to verify.");
+            }
             Ok(())
         })()
-        .map_err(|err| JoseError::InvalidSignature(err))
+        .map_err(JoseError::InvalidSignature)
     }
 
     fn box_clone(&self) -> Box<dyn JwsVerifier> {
@@ -488,6 +497,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn verify_rsassa_tampered_signature() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            RsassaJwsAlgorithm::RS256,
+            RsassaJwsAlgorithm::RS384,
+            RsassaJwsAlgorithm::RS512,
+        ] {
+            let keypair = alg.generate_keypair(2048)?;
+
+            let signer = alg.signer_from_der(keypair.to_der_private_key())?;
+            let mut signature = signer.sign(input)?;
+            signature[0] ^= 0x01;
+
+            let verifier = alg.verifier_from_der(keypair.to_der_public_key())?;
+            assert!(verifier.verify(input, &signature).is_err());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_rsassa_generated_raw() -> Result<()> {
         let input = b"abcde12345";
@@ -594,6 +625,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reject_rsassa_signer_from_public_jwk() -> Result<()> {
+        let public_key = load_file("jwk/RSA_public.jwk")?;
+        let public_key = Jwk::from_slice(&public_key)?;
+
+        let err = RsassaJwsAlgorithm::RS256
+            .signer_from_jwk(&public_key)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert!(format!("{}", err).contains("signing requires a private key"));
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_rsassa_pkcs8_pem() -> Result<()> {
         let input = b"abcde12345";
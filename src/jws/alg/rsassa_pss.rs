@@ -1,9 +1,11 @@
 use std::fmt::Display;
+use std::io::{self, Read};
 use std::ops::Deref;
 
 use anyhow::bail;
 use openssl::pkey::{PKey, Private, Public};
-use openssl::sign::{Signer, Verifier};
+use openssl::rsa::Padding;
+use openssl::sign::{RsaPssSaltlen, Signer, Verifier};
 use serde_json::Value;
 
 use crate::der::{DerBuilder, DerType};
@@ -164,6 +166,8 @@ impl RsassaPssJwsAlgorithm {
                 Some(val) => bail!("A parameter alg must be {} but {}", self.name(), val),
             }
 
+            util::check_private_jwk(jwk)?;
+
             let keypair = RsaPssKeyPair::from_jwk(
                 jwk,
                 self.hash_algorithm(),
@@ -374,12 +378,9 @@ impl RsassaPssJwsAlgorithm {
         }
     }
 
-    fn salt_len(&self) -> u8 {
-        match self {
-            Self::PS256 => 32,
-            Self::PS384 => 48,
-            Self::PS512 => 64,
-        }
+    /// Return the salt length, which is the output size of the hash algorithm as RFC 7518 requires.
+    pub fn salt_len(&self) -> u8 {
+        self.hash_algorithm().output_len() as u8
     }
 }
 
@@ -452,6 +453,9 @@ impl JwsSigner for RsassaPssJwsSigner {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut signer = Signer::new(md, &self.private_key)?;
+            signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+            signer.set_rsa_mgf1_md(md)?;
+            signer.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
             signer.update(message)?;
             let signature = signer.sign_to_vec()?;
             Ok(signature)
@@ -505,15 +509,24 @@ impl JwsVerifier for RsassaPssJwsVerifier {
     }
 
     fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        self.verify_reader(&mut &message[..], signature)
+    }
+
+    fn verify_reader(&self, message: &mut dyn Read, signature: &[u8]) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             let md = self.algorithm.hash_algorithm().message_digest();
 
             let mut verifier = Verifier::new(md, &self.public_key)?;
-            verifier.update(message)?;
-            verifier.verify(signature)?;
+            verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+            verifier.set_rsa_mgf1_md(md)?;
+            verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+            io::copy(message, &mut verifier)?;
+            if !verifier.verify(signature)? {
+                bail!("Failed to verify.");
+            }
             Ok(())
         })()
-        .map_err(|err| JoseError::InvalidSignature(err))
+        .map_err(JoseError::InvalidSignature)
     }
 
     fn box_clone(&self) -> Box<dyn JwsVerifier> {
@@ -559,6 +572,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn verify_rsassa_pss_tampered_signature() -> Result<()> {
+        let input = b"abcde12345";
+
+        for alg in &[
+            RsassaPssJwsAlgorithm::PS256,
+            RsassaPssJwsAlgorithm::PS384,
+            RsassaPssJwsAlgorithm::PS512,
+        ] {
+            let keypair = alg.generate_keypair(2048)?;
+
+            let signer = alg.signer_from_der(keypair.to_der_private_key())?;
+            let mut signature = signer.sign(input)?;
+            signature[0] ^= 0x01;
+
+            let verifier = alg.verifier_from_der(keypair.to_der_public_key())?;
+            assert!(verifier.verify(input, &signature).is_err());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_rsassa_pss_generated_raw() -> Result<()> {
         let input = b"abcde12345";
@@ -665,6 +700,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reject_rsassa_pss_signer_from_public_jwk() -> Result<()> {
+        let public_key = load_file("jwk/RSA_public.jwk")?;
+        let public_key = Jwk::from_slice(&public_key)?;
+
+        let err = RsassaPssJwsAlgorithm::PS256
+            .signer_from_jwk(&public_key)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidKeyFormat(_)));
+        assert!(format!("{}", err).contains("signing requires a private key"));
+
+        Ok(())
+    }
+
     #[test]
     fn sign_and_verify_rsassa_pss_pkcs8_pem() -> Result<()> {
         let input = b"abcde12345";
@@ -725,6 +774,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sign_and_verify_rsassa_pss_digest_salt_len() -> Result<()> {
+        let input = b"abcde12345";
+        let private_key = load_file("der/RSA_2048bit_raw_private.der")?;
+        let public_key = load_file("der/RSA_2048bit_raw_public.der")?;
+
+        for alg in &[
+            RsassaPssJwsAlgorithm::PS256,
+            RsassaPssJwsAlgorithm::PS384,
+            RsassaPssJwsAlgorithm::PS512,
+        ] {
+            let md = alg.hash_algorithm().message_digest();
+            assert_eq!(alg.salt_len() as usize, md.size());
+
+            let signer = alg.signer_from_der(&private_key)?;
+            let verifier = alg.verifier_from_der(&public_key)?;
+
+            // A signature made by this crate is accepted by a peer that expects a
+            // digest-length salt.
+            let signature = signer.sign(input)?;
+            let mut external = Verifier::new(md, &verifier.public_key)?;
+            external.set_rsa_padding(Padding::PKCS1_PSS)?;
+            external.set_rsa_mgf1_md(md)?;
+            external.set_rsa_pss_saltlen(RsaPssSaltlen::custom(md.size() as i32))?;
+            external.update(input)?;
+            assert!(external.verify(&signature)?);
+
+            // A signature made by a peer with a digest-length salt is accepted,
+            // but one with another salt length is not.
+            for (salt_len, expected) in &[(md.size() as i32, true), (md.size() as i32 + 8, false)] {
+                let mut external = Signer::new(md, &signer.private_key)?;
+                external.set_rsa_padding(Padding::PKCS1_PSS)?;
+                external.set_rsa_mgf1_md(md)?;
+                external.set_rsa_pss_saltlen(RsaPssSaltlen::custom(*salt_len))?;
+                external.update(input)?;
+                let signature = external.sign_to_vec()?;
+                assert_eq!(verifier.verify(input, &signature).is_ok(), *expected);
+            }
+        }
+
+        Ok(())
+    }
+
     fn load_file(path: &str) -> Result<Vec<u8>> {
         let mut pb = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         pb.push("data");
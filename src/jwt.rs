@@ -1,25 +1,39 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
 use std::convert::Into;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
 use serde_json::{Map, Number, Value};
 
-use crate::jose::{JoseError, JoseHeader};
+use crate::jose::{Base64Alphabet, JoseError, JoseHeader};
 use crate::jwe::{JweContext, JweDecrypter, JweEncrypter, JweHeader};
 use crate::jwk::{Jwk, JwkSet};
-use crate::jws::{JwsContext, JwsHeader, JwsSigner, JwsVerifier};
-use crate::util::{self, SourceValue};
+use crate::jws::{
+    EdDSA, JwsContext, JwsHeader, JwsSigner, JwsVerifier, VerifierCache, ES256, ES256K, ES384,
+    ES512, PS256, PS384, PS512, RS256, RS384, RS512,
+};
+use crate::util::{self, SharedFn, SourceValue};
 
 static DEFAULT_CONTEXT: Lazy<JwtContext> = Lazy::new(|| JwtContext::new());
 
+/// A context for encoding and decoding JWT.
+///
+/// A context is `Send + Sync`. It can be configured once and then shared read-only across
+/// threads, for example behind an `Arc`.
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct JwtContext {
     jws_context: JwsContext,
     jwe_context: JweContext,
+    max_keys_without_kid: usize,
+    default_token_type: Option<String>,
+    acceptable_nested_content_types: BTreeSet<String>,
 }
 
 impl JwtContext {
@@ -27,6 +41,13 @@ impl JwtContext {
         Self {
             jws_context: JwsContext::new(),
             jwe_context: JweContext::new(),
+            max_keys_without_kid: 0,
+            default_token_type: None,
+            acceptable_nested_content_types: {
+                let mut set = BTreeSet::new();
+                set.insert("application/jwt".to_string());
+                set
+            },
         }
     }
 
@@ -59,16 +80,149 @@ impl JwtContext {
         self.jwe_context.remove_acceptable_critical(name);
     }
 
+    /// Add a validator for a critical header claim of JWS, and make the name acceptable.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a critical header claim name
+    /// * `validator` - a function for validating the header claim value
+    pub fn add_critical_validator<F>(&mut self, name: &str, validator: F)
+    where
+        F: Fn(&Value) -> Result<(), JoseError> + Send + Sync + 'static,
+    {
+        self.jws_context.add_critical_validator(name, validator);
+        self.jwe_context.add_acceptable_critical(name);
+    }
+
+    /// Remove a validator for a critical header claim of JWS. The name stays acceptable.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a critical header claim name
+    pub fn remove_critical_validator(&mut self, name: &str) {
+        self.jws_context.remove_critical_validator(name);
+    }
+
+    /// Set the base64 alphabet used for the segments of JWT for both JWS and JWE.
+    ///
+    /// The default is `Base64Alphabet::UrlSafeNoPad` as required by RFC 7519. Any other
+    /// alphabet produces and accepts non-standard tokens, and should only be used to
+    /// interoperate with consumers that do not follow the specification.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a base64 alphabet
+    pub fn set_base64_alphabet(&mut self, value: Base64Alphabet) {
+        self.jws_context.set_base64_alphabet(value);
+        self.jwe_context.set_base64_alphabet(value);
+    }
+
+    /// Return the base64 alphabet used for the segments of JWT.
+    pub fn base64_alphabet(&self) -> Base64Alphabet {
+        self.jws_context.base64_alphabet()
+    }
+
+    /// Set the maximum number of keys that are tried when a JWT without kid header claim is
+    /// decoded by using a JWK set.
+    ///
+    /// Each key that the selector accepts for the alg header claim is tried in turn, and the
+    /// JWT is accepted if any of them verifies it. When more keys than the maximum are
+    /// candidates, the JWT is rejected, so that a large JWK set cannot be used to make the
+    /// verification expensive. The default is 0, which disables this behavior.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the maximum number of candidate keys
+    pub fn set_max_keys_without_kid(&mut self, value: usize) {
+        self.max_keys_without_kid = value;
+    }
+
+    /// Return the maximum number of keys that are tried for a JWT without kid header claim.
+    pub fn max_keys_without_kid(&self) -> usize {
+        self.max_keys_without_kid
+    }
+
+    /// Set a token type header claim (typ) that is added when an encoded header doesn't have one.
+    ///
+    /// The default is None, which leaves the header as it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a default token type (e.g. "at+jwt")
+    pub fn set_default_token_type(&mut self, value: Option<String>) {
+        self.default_token_type = value;
+    }
+
+    /// Return the token type header claim (typ) that is added when an encoded header doesn't
+    /// have one.
+    pub fn default_token_type(&self) -> Option<&str> {
+        self.default_token_type.as_deref()
+    }
+
+    /// Test a content type header claim (cty) is acceptable for the outer token of a nested JWT.
+    ///
+    /// The value is compared after it is normalized to a media type, case-insensitively.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a content type (e.g. "JWT" or "application/jwt")
+    pub fn is_acceptable_nested_content_type(&self, value: &str) -> bool {
+        let value = util::normalize_media_type(value).to_ascii_lowercase();
+        self.acceptable_nested_content_types.contains(&value)
+    }
+
+    /// Add a acceptable content type header claim (cty) for the outer token of a nested JWT.
+    ///
+    /// The default acceptable content type is only "JWT".
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a content type (e.g. "application/example+jwt")
+    pub fn add_acceptable_nested_content_type(&mut self, value: &str) {
+        let value = util::normalize_media_type(value).to_ascii_lowercase();
+        self.acceptable_nested_content_types.insert(value);
+    }
+
+    /// Remove a acceptable content type header claim (cty) for the outer token of a nested JWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a content type
+    pub fn remove_acceptable_nested_content_type(&mut self, value: &str) {
+        let value = util::normalize_media_type(value).to_ascii_lowercase();
+        self.acceptable_nested_content_types.remove(&value);
+    }
+
     /// Return the string repsentation of the JWT with a "none" algorithm.
     ///
     /// # Arguments
     ///
     /// * `payload` - The payload data.
     /// * `header` - The JWT heaser claims.
+    #[deprecated(note = "Use encode_unsecured_insecure that requires an explicit acknowledgement.")]
     pub fn encode_unsecured(
         &self,
         payload: &JwtPayload,
         header: &JwsHeader,
+    ) -> Result<String, JoseError> {
+        self.encode_unsecured_insecure(payload, header, UnsecuredAcknowledgement)
+    }
+
+    /// Return the string repsentation of the JWT with a "none" algorithm.
+    ///
+    /// The JWT is not signed, so anyone can forge or modify it. The caller must pass
+    /// `UnsecuredAcknowledgement` to confirm that this is intended.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload data.
+    /// * `header` - The JWT heaser claims.
+    /// * `_acknowledgement` - an acknowledgement that the JWT is unsecured.
+    pub fn encode_unsecured_insecure(
+        &self,
+        payload: &JwtPayload,
+        header: &JwsHeader,
+        _acknowledgement: UnsecuredAcknowledgement,
     ) -> Result<String, JoseError> {
         (|| -> anyhow::Result<String> {
             let mut header = header.claims_set().clone();
@@ -81,10 +235,11 @@ impl JwtContext {
             capacity += util::ceiling(header_bytes.len() * 4, 3);
             capacity += util::ceiling(payload_bytes.len() * 4, 3);
 
+            let config = self.base64_alphabet().config();
             let mut message = String::with_capacity(capacity);
-            base64::encode_config_buf(header_bytes, base64::URL_SAFE_NO_PAD, &mut message);
+            base64::encode_config_buf(header_bytes, config, &mut message);
             message.push_str(".");
-            base64::encode_config_buf(payload_bytes, base64::URL_SAFE_NO_PAD, &mut message);
+            base64::encode_config_buf(payload_bytes, config, &mut message);
             message.push_str(".");
 
             Ok(message)
@@ -115,10 +270,12 @@ impl JwtContext {
                 }
             }
 
+            let header = self.with_default_token_type(header)?;
+
             let payload_bytes = serde_json::to_vec(payload.claims_set()).unwrap();
             let jwt = self
                 .jws_context
-                .serialize_compact(&payload_bytes, header, signer)?;
+                .serialize_compact(&payload_bytes, &header, signer)?;
             Ok(jwt)
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
@@ -140,13 +297,29 @@ impl JwtContext {
         header: &JweHeader,
         encrypter: &dyn JweEncrypter,
     ) -> Result<String, JoseError> {
+        let header = self.with_default_token_type(header)?;
+
         let payload_bytes = serde_json::to_vec(payload.claims_set()).unwrap();
         let jwt = self
             .jwe_context
-            .serialize_compact(&payload_bytes, header, encrypter)?;
+            .serialize_compact(&payload_bytes, &header, encrypter)?;
         Ok(jwt)
     }
 
+    fn with_default_token_type<'a, T>(&self, header: &'a T) -> Result<Cow<'a, T>, JoseError>
+    where
+        T: JoseHeader + Clone,
+    {
+        match &self.default_token_type {
+            Some(val) if header.claim("typ").is_none() => {
+                let mut header = header.clone();
+                header.set_claim("typ", Some(Value::String(val.clone())))?;
+                Ok(Cow::Owned(header))
+            }
+            _ => Ok(Cow::Borrowed(header)),
+        }
+    }
+
     /// Return the JWT object decoded with the "none" algorithm.
     ///
     /// # Arguments
@@ -154,33 +327,10 @@ impl JwtContext {
     /// * `input` - a JWT string representation.
     pub fn decode_unsecured(&self, input: &str) -> Result<(JwtPayload, JwsHeader), JoseError> {
         (|| -> anyhow::Result<(JwtPayload, JwsHeader)> {
-            let parts: Vec<&str> = input.split('.').collect();
-            if parts.len() != 3 {
-                bail!("The unsecured JWT must be three parts separated by colon.");
-            }
-            if parts[2].len() != 0 {
-                bail!("The unsecured JWT must not have a signature part.");
-            }
-
-            let header = base64::decode_config(parts[0], base64::URL_SAFE_NO_PAD)?;
-            let header: Map<String, Value> = serde_json::from_slice(&header)?;
-
-            match header.get("alg") {
-                Some(Value::String(val)) if val == "none" => {}
-                Some(Value::String(val)) => bail!("The JWT alg header claim is not none: {}", val),
-                Some(_) => bail!("The JWT alg header claim must be a string."),
-                None => bail!("The JWT alg header claim is missing."),
-            }
-
-            match header.get("kid") {
-                None => {}
-                Some(_) => bail!("A JWT of none alg cannot have kid header claim."),
-            }
-
+            let (header, payload) = self.split_unsecured(input)?;
             let header = JwsHeader::from_map(header)?;
 
-            let payload = base64::decode_config(parts[1], base64::URL_SAFE_NO_PAD)?;
-            let payload: Map<String, Value> = serde_json::from_slice(&payload)?;
+            let payload = util::parse_json_object(&payload)?;
             let payload = JwtPayload::from_map(payload)?;
 
             Ok((payload, header))
@@ -191,6 +341,57 @@ impl JwtContext {
         })
     }
 
+    /// Return the raw payload bytes of a JWT with the "none" algorithm without parsing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    pub fn decode_unsecured_payload_bytes(&self, input: &str) -> Result<Vec<u8>, JoseError> {
+        (|| -> anyhow::Result<Vec<u8>> {
+            let (_, payload) = self.split_unsecured(input)?;
+            Ok(payload)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
+    fn split_unsecured(&self, input: &str) -> anyhow::Result<(Map<String, Value>, Vec<u8>)> {
+        let input = input.trim_matches(|c: char| c.is_ascii_whitespace());
+        if input.contains(|c: char| c.is_ascii_whitespace()) {
+            bail!("The unsecured JWT must not contain whitespace.");
+        }
+
+        let parts: Vec<&str> = input.split('.').collect();
+        if parts.len() != 3 {
+            bail!("The unsecured JWT must be three parts separated by colon.");
+        }
+        if !parts[2].is_empty() {
+            bail!("The unsecured JWT must not have a signature part.");
+        }
+
+        let config = self.base64_alphabet().config();
+        let header = base64::decode_config(parts[0], config)?;
+        let header = util::parse_json_object(&header)?;
+
+        match header.get("alg") {
+            Some(Value::String(val)) if val == "none" => {}
+            Some(Value::String(val)) => bail!("The JWT alg header claim is not none: {}", val),
+            Some(_) => bail!("The JWT alg header claim must be a string."),
+            None => bail!("The JWT alg header claim is missing."),
+        }
+
+        match header.get("kid") {
+            None => {}
+            Some(_) => bail!("A JWT of none alg cannot have kid header claim."),
+        }
+
+        let payload = base64::decode_config(parts[1], config)?;
+
+        Ok((header, payload))
+    }
+
     /// Return the JWT object decoded by the selected verifier.
     ///
     /// # Arguments
@@ -205,48 +406,50 @@ impl JwtContext {
         self.decode_with_verifier_selector(input, |_header| Ok(Some(verifier)))
     }
 
-    /// Return the JWT object decoded with a selected verifying algorithm.
+    /// Return the JWT object decoded by a verifier for the key in the jwk header claim.
+    ///
+    /// This is refused unless the embedded keys are trusted by the options.
+    /// The factory should check the key against a trusted source before it makes a verifier.
     ///
     /// # Arguments
     ///
     /// * `input` - a JWT string representation.
-    /// * `selector` - a function for selecting the verifying algorithm.
-    pub fn decode_with_verifier_selector<'a, F>(
+    /// * `options` - the options for decoding.
+    /// * `factory` - a function for making a verifier from the embedded key.
+    pub fn decode_with_embedded_jwk<F>(
         &self,
         input: &str,
-        selector: F,
+        options: &DecodeOptions,
+        factory: F,
     ) -> Result<(JwtPayload, JwsHeader), JoseError>
     where
-        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+        F: Fn(&Jwk) -> Result<Box<dyn JwsVerifier>, JoseError>,
     {
         (|| -> anyhow::Result<(JwtPayload, JwsHeader)> {
-            let (payload, header) =
-                self.jws_context
-                    .deserialize_compact_with_selector(input, |header| {
-                        (|| -> anyhow::Result<Option<&'a dyn JwsVerifier>> {
-                            let verifier = match selector(&header)? {
-                                Some(val) => val,
-                                None => return Ok(None),
-                            };
-
-                            if self.is_acceptable_critical("b64") {
-                                bail!("JWT is not supported b64 header claim.");
-                            }
+            let input = input.trim_matches(|c: char| c.is_ascii_whitespace());
+            let header_b64 = match input.find('.') {
+                Some(index) => &input[..index],
+                None => bail!("The JWT must be three parts separated by colon."),
+            };
+            let header = base64::decode_config(header_b64, self.base64_alphabet().config())?;
+            let header = JwsHeader::from_slice(&header)?;
 
-                            Ok(Some(verifier))
-                        })()
-                        .map_err(|err| {
-                            match err.downcast::<JoseError>() {
-                                Ok(err) => err,
-                                Err(err) => JoseError::InvalidJwtFormat(err),
-                            }
-                        })
-                    })?;
+            if !options.is_trust_embedded_keys() {
+                for key in &["jwk", "jku", "x5u", "x5c"] {
+                    if header.claim(key).is_some() {
+                        bail!("The key in the {} header claim is not trusted.", key);
+                    }
+                }
+                bail!("The embedded keys are not trusted.");
+            }
 
-            let payload: Map<String, Value> = serde_json::from_slice(&payload)?;
-            let payload = JwtPayload::from_map(payload)?;
+            let jwk = match header.jwk() {
+                Some(val) => val,
+                None => bail!("The jwk header claim is required."),
+            };
 
-            Ok((payload, header))
+            let verifier = factory(jwk)?;
+            Ok(self.decode_with_verifier(input, &*verifier)?)
         })()
         .map_err(|err| match err.downcast::<JoseError>() {
             Ok(err) => err,
@@ -254,78 +457,88 @@ impl JwtContext {
         })
     }
 
-    /// Return the JWT object decoded by using a JWK set.
+    /// Return the JWT object decoded by a verifier that is made from a public key of PEM format
+    /// for the algorithm of the alg header claim.
     ///
     /// # Arguments
     ///
     /// * `input` - a JWT string representation.
-    /// * `jwk_set` - a JWK set.
-    /// * `selector` - a function for selecting the verifying algorithm.
-    pub fn decode_with_verifier_in_jwk_set<F>(
+    /// * `pem` - a public key that is formatted by PEM.
+    pub fn verify_with_pem(
         &self,
         input: &str,
-        jwk_set: &JwkSet,
-        selector: F,
-    ) -> Result<(JwtPayload, JwsHeader), JoseError>
-    where
-        F: Fn(&Jwk) -> Result<Option<&dyn JwsVerifier>, JoseError>,
-    {
-        self.decode_with_verifier_selector(input, |header| {
-            let key_id = match header.key_id() {
-                Some(val) => val,
-                None => return Ok(None),
+        pem: impl AsRef<[u8]>,
+    ) -> Result<(JwtPayload, JwsHeader), JoseError> {
+        (|| -> anyhow::Result<(JwtPayload, JwsHeader)> {
+            let trimmed = input.trim_matches(|c: char| c.is_ascii_whitespace());
+            let header_b64 = match trimmed.find('.') {
+                Some(index) => &trimmed[..index],
+                None => bail!("The JWT must be three parts separated by colon."),
+            };
+            let header = base64::decode_config(header_b64, self.base64_alphabet().config())?;
+            let header = JwsHeader::from_slice(&header)?;
+
+            let pem = pem.as_ref();
+            let verifier: Box<dyn JwsVerifier> = match header.algorithm() {
+                Some("RS256") => Box::new(RS256.verifier_from_pem(pem)?),
+                Some("RS384") => Box::new(RS384.verifier_from_pem(pem)?),
+                Some("RS512") => Box::new(RS512.verifier_from_pem(pem)?),
+                Some("PS256") => Box::new(PS256.verifier_from_pem(pem)?),
+                Some("PS384") => Box::new(PS384.verifier_from_pem(pem)?),
+                Some("PS512") => Box::new(PS512.verifier_from_pem(pem)?),
+                Some("ES256") => Box::new(ES256.verifier_from_pem(pem)?),
+                Some("ES384") => Box::new(ES384.verifier_from_pem(pem)?),
+                Some("ES512") => Box::new(ES512.verifier_from_pem(pem)?),
+                Some("ES256K") => Box::new(ES256K.verifier_from_pem(pem)?),
+                Some("EdDSA") => Box::new(EdDSA.verifier_from_pem(pem)?),
+                Some(val) => bail!("The {} algorithm cannot be verified by a public key.", val),
+                None => bail!("The JWS alg header claim is required."),
             };
 
-            for jwk in jwk_set.get(key_id) {
-                if let Some(val) = selector(jwk)? {
-                    return Ok(Some(val));
-                }
-            }
-            Ok(None)
+            Ok(self.decode_with_verifier(input, &*verifier)?)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
         })
     }
 
-    /// Return the JWT object decoded by the selected decrypter.
+    /// Return the JWT object decoded by the verifier, only if the payload claims are valid.
     ///
     /// # Arguments
     ///
     /// * `input` - a JWT string representation.
-    /// * `decrypter` - a decrypter of the decrypting algorithm.
-    pub fn decode_with_decrypter(
+    /// * `verifier` - a verifier of the signing algorithm.
+    /// * `validator` - a validator for the payload claims.
+    pub fn decode_and_validate(
         &self,
         input: &str,
-        decrypter: &dyn JweDecrypter,
-    ) -> Result<(JwtPayload, JweHeader), JoseError> {
-        self.decode_with_decrypter_selector(input, |_header| Ok(Some(decrypter)))
+        verifier: &dyn JwsVerifier,
+        validator: &JwtPayloadValidator,
+    ) -> Result<(JwtPayload, JwsHeader), JoseError> {
+        let (payload, header) = self.decode_with_verifier(input, verifier)?;
+        validator.validate(&payload)?;
+        Ok((payload, header))
     }
 
-    /// Return the JWT object decoded with a selected decrypting algorithm.
+    /// Return the JWT object decoded with a selected verifying algorithm.
     ///
     /// # Arguments
     ///
     /// * `input` - a JWT string representation.
-    /// * `decrypter_selector` - a function for selecting the decrypting algorithm.
-    pub fn decode_with_decrypter_selector<'a, F>(
+    /// * `selector` - a function for selecting the verifying algorithm.
+    pub fn decode_with_verifier_selector<'a, F>(
         &self,
         input: &str,
         selector: F,
-    ) -> Result<(JwtPayload, JweHeader), JoseError>
+    ) -> Result<(JwtPayload, JwsHeader), JoseError>
     where
-        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
     {
-        (|| -> anyhow::Result<(JwtPayload, JweHeader)> {
-            let (payload, header) =
-                self.jwe_context
-                    .deserialize_compact_with_selector(input, |header| {
-                        let decrypter = match selector(&header)? {
-                            Some(val) => val,
-                            None => return Ok(None),
-                        };
-
-                        Ok(Some(decrypter))
-                    })?;
+        (|| -> anyhow::Result<(JwtPayload, JwsHeader)> {
+            let (payload, header) = self.decode_verified_bytes(input, selector)?;
 
-            let payload: Map<String, Value> = serde_json::from_slice(&payload)?;
+            let payload = parse_verified_payload(&payload)?;
             let payload = JwtPayload::from_map(payload)?;
 
             Ok((payload, header))
@@ -336,23 +549,98 @@ impl JwtContext {
         })
     }
 
+    /// Return the JWT payload deserialized into a type and the header after the signature is
+    /// verified by the verifier.
+    ///
+    /// The payload claims are not validated as `JwtPayload` does, because the type defines
+    /// the shape of the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `verifier` - a verifier of the signing algorithm.
+    pub fn decode_with_verifier_as<T>(
+        &self,
+        input: &str,
+        verifier: &dyn JwsVerifier,
+    ) -> Result<(T, JwsHeader), JoseError>
+    where
+        T: DeserializeOwned,
+    {
+        (|| -> anyhow::Result<(T, JwsHeader)> {
+            let (payload, header) =
+                self.decode_verified_bytes(input, |_header| Ok(Some(verifier)))?;
+
+            let payload = parse_verified_payload(&payload)?;
+            let payload = serde_json::from_value(Value::Object(payload)).map_err(|err| {
+                JoseError::InvalidJwtPayload(
+                    anyhow::Error::new(err).context("JWT payload cannot be deserialized."),
+                )
+            })?;
+
+            Ok((payload, header))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
+    fn decode_verified_bytes<'a, F>(
+        &self,
+        input: &str,
+        selector: F,
+    ) -> anyhow::Result<(Vec<u8>, JwsHeader)>
+    where
+        F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        let (payload, header) =
+            self.jws_context
+                .deserialize_compact_with_selector(input, |header| {
+                    (|| -> anyhow::Result<Option<&'a dyn JwsVerifier>> {
+                        let verifier = match selector(header)? {
+                            Some(val) => val,
+                            None => return Ok(None),
+                        };
+
+                        if self.is_acceptable_critical("b64") {
+                            bail!("JWT is not supported b64 header claim.");
+                        }
+
+                        Ok(Some(verifier))
+                    })()
+                    .map_err(|err| match err.downcast::<JoseError>() {
+                        Ok(err) => err,
+                        Err(err) => JoseError::InvalidJwtFormat(err),
+                    })
+                })?;
+
+        Ok((payload, header))
+    }
+
     /// Return the JWT object decoded by using a JWK set.
     ///
     /// # Arguments
     ///
     /// * `input` - a JWT string representation.
     /// * `jwk_set` - a JWK set.
-    /// * `selector` - a function for selecting the decrypting algorithm.
-    pub fn decode_with_decrypter_in_jwk_set<F>(
+    /// * `selector` - a function for selecting the verifying algorithm.
+    pub fn decode_with_verifier_in_jwk_set<'a, F>(
         &self,
         input: &str,
         jwk_set: &JwkSet,
         selector: F,
-    ) -> Result<(JwtPayload, JweHeader), JoseError>
+    ) -> Result<(JwtPayload, JwsHeader), JoseError>
     where
-        F: Fn(&Jwk) -> Result<Option<&dyn JweDecrypter>, JoseError>,
+        F: Fn(&Jwk) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
     {
-        self.decode_with_decrypter_selector(input, |header| {
+        if self.max_keys_without_kid > 0 {
+            if let Some(val) = self.decode_with_candidate_keys(input, jwk_set, &selector)? {
+                return Ok(val);
+            }
+        }
+
+        self.decode_with_verifier_selector(input, |header| {
             let key_id = match header.key_id() {
                 Some(val) => val,
                 None => return Ok(None),
@@ -366,23 +654,353 @@ impl JwtContext {
             Ok(None)
         })
     }
-}
 
-/// Return the string repsentation of the JWT with a "none" algorithm.
-///
-/// # Arguments
-///
-/// * `payload` - The payload data.
-/// * `header` - The JWT heaser claims.
-pub fn encode_unsecured(payload: &JwtPayload, header: &JwsHeader) -> Result<String, JoseError> {
-    DEFAULT_CONTEXT.encode_unsecured(payload, header)
-}
+    fn decode_with_candidate_keys<'a, F>(
+        &self,
+        input: &str,
+        jwk_set: &JwkSet,
+        selector: &F,
+    ) -> Result<Option<(JwtPayload, JwsHeader)>, JoseError>
+    where
+        F: Fn(&Jwk) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    {
+        (|| -> anyhow::Result<Option<(JwtPayload, JwsHeader)>> {
+            let input = input.trim_matches(|c: char| c.is_ascii_whitespace());
+            let header = match input.find('.') {
+                Some(pos) => &input[0..pos],
+                None => bail!("The JWT must be three parts separated by colon."),
+            };
+            let header = base64::decode_config(header, self.base64_alphabet().config())?;
+            let header = JwsHeader::from_slice(&header)?;
+            if header.key_id().is_some() {
+                return Ok(None);
+            }
 
-/// Return the string repsentation of the JWT with the siginig algorithm.
-///
-/// # Arguments
-///
-/// * `payload` - The payload data.
+            let alg = match header.algorithm() {
+                Some(val) => val,
+                None => bail!("The JWT alg header claim is required."),
+            };
+
+            let mut verifiers = Vec::new();
+            for jwk in jwk_set.keys() {
+                if let Some(verifier) = selector(jwk)? {
+                    if verifier.algorithm().name() == alg {
+                        verifiers.push(verifier);
+                    }
+                }
+            }
+            if verifiers.len() > self.max_keys_without_kid {
+                bail!(
+                    "Too many candidate keys for a JWT without kid header claim: {} (max: {})",
+                    verifiers.len(),
+                    self.max_keys_without_kid
+                );
+            }
+
+            let mut last_err = None;
+            for verifier in verifiers {
+                match self.decode_with_verifier(input, verifier) {
+                    Ok(val) => return Ok(Some(val)),
+                    Err(err) => last_err = Some(err),
+                }
+            }
+            match last_err {
+                Some(err) => Err(err.into()),
+                None => bail!("A verifier is not found."),
+            }
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
+    /// Return the JWT object decoded by using a JWK set and a verifier cache.
+    ///
+    /// The verifier that is created by the selector is cached by the JWK thumbprint,
+    /// and is reused for later decodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `jwk_set` - a JWK set.
+    /// * `cache` - a verifier cache.
+    /// * `selector` - a function for creating the verifier from a JWK.
+    pub fn decode_with_verifier_in_jwk_set_cached<F>(
+        &self,
+        input: &str,
+        jwk_set: &JwkSet,
+        cache: &VerifierCache,
+        selector: F,
+    ) -> Result<(JwtPayload, JwsHeader), JoseError>
+    where
+        F: Fn(&Jwk) -> Result<Option<Box<dyn JwsVerifier>>, JoseError>,
+    {
+        (|| -> anyhow::Result<(JwtPayload, JwsHeader)> {
+            let input = input.trim_matches(|c: char| c.is_ascii_whitespace());
+            let header = match input.find('.') {
+                Some(pos) => &input[0..pos],
+                None => bail!("The JWT must be three parts separated by colon."),
+            };
+            let header = base64::decode_config(header, self.base64_alphabet().config())?;
+            let header = JwsHeader::from_slice(&header)?;
+
+            let key_id = match header.key_id() {
+                Some(val) => val,
+                None => bail!("The JWT kid header claim is required."),
+            };
+
+            for jwk in jwk_set.get(key_id) {
+                if let Some(verifier) = cache.get_or_insert_with(jwk, &selector)? {
+                    return Ok(self.decode_with_verifier(input, &*verifier)?);
+                }
+            }
+
+            bail!("A verifier is not found.");
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
+    /// Return the JWT object decoded by the selected decrypter.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `decrypter` - a decrypter of the decrypting algorithm.
+    pub fn decode_with_decrypter(
+        &self,
+        input: &str,
+        decrypter: &dyn JweDecrypter,
+    ) -> Result<(JwtPayload, JweHeader), JoseError> {
+        self.decode_with_decrypter_selector(input, |_header| Ok(Some(decrypter)))
+    }
+
+    /// Return the JWT object decoded by the selected decrypter, and refuse a content
+    /// encryption algorithm that the options don't allow before decrypting it.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `decrypter` - a decrypter of the decrypting algorithm.
+    /// * `options` - the options for decoding.
+    pub fn decode_with_decrypter_and_options(
+        &self,
+        input: &str,
+        decrypter: &dyn JweDecrypter,
+        options: &DecodeOptions,
+    ) -> Result<(JwtPayload, JweHeader), JoseError> {
+        self.decode_with_decrypter_selector(input, |header| {
+            if let Some(val) = header.content_encryption() {
+                if !options.is_allowed_content_encryption(val) {
+                    return Err(JoseError::InvalidJweFormat(anyhow!(
+                        "The JWE enc header claim is not allowed: {}",
+                        val
+                    )));
+                }
+            }
+            Ok(Some(decrypter))
+        })
+    }
+
+    /// Return the JWT object decoded with a selected decrypting algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `decrypter_selector` - a function for selecting the decrypting algorithm.
+    pub fn decode_with_decrypter_selector<'a, F>(
+        &self,
+        input: &str,
+        selector: F,
+    ) -> Result<(JwtPayload, JweHeader), JoseError>
+    where
+        F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    {
+        (|| -> anyhow::Result<(JwtPayload, JweHeader)> {
+            let (payload, header) =
+                self.jwe_context
+                    .deserialize_compact_with_selector(input, |header| {
+                        let decrypter = match selector(&header)? {
+                            Some(val) => val,
+                            None => return Ok(None),
+                        };
+
+                        Ok(Some(decrypter))
+                    })?;
+
+            let payload = parse_verified_payload(&payload)?;
+            let payload = JwtPayload::from_map(payload)?;
+
+            Ok((payload, header))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
+    /// Return the JWT object of a nested JWT that is signed and then encrypted.
+    ///
+    /// The content type header claim (cty) of the outer JWE must be one of the acceptable
+    /// nested content types.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `decrypter` - a decrypter of the decrypting algorithm for the outer JWE.
+    /// * `verifier` - a verifier of the signing algorithm for the inner JWS.
+    pub fn decode_nested_with_decrypter(
+        &self,
+        input: &str,
+        decrypter: &dyn JweDecrypter,
+        verifier: &dyn JwsVerifier,
+    ) -> Result<(JwtPayload, JweHeader, JwsHeader), JoseError> {
+        (|| -> anyhow::Result<(JwtPayload, JweHeader, JwsHeader)> {
+            let (content, header) = self.jwe_context.deserialize_compact(input, decrypter)?;
+
+            match header.content_type() {
+                Some(cty) if self.is_acceptable_nested_content_type(cty) => {}
+                Some(cty) => bail!(
+                    "The cty header claim is not acceptable for a nested JWT: {}",
+                    cty
+                ),
+                None => bail!("The cty header claim is required for a nested JWT."),
+            }
+
+            let content = String::from_utf8(content)?;
+            let (payload, inner_header) = self.decode_with_verifier(&content, verifier)?;
+
+            Ok((payload, header, inner_header))
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
+    /// Return the JWT object decoded by using a JWK set.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `jwk_set` - a JWK set.
+    /// * `selector` - a function for selecting the decrypting algorithm.
+    pub fn decode_with_decrypter_in_jwk_set<F>(
+        &self,
+        input: &str,
+        jwk_set: &JwkSet,
+        selector: F,
+    ) -> Result<(JwtPayload, JweHeader), JoseError>
+    where
+        F: Fn(&Jwk) -> Result<Option<&dyn JweDecrypter>, JoseError>,
+    {
+        self.decode_with_decrypter_selector(input, |header| {
+            let key_id = match header.key_id() {
+                Some(val) => val,
+                None => return Ok(None),
+            };
+
+            for jwk in jwk_set.get(key_id) {
+                if let Some(val) = selector(jwk)? {
+                    return Ok(Some(val));
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    /// Return the JWT object decoded by the verifier or the decrypter.
+    ///
+    /// A JWT of three parts is decoded as JWS, and a JWT of five parts with
+    /// an enc header claim is decoded as JWE.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - a JWT string representation.
+    /// * `verifier` - a verifier of the signing algorithm.
+    /// * `decrypter` - a decrypter of the decrypting algorithm.
+    pub fn decode_auto(
+        &self,
+        input: &str,
+        verifier: Option<&dyn JwsVerifier>,
+        decrypter: Option<&dyn JweDecrypter>,
+    ) -> Result<DecodedJwt, JoseError> {
+        (|| -> anyhow::Result<DecodedJwt> {
+            let trimmed = input.trim_matches(|c: char| c.is_ascii_whitespace());
+            let parts: Vec<&str> = trimmed.split('.').collect();
+
+            let header = base64::decode_config(parts[0], self.base64_alphabet().config())?;
+            let header = util::parse_json_object(&header)?;
+
+            match (parts.len(), header.contains_key("enc")) {
+                (3, false) => {
+                    let verifier = match verifier {
+                        Some(val) => val,
+                        None => bail!("A verifier is required to decode the signed JWT."),
+                    };
+                    let (payload, header) = self.decode_with_verifier(input, verifier)?;
+                    Ok(DecodedJwt::Signed(payload, header))
+                }
+                (5, true) => {
+                    let decrypter = match decrypter {
+                        Some(val) => val,
+                        None => bail!("A decrypter is required to decode the encrypted JWT."),
+                    };
+                    let (payload, header) = self.decode_with_decrypter(input, decrypter)?;
+                    Ok(DecodedJwt::Encrypted(payload, header))
+                }
+                (3, true) => bail!("The signed JWT must not have an enc header claim."),
+                (5, false) => bail!("The encrypted JWT must have an enc header claim."),
+                (len, _) => bail!(
+                    "The JWT must be three parts (JWS) or five parts (JWE) separated by colon: {}",
+                    len
+                ),
+            }
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+}
+
+/// Return the string repsentation of the JWT with a "none" algorithm.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `header` - The JWT heaser claims.
+#[deprecated(note = "Use encode_unsecured_insecure that requires an explicit acknowledgement.")]
+pub fn encode_unsecured(payload: &JwtPayload, header: &JwsHeader) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.encode_unsecured_insecure(payload, header, UnsecuredAcknowledgement)
+}
+
+/// Return the string repsentation of the JWT with a "none" algorithm.
+///
+/// The JWT is not signed, so anyone can forge or modify it. The caller must pass
+/// `UnsecuredAcknowledgement` to confirm that this is intended.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
+/// * `header` - The JWT heaser claims.
+/// * `acknowledgement` - an acknowledgement that the JWT is unsecured.
+pub fn encode_unsecured_insecure(
+    payload: &JwtPayload,
+    header: &JwsHeader,
+    acknowledgement: UnsecuredAcknowledgement,
+) -> Result<String, JoseError> {
+    DEFAULT_CONTEXT.encode_unsecured_insecure(payload, header, acknowledgement)
+}
+
+/// Return the string repsentation of the JWT with the siginig algorithm.
+///
+/// # Arguments
+///
+/// * `payload` - The payload data.
 /// * `header` - The JWS heaser claims.
 /// * `signer` - a signer object.
 pub fn encode_with_signer(
@@ -417,6 +1035,15 @@ pub fn decode_unsecured(input: &str) -> Result<(JwtPayload, JwsHeader), JoseErro
     DEFAULT_CONTEXT.decode_unsecured(input)
 }
 
+/// Return the raw payload bytes of a JWT with the "none" algorithm without parsing them.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+pub fn decode_unsecured_payload_bytes(input: &str) -> Result<Vec<u8>, JoseError> {
+    DEFAULT_CONTEXT.decode_unsecured_payload_bytes(input)
+}
+
 /// Return the JWT object decoded by the selected verifier.
 ///
 /// # Arguments
@@ -430,67 +1057,151 @@ pub fn decode_with_verifier(
     DEFAULT_CONTEXT.decode_with_verifier(input, verifier)
 }
 
-/// Return the JWT object decoded with a selected verifying algorithm.
+/// Return the JWT payload deserialized into a type and the header after the signature is
+/// verified by the verifier.
 ///
 /// # Arguments
 ///
 /// * `input` - a JWT string representation.
-/// * `selector` - a function for selecting the verifying algorithm.
-pub fn decode_with_verifier_selector<'a, F>(
+/// * `verifier` - a verifier of the signing algorithm.
+pub fn decode_with_verifier_as<T>(
     input: &str,
-    selector: F,
-) -> Result<(JwtPayload, JwsHeader), JoseError>
+    verifier: &dyn JwsVerifier,
+) -> Result<(T, JwsHeader), JoseError>
 where
-    F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+    T: DeserializeOwned,
 {
-    DEFAULT_CONTEXT.decode_with_verifier_selector(input, selector)
+    DEFAULT_CONTEXT.decode_with_verifier_as(input, verifier)
 }
 
-/// Return the JWT object decoded by using a JWK set.
+/// Return the JWT object decoded by a verifier that is made from a public key of PEM format
+/// for the algorithm of the alg header claim.
 ///
 /// # Arguments
 ///
 /// * `input` - a JWT string representation.
-/// * `jwk_set` - a JWK set.
-/// * `selector` - a function for selecting the verifying algorithm.
-pub fn decode_with_verifier_in_jwk_set<F>(
+/// * `pem` - a public key that is formatted by PEM.
+pub fn verify_with_pem(
     input: &str,
-    jwk_set: &JwkSet,
-    selector: F,
-) -> Result<(JwtPayload, JwsHeader), JoseError>
-where
-    F: Fn(&Jwk) -> Result<Option<&dyn JwsVerifier>, JoseError>,
-{
-    DEFAULT_CONTEXT.decode_with_verifier_in_jwk_set(input, jwk_set, selector)
+    pem: impl AsRef<[u8]>,
+) -> Result<(JwtPayload, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.verify_with_pem(input, pem)
 }
 
-/// Return the JWT object decoded by the selected decrypter.
+/// Return the JWT object decoded by the verifier, only if the payload claims are valid.
 ///
 /// # Arguments
 ///
 /// * `input` - a JWT string representation.
-/// * `decrypter` - a decrypter of the decrypting algorithm.
-pub fn decode_with_decrypter(
+/// * `verifier` - a verifier of the signing algorithm.
+/// * `validator` - a validator for the payload claims.
+pub fn decode_and_validate(
     input: &str,
-    decrypter: &dyn JweDecrypter,
-) -> Result<(JwtPayload, JweHeader), JoseError> {
-    DEFAULT_CONTEXT.decode_with_decrypter(input, decrypter)
+    verifier: &dyn JwsVerifier,
+    validator: &JwtPayloadValidator,
+) -> Result<(JwtPayload, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.decode_and_validate(input, verifier, validator)
 }
 
-/// Return the JWT object decoded with a selected decrypting algorithm.
+/// Return the JWT object decoded with a selected verifying algorithm.
 ///
 /// # Arguments
 ///
 /// * `input` - a JWT string representation.
-/// * `decrypter_selector` - a function for selecting the decrypting algorithm.
-pub fn decode_with_decrypter_selector<'a, F>(
+/// * `selector` - a function for selecting the verifying algorithm.
+pub fn decode_with_verifier_selector<'a, F>(
     input: &str,
     selector: F,
-) -> Result<(JwtPayload, JweHeader), JoseError>
+) -> Result<(JwtPayload, JwsHeader), JoseError>
 where
-    F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+    F: Fn(&JwsHeader) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
 {
-    DEFAULT_CONTEXT.decode_with_decrypter_selector(input, selector)
+    DEFAULT_CONTEXT.decode_with_verifier_selector(input, selector)
+}
+
+/// Return the JWT object decoded by using a JWK set.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `jwk_set` - a JWK set.
+/// * `selector` - a function for selecting the verifying algorithm.
+pub fn decode_with_verifier_in_jwk_set<'a, F>(
+    input: &str,
+    jwk_set: &JwkSet,
+    selector: F,
+) -> Result<(JwtPayload, JwsHeader), JoseError>
+where
+    F: Fn(&Jwk) -> Result<Option<&'a dyn JwsVerifier>, JoseError>,
+{
+    DEFAULT_CONTEXT.decode_with_verifier_in_jwk_set(input, jwk_set, selector)
+}
+
+/// Return the JWT object decoded by using a JWK set and a verifier cache.
+///
+/// The verifier that is created by the selector is cached by the JWK thumbprint,
+/// and is reused for later decodes.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `jwk_set` - a JWK set.
+/// * `cache` - a verifier cache.
+/// * `selector` - a function for creating the verifier from a JWK.
+pub fn decode_with_verifier_in_jwk_set_cached<F>(
+    input: &str,
+    jwk_set: &JwkSet,
+    cache: &VerifierCache,
+    selector: F,
+) -> Result<(JwtPayload, JwsHeader), JoseError>
+where
+    F: Fn(&Jwk) -> Result<Option<Box<dyn JwsVerifier>>, JoseError>,
+{
+    DEFAULT_CONTEXT.decode_with_verifier_in_jwk_set_cached(input, jwk_set, cache, selector)
+}
+
+/// Return the JWT object decoded by the selected decrypter.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `decrypter` - a decrypter of the decrypting algorithm.
+pub fn decode_with_decrypter(
+    input: &str,
+    decrypter: &dyn JweDecrypter,
+) -> Result<(JwtPayload, JweHeader), JoseError> {
+    DEFAULT_CONTEXT.decode_with_decrypter(input, decrypter)
+}
+
+/// Return the JWT object decoded with a selected decrypting algorithm.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `decrypter_selector` - a function for selecting the decrypting algorithm.
+pub fn decode_with_decrypter_selector<'a, F>(
+    input: &str,
+    selector: F,
+) -> Result<(JwtPayload, JweHeader), JoseError>
+where
+    F: Fn(&JweHeader) -> Result<Option<&'a dyn JweDecrypter>, JoseError>,
+{
+    DEFAULT_CONTEXT.decode_with_decrypter_selector(input, selector)
+}
+
+/// Return the JWT object of a nested JWT that is signed and then encrypted.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `decrypter` - a decrypter of the decrypting algorithm for the outer JWE.
+/// * `verifier` - a verifier of the signing algorithm for the inner JWS.
+pub fn decode_nested_with_decrypter(
+    input: &str,
+    decrypter: &dyn JweDecrypter,
+    verifier: &dyn JwsVerifier,
+) -> Result<(JwtPayload, JweHeader, JwsHeader), JoseError> {
+    DEFAULT_CONTEXT.decode_nested_with_decrypter(input, decrypter, verifier)
 }
 
 /// Return the JWT object decoded by using a JWK set.
@@ -511,6 +1222,103 @@ where
     DEFAULT_CONTEXT.decode_with_decrypter_in_jwk_set(input, jwk_set, selector)
 }
 
+/// Return the JWT object decoded by the verifier or the decrypter.
+///
+/// A JWT of three parts is decoded as JWS, and a JWT of five parts with
+/// an enc header claim is decoded as JWE.
+///
+/// # Arguments
+///
+/// * `input` - a JWT string representation.
+/// * `verifier` - a verifier of the signing algorithm.
+/// * `decrypter` - a decrypter of the decrypting algorithm.
+pub fn decode_auto(
+    input: &str,
+    verifier: Option<&dyn JwsVerifier>,
+    decrypter: Option<&dyn JweDecrypter>,
+) -> Result<DecodedJwt, JoseError> {
+    DEFAULT_CONTEXT.decode_auto(input, verifier, decrypter)
+}
+
+/// An explicit acknowledgement that a JWT is produced without a signature.
+///
+/// This is required by `encode_unsecured_insecure` to prevent unsigned tokens from being
+/// produced by accident.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct UnsecuredAcknowledgement;
+
+/// Options for decoding a JWT, e.g. with the keys that the JWT carries itself.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct DecodeOptions {
+    trust_embedded_keys: bool,
+    allowed_content_encryptions: Option<BTreeSet<String>>,
+}
+
+impl DecodeOptions {
+    pub fn new() -> Self {
+        Self {
+            trust_embedded_keys: false,
+            allowed_content_encryptions: None,
+        }
+    }
+
+    /// Set whether the key material in the jwk, jku, x5u and x5c header claims of a JWT can be
+    /// used to verify the JWT.
+    ///
+    /// A JWT that is verified by a key it carries itself can be signed by anyone, so the key
+    /// must be checked against a trusted source (e.g. by its thumbprint) before it is used.
+    /// The default is false. The decode methods that take a verifier never use these header
+    /// claims, because the caller supplies the keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - whether the embedded keys are trusted
+    pub fn trust_embedded_keys(mut self, value: bool) -> Self {
+        self.trust_embedded_keys = value;
+        self
+    }
+
+    /// Return whether the key material in the header claims of a JWT can be used to verify
+    /// the JWT.
+    pub fn is_trust_embedded_keys(&self) -> bool {
+        self.trust_embedded_keys
+    }
+
+    /// Set the content encryption algorithms that are allowed in the enc header claim of a JWT
+    /// encrypted by JWE.
+    ///
+    /// The default allows any registered content encryption algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - enc header claim names
+    pub fn allowed_content_encryptions(mut self, values: Vec<String>) -> Self {
+        self.allowed_content_encryptions = Some(values.into_iter().collect());
+        self
+    }
+
+    /// Test a content encryption algorithm is allowed in the enc header claim.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - a enc header claim name
+    pub fn is_allowed_content_encryption(&self, name: &str) -> bool {
+        match &self.allowed_content_encryptions {
+            Some(vals) => vals.contains(name),
+            None => true,
+        }
+    }
+}
+
+/// The JWT object decoded by `decode_auto`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum DecodedJwt {
+    /// The JWT signed by JWS.
+    Signed(JwtPayload, JwsHeader),
+    /// The JWT encrypted by JWE.
+    Encrypted(JwtPayload, JweHeader),
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, Default)]
 pub struct JwtPayload {
     claims: Map<String, Value>,
@@ -536,31 +1344,29 @@ impl JwtPayload {
             let mut sources = HashMap::new();
             for (key, value) in &claims {
                 match key.as_ref() {
-                    "iss" | "sub" | "jti" => match value {
+                    "iss" | "sub" | "jti" | "acr" => match value {
                         Value::String(_) => {}
                         _ => bail!("The JWT {} payload claim must be a string.", key),
                     },
+                    "amr" => match value {
+                        Value::Array(vals) => {
+                            let vec = parse_string_array(key, vals)?;
+                            sources.insert(key.clone(), SourceValue::StringArray(vec));
+                        }
+                        _ => bail!("The JWT {} payload claim must be a array.", key),
+                    },
                     "aud" => match value {
                         Value::String(_) => {}
                         Value::Array(vals) => {
-                            let mut vec = Vec::with_capacity(vals.len());
-                            for val in vals {
-                                match val {
-                                    Value::String(val) => vec.push(val.to_string()),
-                                    _ => bail!(
-                                        "An element of JWT {} payload claim must be a string.",
-                                        key
-                                    ),
-                                }
-                            }
+                            let vec = parse_string_array(key, vals)?;
                             sources.insert(key.clone(), SourceValue::StringArray(vec));
                         }
                         _ => bail!("The JWT {} payload claim must be a string or array.", key),
                     },
-                    "exp" | "nbf" | "iat" => match value {
+                    "exp" | "nbf" | "iat" | "auth_time" => match value {
                         Value::Number(val) => match val.as_u64() {
                             Some(val) => {
-                                let val = SystemTime::UNIX_EPOCH + Duration::from_secs(val);
+                                let val = numeric_date_to_system_time(key, val)?;
                                 sources.insert(key.clone(), SourceValue::SystemTime(val));
                             }
                             None => bail!(
@@ -582,6 +1388,22 @@ impl JwtPayload {
         })
     }
 
+    /// Return the JWT payload from a JSON byte representation.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - JWT payload claims as JSON bytes.
+    pub fn from_slice(input: impl AsRef<[u8]>) -> Result<Self, JoseError> {
+        (|| -> anyhow::Result<Self> {
+            let claims = util::parse_json_object(input.as_ref())?;
+            Ok(Self::from_map(claims)?)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJwtFormat(err),
+        })
+    }
+
     /// Set a value for issuer payload claim (iss).
     ///
     /// # Arguments
@@ -633,18 +1455,21 @@ impl JwtPayload {
                 break;
             }
         } else if values.len() > 1 {
-            let mut vec1 = Vec::with_capacity(values.len());
-            let mut vec2 = Vec::with_capacity(values.len());
-            for val in values {
-                let val: String = val.into();
-                vec1.push(Value::String(val.clone()));
-                vec2.push(val);
-            }
-            self.claims.insert(key.clone(), Value::Array(vec1));
-            self.sources.insert(key, SourceValue::StringArray(vec2));
+            self.set_string_array(&key, values);
         }
     }
 
+    /// Set values for audience payload claim (aud) as a JSON array.
+    ///
+    /// Unlike `set_audience`, a single audience is not collapsed into a string.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - a list of audiences
+    pub fn set_audience_array(&mut self, values: Vec<impl Into<String>>) {
+        self.set_string_array("aud", values);
+    }
+
     /// Return values for audience payload claim (aud).
     pub fn audience(&self) -> Option<&Vec<String>> {
         match self.sources.get("aud") {
@@ -654,20 +1479,35 @@ impl JwtPayload {
         }
     }
 
+    /// Return all values for audience payload claim (aud).
+    ///
+    /// The claim may be a single string or an array of strings. An empty vector is
+    /// returned when the claim is absent.
+    pub fn audiences(&self) -> Vec<String> {
+        match self.claims.get("aud") {
+            Some(Value::String(val)) => vec![val.clone()],
+            Some(Value::Array(vals)) => vals
+                .iter()
+                .filter_map(|val| match val {
+                    Value::String(val) => Some(val.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
     /// Set a system time for expires at payload claim (exp).
     ///
+    /// The time is truncated to whole seconds because NumericDate has no sub-second precision.
+    ///
     /// # Arguments
     ///
     /// * `value` - A expiration time on or after which the JWT must not be accepted for processing.
     pub fn set_expires_at(&mut self, value: SystemTime) {
         let key = "exp".to_string();
-        let val = Number::from(
-            value
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        );
-        self.claims.insert(key.clone(), Value::Number(val));
+        let (val, value) = to_numeric_date(value);
+        self.claims.insert(key.clone(), val);
         self.sources.insert(key, SourceValue::SystemTime(value));
     }
 
@@ -682,18 +1522,15 @@ impl JwtPayload {
 
     /// Set a system time for not before payload claim (nbf).
     ///
+    /// The time is truncated to whole seconds because NumericDate has no sub-second precision.
+    ///
     /// # Arguments
     ///
     /// * `value` - A time before which the JWT must not be accepted for processing.
     pub fn set_not_before(&mut self, value: SystemTime) {
         let key = "nbf".to_string();
-        let val = Number::from(
-            value
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        );
-        self.claims.insert(key.clone(), Value::Number(val));
+        let (val, value) = to_numeric_date(value);
+        self.claims.insert(key.clone(), val);
         self.sources.insert(key, SourceValue::SystemTime(value));
     }
 
@@ -708,18 +1545,15 @@ impl JwtPayload {
 
     /// Set a time for issued at payload claim (iat).
     ///
+    /// The time is truncated to whole seconds because NumericDate has no sub-second precision.
+    ///
     /// # Arguments
     ///
     /// * `value` - a time at which the JWT was issued.
     pub fn set_issued_at(&mut self, value: SystemTime) {
         let key = "iat".to_string();
-        let val = Number::from(
-            value
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        );
-        self.claims.insert(key.clone(), Value::Number(val));
+        let (val, value) = to_numeric_date(value);
+        self.claims.insert(key.clone(), val);
         self.sources.insert(key, SourceValue::SystemTime(value));
     }
 
@@ -742,6 +1576,15 @@ impl JwtPayload {
         self.claims.insert("jti".to_string(), Value::String(value));
     }
 
+    /// Set a random value for JWT ID payload claim (jti).
+    ///
+    /// The value is 128 bits from a cryptographically secure random number generator
+    /// and is encoded by base64url without padding (22 characters).
+    pub fn set_random_jwt_id(&mut self) {
+        let jti = util::rand_bytes(16);
+        self.set_jwt_id(base64::encode_config(&jti, base64::URL_SAFE_NO_PAD));
+    }
+
     /// Return the value for JWT ID payload claim (jti).
     pub fn jwt_id(&self) -> Option<&str> {
         match self.claims.get("jti") {
@@ -750,6 +1593,82 @@ impl JwtPayload {
         }
     }
 
+    /// Set a time for authentication time payload claim (auth_time) of OpenID Connect.
+    ///
+    /// The time is truncated to whole seconds because NumericDate has no sub-second precision.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a time when the end-user authentication occurred.
+    pub fn set_auth_time(&mut self, value: SystemTime) {
+        let key = "auth_time".to_string();
+        let (val, value) = to_numeric_date(value);
+        self.claims.insert(key.clone(), val);
+        self.sources.insert(key, SourceValue::SystemTime(value));
+    }
+
+    /// Return the time for authentication time payload claim (auth_time) of OpenID Connect.
+    pub fn auth_time(&self) -> Option<SystemTime> {
+        match self.sources.get("auth_time") {
+            Some(SourceValue::SystemTime(val)) => Some(*val),
+            None => None,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Set a value for authentication context class reference payload claim (acr)
+    /// of OpenID Connect.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a authentication context class reference
+    pub fn set_acr(&mut self, value: impl Into<String>) {
+        let value: String = value.into();
+        self.claims.insert("acr".to_string(), Value::String(value));
+    }
+
+    /// Return the value for authentication context class reference payload claim (acr)
+    /// of OpenID Connect.
+    pub fn acr(&self) -> Option<&str> {
+        match self.claims.get("acr") {
+            Some(Value::String(val)) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Set values for authentication methods references payload claim (amr)
+    /// of OpenID Connect.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - a list of authentication methods
+    pub fn set_amr(&mut self, values: Vec<impl Into<String>>) {
+        self.set_string_array("amr", values);
+    }
+
+    /// Return values for authentication methods references payload claim (amr)
+    /// of OpenID Connect.
+    pub fn amr(&self) -> Option<Vec<String>> {
+        match self.sources.get("amr") {
+            Some(SourceValue::StringArray(val)) => Some(val.clone()),
+            None => None,
+            _ => unreachable!(),
+        }
+    }
+
+    fn set_string_array(&mut self, key: &str, values: Vec<impl Into<String>>) {
+        let key = key.to_string();
+        let mut vec1 = Vec::with_capacity(values.len());
+        let mut vec2 = Vec::with_capacity(values.len());
+        for val in values {
+            let val: String = val.into();
+            vec1.push(Value::String(val.clone()));
+            vec2.push(val);
+        }
+        self.claims.insert(key.clone(), Value::Array(vec1));
+        self.sources.insert(key, SourceValue::StringArray(vec2));
+    }
+
     /// Set a value for payload claim of a specified key.
     ///
     /// # Arguments
@@ -759,7 +1678,7 @@ impl JwtPayload {
     pub fn set_claim(&mut self, key: &str, value: Option<Value>) -> Result<(), JoseError> {
         (|| -> anyhow::Result<()> {
             match key {
-                "iss" | "sub" | "jti" => match &value {
+                "iss" | "sub" | "jti" | "acr" => match &value {
                     Some(Value::String(_)) => {
                         self.claims.insert(key.to_string(), value.unwrap());
                     }
@@ -775,17 +1694,8 @@ impl JwtPayload {
                         self.claims.insert(key, value.unwrap());
                     }
                     Some(Value::Array(vals)) => {
+                        let vec = parse_string_array(key, vals)?;
                         let key = key.to_string();
-                        let mut vec = Vec::with_capacity(vals.len());
-                        for val in vals {
-                            match val {
-                                Value::String(val) => vec.push(val.to_string()),
-                                _ => bail!(
-                                    "An element of the JWT {} payload claim must be a string.",
-                                    key
-                                ),
-                            }
-                        }
                         self.sources
                             .insert(key.clone(), SourceValue::StringArray(vec));
                         self.claims.insert(key, value.unwrap());
@@ -796,11 +1706,25 @@ impl JwtPayload {
                     }
                     _ => bail!("The JWT {} payload claim must be a string or array.", key),
                 },
-                "exp" | "nbf" | "iat" => match &value {
+                "amr" => match &value {
+                    Some(Value::Array(vals)) => {
+                        let vec = parse_string_array(key, vals)?;
+                        let key = key.to_string();
+                        self.sources
+                            .insert(key.clone(), SourceValue::StringArray(vec));
+                        self.claims.insert(key, value.unwrap());
+                    }
+                    None => {
+                        self.sources.remove(key);
+                        self.claims.remove(key);
+                    }
+                    _ => bail!("The JWT {} payload claim must be a array.", key),
+                },
+                "exp" | "nbf" | "iat" | "auth_time" => match &value {
                     Some(Value::Number(val)) => match val.as_u64() {
                         Some(val) => {
+                            let val = numeric_date_to_system_time(key, val)?;
                             let key = key.to_string();
-                            let val = SystemTime::UNIX_EPOCH + Duration::from_secs(val);
                             self.sources
                                 .insert(key.clone(), SourceValue::SystemTime(val));
                             self.claims.insert(key, value.unwrap());
@@ -840,10 +1764,144 @@ impl JwtPayload {
         self.claims.get(key)
     }
 
+    /// Set a value for payload claim at a specified JSON Pointer (RFC 6901).
+    ///
+    /// Missing intermediate objects are created. The last reference token of an array may be
+    /// "-" to append the value.
+    ///
+    /// # Arguments
+    ///
+    /// * `pointer` - a JSON Pointer to the payload claim (e.g. "/realm_access/roles")
+    /// * `value` - a typed value of payload claim
+    pub fn set_claim_at(&mut self, pointer: &str, value: Value) -> Result<(), JoseError> {
+        let (key, value) = (|| -> anyhow::Result<(String, Value)> {
+            let mut tokens = parse_json_pointer(pointer)?;
+            let key = tokens.remove(0);
+            let value = match tokens.pop() {
+                Some(last) => {
+                    let mut root = match self.claims.get(&key) {
+                        Some(val) => val.clone(),
+                        None => Value::Object(Map::new()),
+                    };
+
+                    let mut current = &mut root;
+                    for token in &tokens {
+                        current = match current {
+                            Value::Object(map) => map
+                                .entry(token.as_str())
+                                .or_insert_with(|| Value::Object(Map::new())),
+                            Value::Array(vec) => match token.parse::<usize>() {
+                                Ok(index) if index < vec.len() => &mut vec[index],
+                                _ => bail!("The array index is invalid: {}", token),
+                            },
+                            _ => bail!("The value at {} is not an object or array.", token),
+                        };
+                    }
+
+                    match current {
+                        Value::Object(map) => {
+                            map.insert(last, value);
+                        }
+                        Value::Array(vec) if last == "-" => vec.push(value),
+                        Value::Array(vec) => match last.parse::<usize>() {
+                            Ok(index) if index < vec.len() => vec[index] = value,
+                            Ok(index) if index == vec.len() => vec.push(value),
+                            _ => bail!("The array index is invalid: {}", last),
+                        },
+                        _ => bail!("The value at {} is not an object or array.", last),
+                    }
+                    root
+                }
+                None => value,
+            };
+            Ok((key, value))
+        })()
+        .map_err(JoseError::InvalidJwtFormat)?;
+
+        self.set_claim(&key, Some(value))
+    }
+
+    /// Return a value for payload claim at a specified JSON Pointer (RFC 6901).
+    ///
+    /// # Arguments
+    ///
+    /// * `pointer` - a JSON Pointer to the payload claim (e.g. "/realm_access/roles")
+    pub fn claim_at(&self, pointer: &str) -> Option<&Value> {
+        if !pointer.starts_with('/') {
+            return None;
+        }
+
+        let (key, rest) = match pointer[1..].find('/') {
+            Some(pos) => pointer[1..].split_at(pos),
+            None => (&pointer[1..], ""),
+        };
+        let key = key.replace("~1", "/").replace("~0", "~");
+        self.claims.get(&key)?.pointer(rest)
+    }
+
     /// Return values for payload claims set
     pub fn claims_set(&self) -> &Map<String, Value> {
         &self.claims
     }
+
+    /// Return the JSON byte representation of the payload claims set.
+    pub fn to_vec(&self) -> Vec<u8> {
+        serde_json::to_vec(&self.claims).unwrap()
+    }
+}
+
+/// Parse the elements of a JWT payload claim that must be an array of strings.
+fn parse_string_array(key: &str, vals: &[Value]) -> anyhow::Result<Vec<String>> {
+    let mut vec = Vec::with_capacity(vals.len());
+    for val in vals {
+        match val {
+            Value::String(val) => vec.push(val.to_string()),
+            _ => bail!(
+                "An element of the JWT {} payload claim must be a string.",
+                key
+            ),
+        }
+    }
+    Ok(vec)
+}
+
+/// Convert a system time to a NumericDate, and return it with the system time truncated to
+/// whole seconds so that the source value matches the claim.
+fn to_numeric_date(value: SystemTime) -> (Value, SystemTime) {
+    let secs = value
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let value = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+    (Value::Number(Number::from(secs)), value)
+}
+
+/// Convert a NumericDate to a system time, and reject a value the system time cannot represent.
+fn numeric_date_to_system_time(key: &str, secs: u64) -> anyhow::Result<SystemTime> {
+    match SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs)) {
+        Some(val) => Ok(val),
+        None => bail!("The JWT {} payload claim is out of range: {}", key, secs),
+    }
+}
+
+/// Parse a payload whose signature or encryption has already been checked, so that a
+/// payload that is not a JWT is distinguished from a verification failure.
+fn parse_verified_payload(payload: &[u8]) -> Result<Map<String, Value>, JoseError> {
+    util::parse_json_object(payload).map_err(|err| {
+        JoseError::InvalidJwtPayload(err.context("JWT payload is not valid UTF-8/JSON."))
+    })
+}
+
+fn parse_json_pointer(pointer: &str) -> anyhow::Result<Vec<String>> {
+    if !pointer.starts_with('/') {
+        bail!("A JSON Pointer must start with '/': {}", pointer);
+    }
+
+    let tokens = pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    Ok(tokens)
 }
 
 impl AsRef<Map<String, Value>> for JwtPayload {
@@ -865,13 +1923,32 @@ impl Display for JwtPayload {
     }
 }
 
+impl FromStr for JwtPayload {
+    type Err = JoseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        (|| -> anyhow::Result<Self> {
+            let claims = util::parse_json_object(s.as_bytes())?;
+            Ok(Self::from_map(claims)?)
+        })()
+        .map_err(|err| match err.downcast::<JoseError>() {
+            Ok(err) => err,
+            Err(err) => JoseError::InvalidJson(err),
+        })
+    }
+}
+
 /// Represents JWT payload validator.
 #[derive(Debug, Eq, PartialEq)]
 pub struct JwtPayloadValidator {
     base_time: Option<SystemTime>,
     min_issued_time: Option<SystemTime>,
     max_issued_time: Option<SystemTime>,
+    issued_at_required: bool,
+    expiration_required: bool,
     audience: Option<String>,
+    authorized_party: Option<String>,
+    subject_pattern: Option<SubjectPredicate>,
     claims: Map<String, Value>,
 }
 
@@ -882,20 +1959,48 @@ impl JwtPayloadValidator {
             base_time: None,
             min_issued_time: None,
             max_issued_time: None,
+            issued_at_required: false,
+            expiration_required: false,
             audience: None,
+            authorized_party: None,
+            subject_pattern: None,
             claims: Map::new(),
         }
     }
 
-    /// Set a base time for time related claims (exp, nbf) validation.
+    /// Return a new JwtPayloadValidator that checks claims are equal to the expected payload.
+    ///
+    /// The time related claims (exp, nbf, iat) are not copied. Only the first value of
+    /// audience payload claim (aud) is used.
     ///
     /// # Arguments
     ///
-    /// * `base_time` - a min time
-    pub fn set_base_time(&mut self, base_time: SystemTime) {
-        self.base_time = Some(base_time);
-    }
-
+    /// * `expected` - a payload that has the expected claims.
+    pub fn from_expected(expected: &JwtPayload) -> Self {
+        let mut validator = Self::new();
+        for (key, value) in expected.claims_set() {
+            match key.as_str() {
+                "exp" | "nbf" | "iat" => {}
+                "aud" => {
+                    if let Some(audience) = expected.audiences().into_iter().next() {
+                        validator.set_audience(audience);
+                    }
+                }
+                _ => validator.set_claim(key, value.clone()),
+            }
+        }
+        validator
+    }
+
+    /// Set a base time for time related claims (exp, nbf) validation.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_time` - a min time
+    pub fn set_base_time(&mut self, base_time: SystemTime) {
+        self.base_time = Some(base_time);
+    }
+
     /// Return the base time for time related claims (exp, nbf) validation.
     pub fn base_time(&self) -> Option<&SystemTime> {
         self.base_time.as_ref()
@@ -929,6 +2034,28 @@ impl JwtPayloadValidator {
         self.max_issued_time.as_ref()
     }
 
+    /// Require issued at payload claim (iat) to be present.
+    pub fn require_issued_at(&mut self) {
+        self.issued_at_required = true;
+    }
+
+    /// Return whether issued at payload claim (iat) is required.
+    pub fn is_issued_at_required(&self) -> bool {
+        self.issued_at_required
+    }
+
+    /// Require expiration time payload claim (exp) to be present.
+    ///
+    /// By default, a payload without exp never expires.
+    pub fn require_expiration(&mut self) {
+        self.expiration_required = true;
+    }
+
+    /// Return whether expiration time payload claim (exp) is required.
+    pub fn is_expiration_required(&self) -> bool {
+        self.expiration_required
+    }
+
     /// Set a value for issuer payload claim (iss) validation.
     ///
     /// # Arguments
@@ -965,6 +2092,26 @@ impl JwtPayloadValidator {
         }
     }
 
+    /// Set a predicate for subject payload claim (sub) validation.
+    ///
+    /// The sub claim is required and must satisfy the predicate. This check is applied
+    /// in addition to the exact match of `set_subject`.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - a function that returns true when the subject is acceptable
+    pub fn set_subject_pattern<F>(&mut self, predicate: F)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.subject_pattern = Some(SubjectPredicate::new(Arc::new(predicate)));
+    }
+
+    /// Remove the predicate for subject payload claim (sub) validation.
+    pub fn remove_subject_pattern(&mut self) {
+        self.subject_pattern = None;
+    }
+
     /// Set a value for audience payload claim (aud) validation.
     ///
     /// # Arguments
@@ -983,6 +2130,27 @@ impl JwtPayloadValidator {
         }
     }
 
+    /// Set a value for authorized party payload claim (azp) validation of OpenID Connect.
+    ///
+    /// The claim must match when it is present, and it is required when the payload
+    /// has multiple audiences.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a client ID of the authorized party
+    pub fn set_authorized_party(&mut self, value: impl Into<String>) {
+        let value: String = value.into();
+        self.authorized_party = Some(value);
+    }
+
+    /// Return the value for authorized party payload claim (azp) validation.
+    pub fn authorized_party(&self) -> Option<&str> {
+        match self.authorized_party {
+            Some(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
     /// Set a value for JWT ID payload claim (jti) validation.
     ///
     /// # Arguments
@@ -1001,6 +2169,27 @@ impl JwtPayloadValidator {
         }
     }
 
+    /// Set a value for nonce payload claim (nonce) validation of OpenID Connect.
+    ///
+    /// The validation fails when the claim is absent or does not match.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A nonce that was sent in the authentication request
+    pub fn set_nonce(&mut self, value: impl Into<String>) {
+        let value: String = value.into();
+        self.claims
+            .insert("nonce".to_string(), Value::String(value));
+    }
+
+    /// Return the value for nonce payload claim (nonce) validation of OpenID Connect.
+    pub fn nonce(&self) -> Option<&str> {
+        match self.claims.get("nonce") {
+            Some(Value::String(val)) => Some(val),
+            _ => None,
+        }
+    }
+
     /// Set a value for payload claim of a specified key.
     ///
     /// # Arguments
@@ -1048,6 +2237,8 @@ impl JwtPayloadValidator {
                         DateTime::<Utc>::from(*expires_at)
                     );
                 }
+            } else if self.expiration_required {
+                bail!("The expiration time (exp) is required.");
             }
 
             if let Some(issued_at) = payload.issued_at() {
@@ -1064,12 +2255,41 @@ impl JwtPayloadValidator {
                         DateTime::<Utc>::from(*issued_at)
                     );
                 }
+            } else if self.issued_at_required {
+                bail!("The issued time (iat) is required.");
             }
 
             if let Some(audience) = &self.audience {
-                if let Some(audiences) = payload.audience() {
-                    if !audiences.contains(&audience) {
-                        bail!("Key aud is invalid: {}", audiences.join(", "));
+                let audiences = payload.audiences();
+                if !audiences.is_empty() && !audiences.contains(audience) {
+                    bail!("Key aud is invalid: {}", audiences.join(", "));
+                }
+            }
+
+            if let Some(subject_pattern) = &self.subject_pattern {
+                match payload.claim("sub") {
+                    Some(Value::String(val)) => {
+                        if !(subject_pattern.0)(val) {
+                            bail!("Key sub is invalid: {}", val);
+                        }
+                    }
+                    Some(val) => bail!("Key sub is invalid: {}", val),
+                    None => bail!("Key sub is missing."),
+                }
+            }
+
+            if let Some(authorized_party) = &self.authorized_party {
+                match payload.claim("azp") {
+                    Some(Value::String(val)) => {
+                        if val != authorized_party {
+                            bail!("Key azp is invalid: {}", val);
+                        }
+                    }
+                    Some(val) => bail!("Key azp is invalid: {}", val),
+                    None => {
+                        if payload.audiences().len() > 1 {
+                            bail!("Key azp is required when there are multiple audiences.");
+                        }
                     }
                 }
             }
@@ -1093,23 +2313,187 @@ impl JwtPayloadValidator {
     }
 }
 
+/// A function for validating a subject payload claim that is registered in JwtPayloadValidator.
+type SubjectPredicateFn = dyn Fn(&str) -> bool + Send + Sync;
+
+type SubjectPredicate = SharedFn<SubjectPredicateFn>;
+
+/// A builder of JWT access tokens (RFC 9068).
+///
+/// The built header has the `at+jwt` token type unless another one is set, and `build`
+/// fails when one of the mandatory claims (iss, exp, aud, sub, client_id, iat and jti)
+/// is missing.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct AccessTokenBuilder {
+    payload: JwtPayload,
+    token_type: Option<String>,
+}
+
+impl AccessTokenBuilder {
+    /// Return a new AccessTokenBuilder.
+    pub fn new() -> Self {
+        Self {
+            payload: JwtPayload::new(),
+            token_type: None,
+        }
+    }
+
+    /// Set a value for token type header claim (typ) of the access token.
+    ///
+    /// The value is emitted as is, so this controls the casing (e.g. "at+jwt" or
+    /// "application/at+jwt").
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a token type
+    pub fn set_token_type(&mut self, value: impl Into<String>) {
+        self.token_type = Some(value.into());
+    }
+
+    /// Return the value for token type header claim (typ) of the access token.
+    pub fn token_type(&self) -> &str {
+        self.token_type.as_deref().unwrap_or("at+jwt")
+    }
+
+    /// Set a value for issuer payload claim (iss).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a issuer
+    pub fn set_issuer(&mut self, value: impl Into<String>) {
+        self.payload.set_issuer(value);
+    }
+
+    /// Set a value for subject payload claim (sub).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a subject
+    pub fn set_subject(&mut self, value: impl Into<String>) {
+        self.payload.set_subject(value);
+    }
+
+    /// Set values for audience payload claim (aud).
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - a list of audiences
+    pub fn set_audience(&mut self, values: Vec<impl Into<String>>) {
+        self.payload.set_audience(values);
+    }
+
+    /// Set a value for client identifier payload claim (client_id).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a client identifier
+    pub fn set_client_id(&mut self, value: impl Into<String>) {
+        let value: String = value.into();
+        self.payload
+            .set_claim("client_id", Some(Value::String(value)))
+            .unwrap();
+    }
+
+    /// Set a system time for expires at payload claim (exp).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A expiration time on or after which the token must not be accepted.
+    pub fn set_expires_at(&mut self, value: SystemTime) {
+        self.payload.set_expires_at(value);
+    }
+
+    /// Set a time for issued at payload claim (iat).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a time at which the token was issued.
+    pub fn set_issued_at(&mut self, value: SystemTime) {
+        self.payload.set_issued_at(value);
+    }
+
+    /// Set a value for JWT ID payload claim (jti).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - a JWT ID
+    pub fn set_jwt_id(&mut self, value: impl Into<String>) {
+        self.payload.set_jwt_id(value);
+    }
+
+    /// Set values for scope payload claim (scope).
+    ///
+    /// The values are joined by a space.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - a list of scopes
+    pub fn set_scope(&mut self, values: Vec<impl Into<String>>) {
+        let values: Vec<String> = values.into_iter().map(|val| val.into()).collect();
+        self.payload
+            .set_claim("scope", Some(Value::String(values.join(" "))))
+            .unwrap();
+    }
+
+    /// Set a value for payload claim of a specified key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key name of payload claim
+    /// * `value` - a typed value of payload claim
+    pub fn set_claim(&mut self, key: &str, value: Option<Value>) -> Result<(), JoseError> {
+        self.payload.set_claim(key, value)
+    }
+
+    /// Return the payload and the header of the access token that are ready to sign.
+    pub fn build(&self) -> Result<(JwtPayload, JwsHeader), JoseError> {
+        (|| -> anyhow::Result<(JwtPayload, JwsHeader)> {
+            for key in &["iss", "exp", "aud", "sub", "client_id", "iat", "jti"] {
+                if self.payload.claim(key).is_none() {
+                    bail!("The access token {} payload claim is required.", key);
+                }
+            }
+
+            let mut header = JwsHeader::new();
+            header.set_token_type(self.token_type());
+
+            Ok((self.payload.clone(), header))
+        })()
+        .map_err(JoseError::InvalidClaim)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use serde_json::json;
+    use serde::Deserialize;
+    use serde_json::{json, Map};
+    use std::cell::Cell;
     use std::fs::File;
     use std::io::Read;
     use std::path::PathBuf;
+    use std::sync::Arc;
     use std::time::{Duration, SystemTime};
 
-    use crate::jwk::Jwk;
+    use crate::jose::{Base64Alphabet, JoseError};
+    use crate::jwe::{self, Dir, JweHeader};
+    use crate::jwk::{Jwk, JwkSet};
     use crate::jws::{
-        EdDSA, JwsHeader, ES256, ES256K, ES384, ES512, HS256, HS384, HS512, PS256, PS384, PS512,
-        RS256, RS384, RS512,
+        self, EdDSA, JwsHeader, JwsVerifier, VerifierCache, ES256, ES256K, ES384, ES512, HS256,
+        HS384, HS512, PS256, PS384, PS512, RS256, RS384, RS512,
+    };
+    use crate::jwt::{
+        self, AccessTokenBuilder, DecodeOptions, DecodedJwt, JwtContext, JwtPayload,
+        JwtPayloadValidator, UnsecuredAcknowledgement,
     };
-    use crate::jwt::{self, JwtPayload, JwtPayloadValidator};
     use crate::util;
 
+    #[test]
+    fn test_jwt_context_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<JwtContext>();
+    }
+
     #[test]
     fn test_new_header() -> Result<()> {
         let mut header = JwsHeader::new();
@@ -1128,92 +2512,650 @@ mod tests {
         header.set_nonce(b"nonce".to_vec());
         header.set_claim("header_claim", Some(json!("header_claim")))?;
 
-        assert!(matches!(header.jwk_set_url(), Some("jku")));
-        assert!(matches!(header.jwk(), Some(val) if val == &jwk));
-        assert!(matches!(header.x509_url(), Some("x5u")));
-        assert!(
-            matches!(header.x509_certificate_chain(), Some(vals) if vals == &vec![
-                b"x5c0".to_vec(),
-                b"x5c1".to_vec(),
-            ])
-        );
-        assert!(
-            matches!(header.x509_certificate_sha1_thumbprint(), Some(val) if val == &b"x5t".to_vec())
-        );
-        assert!(
-            matches!(header.x509_certificate_sha256_thumbprint(), Some(val) if val == &b"x5t#S256".to_vec())
+        assert!(matches!(header.jwk_set_url(), Some("jku")));
+        assert!(matches!(header.jwk(), Some(val) if val == &jwk));
+        assert!(matches!(header.x509_url(), Some("x5u")));
+        assert!(
+            matches!(header.x509_certificate_chain(), Some(vals) if vals == &vec![
+                b"x5c0".to_vec(),
+                b"x5c1".to_vec(),
+            ])
+        );
+        assert!(
+            matches!(header.x509_certificate_sha1_thumbprint(), Some(val) if val == &b"x5t".to_vec())
+        );
+        assert!(
+            matches!(header.x509_certificate_sha256_thumbprint(), Some(val) if val == &b"x5t#S256".to_vec())
+        );
+        assert!(matches!(header.key_id(), Some("kid")));
+        assert!(matches!(header.token_type(), Some("typ")));
+        assert!(matches!(header.content_type(), Some("cty")));
+        assert!(matches!(header.url(), Some("url")));
+        assert!(matches!(header.nonce(), Some(val) if val == &b"nonce".to_vec()));
+        assert!(matches!(header.critical(), Some(vals) if vals == &vec!["crit0", "crit1"]));
+        assert!(matches!(header.claim("header_claim"), Some(val) if val == &json!("header_claim")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_payload() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("iss");
+        payload.set_subject("sub");
+        payload.set_audience(vec!["aud0", "aud1"]);
+        payload.set_expires_at(SystemTime::UNIX_EPOCH);
+        payload.set_not_before(SystemTime::UNIX_EPOCH);
+        payload.set_issued_at(SystemTime::UNIX_EPOCH);
+        payload.set_jwt_id("jti");
+        payload.set_claim("payload_claim", Some(json!("payload_claim")))?;
+
+        assert!(matches!(payload.issuer(), Some("iss")));
+        assert!(matches!(payload.subject(), Some("sub")));
+        assert!(matches!(payload.audience(), Some(vals) if vals == &vec!["aud0", "aud1"]));
+        assert!(matches!(payload.expires_at(), Some(val) if val == &SystemTime::UNIX_EPOCH));
+        assert!(matches!(payload.not_before(), Some(val) if val == &SystemTime::UNIX_EPOCH));
+        assert!(matches!(payload.issued_at(), Some(val) if val == &SystemTime::UNIX_EPOCH));
+        assert!(matches!(payload.jwt_id(), Some("jti")));
+        assert!(
+            matches!(payload.claim("payload_claim"), Some(val) if val == &json!("payload_claim"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_unsecured() -> Result<()> {
+        let mut src_header = JwsHeader::new();
+        src_header.set_token_type("JWT");
+        let src_payload = JwtPayload::new();
+        let jwt_string =
+            jwt::encode_unsecured_insecure(&src_payload, &src_header, UnsecuredAcknowledgement)?;
+        let (dst_payload, dst_header) = jwt::decode_unsecured(&jwt_string)?;
+
+        src_header.set_claim("alg", Some(json!("none")))?;
+        assert_eq!(src_header, dst_header);
+        assert_eq!(src_payload, dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_decode_unsecured_payload_bytes() -> Result<()> {
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("subject");
+        let jwt_string = jwt::encode_unsecured_insecure(
+            &src_payload,
+            &JwsHeader::new(),
+            UnsecuredAcknowledgement,
+        )?;
+
+        let payload = jwt::decode_unsecured_payload_bytes(&jwt_string)?;
+        assert_eq!(payload, src_payload.to_vec());
+
+        assert!(jwt::decode_unsecured_payload_bytes(&format!("{}sig", jwt_string)).is_err());
+        let parts: Vec<&str> = jwt_string.split('.').collect();
+        assert!(
+            jwt::decode_unsecured_payload_bytes(&format!("{}.{}", parts[0], parts[1])).is_err()
+        );
+
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        assert!(jwt::decode_unsecured_payload_bytes(&jwt_string).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_jwt_unsecured_deprecated_alias() -> Result<()> {
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        let mut payload = JwtPayload::new();
+        payload.set_subject("subject");
+
+        let jwt_string = jwt::encode_unsecured(&payload, &header)?;
+        assert_eq!(
+            jwt_string,
+            jwt::encode_unsecured_insecure(&payload, &header, UnsecuredAcknowledgement)?
+        );
+        assert_eq!(
+            JwtContext::new().encode_unsecured(&payload, &header)?,
+            jwt_string
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_with_hmac() -> Result<()> {
+        for alg in &[HS256, HS384, HS512] {
+            let private_key = util::rand_bytes(64);
+
+            let mut src_header = JwsHeader::new();
+            src_header.set_token_type("JWT");
+            let src_payload = JwtPayload::new();
+            let signer = alg.signer_from_slice(&private_key)?;
+            let jwt_string = jwt::encode_with_signer(&src_payload, &src_header, &signer)?;
+
+            let verifier = alg.verifier_from_slice(&private_key)?;
+            let (dst_payload, dst_header) = jwt::decode_with_verifier(&jwt_string, &verifier)?;
+
+            src_header.set_claim("alg", Some(json!(alg.name())))?;
+            assert_eq!(src_header, dst_header);
+            assert_eq!(src_payload, dst_payload);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_standard_base64_alphabet() -> Result<()> {
+        let mut context = JwtContext::new();
+        context.set_base64_alphabet(Base64Alphabet::Standard);
+        assert_eq!(context.base64_alphabet(), Base64Alphabet::Standard);
+
+        let private_key = util::rand_bytes(64);
+        let mut src_header = JwsHeader::new();
+        src_header.set_token_type("JWT");
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("???>>>");
+
+        let signer = HS256.signer_from_slice(&private_key)?;
+        let jwt_string = context.encode_with_signer(&src_payload, &src_header, &signer)?;
+        for part in jwt_string.split('.') {
+            base64::decode_config(part, base64::STANDARD)?;
+        }
+        // The payload segment contains '/' and '+', which base64url rejects.
+        assert!(jwt_string.contains("I/Pz8+Pj4"));
+        // A 32-byte HS256 signature always needs padding in the standard alphabet.
+        assert!(jwt_string.ends_with('='));
+
+        let verifier = HS256.verifier_from_slice(&private_key)?;
+        let (dst_payload, dst_header) = context.decode_with_verifier(&jwt_string, &verifier)?;
+        src_header.set_claim("alg", Some(json!("HS256")))?;
+        assert_eq!(src_header, dst_header);
+        assert_eq!(src_payload, dst_payload);
+        assert!(jwt::decode_with_verifier(&jwt_string, &verifier).is_err());
+
+        let key = util::rand_bytes(32);
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128CBC-HS256");
+        let encrypter = Dir.encrypter_from_slice(&key)?;
+        let jwt_string = context.encode_with_encrypter(&src_payload, &src_header, &encrypter)?;
+        for part in jwt_string.split('.') {
+            base64::decode_config(part, base64::STANDARD)?;
+        }
+
+        let decrypter = Dir.decrypter_from_slice(&key)?;
+        let (dst_payload, _) = context.decode_with_decrypter(&jwt_string, &decrypter)?;
+        assert_eq!(src_payload, dst_payload);
+
+        let jwt_string = context.encode_unsecured_insecure(
+            &src_payload,
+            &JwsHeader::new(),
+            UnsecuredAcknowledgement,
+        )?;
+        let (dst_payload, _) = context.decode_unsecured(&jwt_string)?;
+        assert_eq!(src_payload, dst_payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_content_type_media_type() -> Result<()> {
+        let mut header = JwsHeader::new();
+        assert_eq!(header.content_type_media_type(), None);
+
+        header.set_content_type("JWT");
+        assert_eq!(
+            header.content_type_media_type(),
+            Some("application/JWT".to_string())
+        );
+
+        header.set_content_type("application/JWT");
+        assert_eq!(
+            header.content_type_media_type(),
+            Some("application/JWT".to_string())
+        );
+
+        header.set_content_type("text/plain");
+        assert_eq!(
+            header.content_type_media_type(),
+            Some("text/plain".to_string())
+        );
+
+        let private_key = util::rand_bytes(64);
+        let signer = HS256.signer_from_slice(&private_key)?;
+        let verifier = HS256.verifier_from_slice(&private_key)?;
+        for cty in &["JWT", "application/JWT", "application/jwt"] {
+            let mut header = JwsHeader::new();
+            header.set_content_type(*cty);
+            let jwt_string = jwt::encode_with_signer(&JwtPayload::new(), &header, &signer)?;
+            let (_, header) = jwt::decode_with_verifier(&jwt_string, &verifier)?;
+            assert_eq!(header.content_type(), Some(*cty));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_decode_nested_with_acceptable_content_types() -> Result<()> {
+        let sign_key = util::rand_bytes(64);
+        let signer = HS256.signer_from_slice(&sign_key)?;
+        let verifier = HS256.verifier_from_slice(&sign_key)?;
+        let encrypt_key = util::rand_bytes(32);
+        let encrypter = Dir.encrypter_from_slice(&encrypt_key)?;
+        let decrypter = Dir.decrypter_from_slice(&encrypt_key)?;
+
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("nested");
+        let inner = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+
+        let encrypt_nested = |cty: &str| -> Result<String> {
+            let mut header = JweHeader::new();
+            header.set_content_encryption("A128CBC-HS256");
+            header.set_content_type(cty);
+            Ok(jwe::serialize_compact(
+                inner.as_bytes(),
+                &header,
+                &encrypter,
+            )?)
+        };
+
+        let input = encrypt_nested("application/jwt")?;
+        let (dst_payload, outer_header, inner_header) =
+            jwt::decode_nested_with_decrypter(&input, &decrypter, &verifier)?;
+        assert_eq!(dst_payload.subject(), Some("nested"));
+        assert_eq!(outer_header.content_type(), Some("application/jwt"));
+        assert_eq!(inner_header.algorithm(), Some("HS256"));
+
+        let input = encrypt_nested("application/example+jwt")?;
+        assert!(jwt::decode_nested_with_decrypter(&input, &decrypter, &verifier).is_err());
+
+        let mut context = JwtContext::new();
+        context.add_acceptable_nested_content_type("example+JWT");
+        assert!(context.is_acceptable_nested_content_type("application/Example+jwt"));
+        let (dst_payload, _, _) =
+            context.decode_nested_with_decrypter(&input, &decrypter, &verifier)?;
+        assert_eq!(dst_payload.subject(), Some("nested"));
+        assert!(context.decode_with_decrypter(&input, &decrypter).is_err());
+
+        context.remove_acceptable_nested_content_type("JWT");
+        let input = encrypt_nested("JWT")?;
+        assert!(context
+            .decode_nested_with_decrypter(&input, &decrypter, &verifier)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_decode_with_embedded_jwk() -> Result<()> {
+        let private_key = Jwk::from_slice(load_file("jwk/EC_P-256_private.jwk")?)?;
+        let public_key = Jwk::from_slice(load_file("jwk/EC_P-256_public.jwk")?)?;
+
+        // Anyone can sign a JWT and embed the matching public key.
+        let mut header = JwsHeader::new();
+        header.set_jwk(public_key.clone());
+        let signer = ES256.signer_from_jwk(&private_key)?;
+        let input = jwt::encode_with_signer(&JwtPayload::new(), &header, &signer)?;
+
+        let factory = |jwk: &Jwk| -> Result<Box<dyn JwsVerifier>, JoseError> {
+            Ok(Box::new(ES256.verifier_from_jwk(jwk)?))
+        };
+
+        let context = JwtContext::new();
+        let options = DecodeOptions::new();
+        assert!(!options.is_trust_embedded_keys());
+        assert!(context
+            .decode_with_embedded_jwk(&input, &options, factory)
+            .is_err());
+
+        // The flow that the caller supplies the key is unaffected.
+        let verifier = ES256.verifier_from_jwk(&public_key)?;
+        let (_, dst_header) = context.decode_with_verifier(&input, &verifier)?;
+        assert_eq!(dst_header.jwk(), Some(&public_key));
+
+        let trusted = DecodeOptions::new().trust_embedded_keys(true);
+        let (_, dst_header) =
+            context.decode_with_embedded_jwk(&format!(" {}\n", input), &trusted, factory)?;
+        assert_eq!(dst_header.jwk(), Some(&public_key));
+
+        let input = jwt::encode_with_signer(&JwtPayload::new(), &JwsHeader::new(), &signer)?;
+        assert!(context
+            .decode_with_embedded_jwk(&input, &trusted, factory)
+            .is_err());
+
+        // The other header claims that carry key material are refused too.
+        for (key, value) in &[
+            ("jku", json!("https://example.com/jwks.json")),
+            ("x5u", json!("https://example.com/cert.pem")),
+            ("x5c", json!(["MIIB"])),
+        ] {
+            let mut header = JwsHeader::new();
+            header.set_claim(key, Some(value.clone()))?;
+            let input = jwt::encode_with_signer(&JwtPayload::new(), &header, &signer)?;
+            match context.decode_with_embedded_jwk(&input, &options, factory) {
+                Err(err) => assert!(err.to_string().contains(key)),
+                Ok(_) => panic!("the {} header claim must not be trusted", key),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_decode_with_allowed_content_encryptions() -> Result<()> {
+        let key = util::rand_bytes(32);
+        let encrypter = Dir.encrypter_from_slice(&key)?;
+        let decrypter = Dir.decrypter_from_slice(&key)?;
+
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A128CBC-HS256");
+        let input = jwt::encode_with_encrypter(&JwtPayload::new(), &header, &encrypter)?;
+
+        let context = JwtContext::new();
+        let options = DecodeOptions::new();
+        assert!(options.is_allowed_content_encryption("A128CBC-HS256"));
+        context.decode_with_decrypter_and_options(&input, &decrypter, &options)?;
+
+        let options = DecodeOptions::new().allowed_content_encryptions(vec!["A256GCM".to_string()]);
+        assert!(!options.is_allowed_content_encryption("A128CBC-HS256"));
+        let err = context
+            .decode_with_decrypter_and_options(&input, &decrypter, &options)
+            .unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJweFormat(_)));
+        assert!(err.to_string().contains("not allowed: A128CBC-HS256"));
+
+        let options = DecodeOptions::new()
+            .allowed_content_encryptions(vec!["A256GCM".to_string(), "A128CBC-HS256".to_string()]);
+        context.decode_with_decrypter_and_options(&input, &decrypter, &options)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_with_verifier_cache() -> Result<()> {
+        let mut jwk_set = JwkSet::new();
+        for kid in &["key-1", "key-2"] {
+            let mut jwk = HS256.to_jwk(&util::rand_bytes(64));
+            jwk.set_key_id(*kid);
+            jwk_set.push_key(jwk);
+        }
+
+        let jwk = jwk_set.get("key-1").next().unwrap();
+        let signer = HS256.signer_from_jwk(jwk)?;
+        let mut header = JwsHeader::new();
+        header.set_key_id("key-1");
+        let jwt_string1 = jwt::encode_with_signer(&JwtPayload::new(), &header, &signer)?;
+        let mut payload = JwtPayload::new();
+        payload.set_subject("sub");
+        let jwt_string2 = jwt::encode_with_signer(&payload, &header, &signer)?;
+
+        let cache = VerifierCache::new(8);
+        let count = Cell::new(0);
+        let selector = |jwk: &Jwk| -> Result<Option<Box<dyn JwsVerifier>>, JoseError> {
+            count.set(count.get() + 1);
+            Ok(Some(Box::new(HS256.verifier_from_jwk(jwk)?)))
+        };
+
+        jwt::decode_with_verifier_in_jwk_set_cached(&jwt_string1, &jwk_set, &cache, selector)?;
+        let verifier1 = cache.get_or_insert_with(jwk, |_| unreachable!())?.unwrap();
+
+        let (dst_payload, _) =
+            jwt::decode_with_verifier_in_jwk_set_cached(&jwt_string2, &jwk_set, &cache, selector)?;
+        let verifier2 = cache.get_or_insert_with(jwk, |_| unreachable!())?.unwrap();
+
+        assert_eq!(dst_payload, payload);
+        assert_eq!(count.get(), 1);
+        assert_eq!(cache.len(), 1);
+        assert!(Arc::ptr_eq(&verifier1, &verifier2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verifier_cache_eviction() -> Result<()> {
+        let jwks: Vec<Jwk> = (0..3)
+            .map(|_| HS256.to_jwk(&util::rand_bytes(64)))
+            .collect();
+
+        let cache = VerifierCache::new(2);
+        let selector = |jwk: &Jwk| -> Result<Option<Box<dyn JwsVerifier>>, JoseError> {
+            Ok(Some(Box::new(HS256.verifier_from_jwk(jwk)?)))
+        };
+        for jwk in &jwks {
+            cache.get_or_insert_with(jwk, selector)?;
+        }
+        assert_eq!(cache.len(), 2);
+
+        let count = Cell::new(0);
+        cache.get_or_insert_with(&jwks[0], |jwk| {
+            count.set(count.get() + 1);
+            selector(jwk)
+        })?;
+        assert_eq!(count.get(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verifier_cache_key_parameters() -> Result<()> {
+        let k = base64::encode_config(util::rand_bytes(64), base64::URL_SAFE_NO_PAD);
+        let mut jwk = Jwk::new("oct");
+        jwk.set_parameter("k", Some(json!(k)))?;
+
+        let cache = VerifierCache::new(8);
+        let count = Cell::new(0);
+        let selector = |jwk: &Jwk| -> Result<Option<Box<dyn JwsVerifier>>, JoseError> {
+            count.set(count.get() + 1);
+            Ok(Some(Box::new(HS256.verifier_from_jwk(jwk)?)))
+        };
+
+        cache.get_or_insert_with(&jwk, selector)?;
+        cache.get_or_insert_with(&jwk, selector)?;
+        assert_eq!(count.get(), 1);
+
+        jwk.set_algorithm("HS256");
+        cache.get_or_insert_with(&jwk, selector)?;
+        jwk.set_key_use("sig");
+        cache.get_or_insert_with(&jwk, selector)?;
+        jwk.set_key_operations(vec!["verify"]);
+        cache.get_or_insert_with(&jwk, selector)?;
+        jwk.set_key_id("kid");
+        let verifier = cache.get_or_insert_with(&jwk, selector)?.unwrap();
+        assert_eq!(count.get(), 5);
+        assert_eq!(verifier.key_id(), Some("kid"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validator_from_expected() -> Result<()> {
+        let mut expected = JwtPayload::new();
+        expected.set_issuer("iss");
+        expected.set_subject("sub");
+        expected.set_audience(vec!["aud1", "aud2"]);
+        expected.set_jwt_id("jti");
+        expected.set_claim("role", Some(json!("admin")))?;
+        expected.set_issued_at(SystemTime::now() - Duration::from_secs(60));
+        expected.set_expires_at(SystemTime::now() + Duration::from_secs(60));
+
+        let validator = JwtPayloadValidator::from_expected(&expected);
+        assert_eq!(validator.issuer(), Some("iss"));
+        assert_eq!(validator.subject(), Some("sub"));
+        assert_eq!(validator.audience(), Some("aud1"));
+        assert_eq!(validator.jwt_id(), Some("jti"));
+        assert_eq!(validator.claim("role"), Some(&json!("admin")));
+        assert_eq!(validator.claim("iat"), None);
+        assert_eq!(validator.claim("exp"), None);
+
+        validator.validate(&expected)?;
+
+        let mut payload = expected.clone();
+        payload.set_claim("role", Some(json!("user")))?;
+        assert!(validator.validate(&payload).is_err());
+
+        let mut payload = expected.clone();
+        payload.set_audience(vec!["aud3"]);
+        assert!(validator.validate(&payload).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_audiences() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        assert_eq!(payload.audiences(), Vec::<String>::new());
+
+        payload.set_audience(vec!["a"]);
+        assert_eq!(payload.audiences(), vec!["a".to_string()]);
+
+        payload.set_audience(vec!["a", "b"]);
+        assert_eq!(payload.audiences(), vec!["a".to_string(), "b".to_string()]);
+
+        let mut map = Map::new();
+        map.insert("aud".to_string(), json!("c"));
+        let payload = JwtPayload::from_map(map)?;
+        assert_eq!(payload.audiences(), vec!["c".to_string()]);
+
+        let mut map = Map::new();
+        map.insert("aud".to_string(), json!(["c", "d"]));
+        let payload = JwtPayload::from_map(map)?;
+        assert_eq!(payload.audiences(), vec!["c".to_string(), "d".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_truncates_time_to_seconds() -> Result<()> {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_600_000_000_999);
+        let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+        let mut payload = JwtPayload::new();
+        payload.set_expires_at(time);
+        payload.set_not_before(time);
+        payload.set_issued_at(time);
+
+        assert_eq!(payload.expires_at(), Some(&expected));
+        assert_eq!(payload.not_before(), Some(&expected));
+        assert_eq!(payload.issued_at(), Some(&expected));
+        assert_eq!(payload.claim("exp"), Some(&json!(1_600_000_000)));
+
+        let decoded = JwtPayload::from_map(payload.claims_set().clone())?;
+        assert_eq!(decoded, payload);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_time_out_of_range() -> Result<()> {
+        for key in &["exp", "nbf", "iat", "auth_time"] {
+            let mut map = Map::new();
+            map.insert(key.to_string(), json!(u64::MAX));
+            let err = JwtPayload::from_map(map).unwrap_err();
+            assert!(matches!(err, JoseError::InvalidJwtFormat(_)));
+            assert!(format!("{}", err).contains("out of range"));
+
+            let mut payload = JwtPayload::new();
+            assert!(payload.set_claim(key, Some(json!(u64::MAX))).is_err());
+            assert_eq!(payload.claim(key), None);
+        }
+
+        let payload = JwtPayload::from_slice(br#"{"exp":18446744073709551615}"#);
+        assert!(payload.is_err());
+
+        let mut map = Map::new();
+        map.insert("exp".to_string(), json!(253402300799u64));
+        let payload = JwtPayload::from_map(map)?;
+        assert_eq!(
+            payload.expires_at(),
+            Some(&(SystemTime::UNIX_EPOCH + Duration::from_secs(253402300799)))
         );
-        assert!(matches!(header.key_id(), Some("kid")));
-        assert!(matches!(header.token_type(), Some("typ")));
-        assert!(matches!(header.content_type(), Some("cty")));
-        assert!(matches!(header.url(), Some("url")));
-        assert!(matches!(header.nonce(), Some(val) if val == &b"nonce".to_vec()));
-        assert!(matches!(header.critical(), Some(vals) if vals == &vec!["crit0", "crit1"]));
-        assert!(matches!(header.claim("header_claim"), Some(val) if val == &json!("header_claim")));
 
         Ok(())
     }
 
     #[test]
-    fn test_new_payload() -> Result<()> {
+    fn test_jwt_payload_set_audience_array() -> Result<()> {
         let mut payload = JwtPayload::new();
-        payload.set_issuer("iss");
-        payload.set_subject("sub");
-        payload.set_audience(vec!["aud0", "aud1"]);
-        payload.set_expires_at(SystemTime::UNIX_EPOCH);
-        payload.set_not_before(SystemTime::UNIX_EPOCH);
-        payload.set_issued_at(SystemTime::UNIX_EPOCH);
-        payload.set_jwt_id("jti");
-        payload.set_claim("payload_claim", Some(json!("payload_claim")))?;
+        payload.set_audience(vec!["a"]);
+        assert_eq!(payload.claims_set().get("aud"), Some(&json!("a")));
 
-        assert!(matches!(payload.issuer(), Some("iss")));
-        assert!(matches!(payload.subject(), Some("sub")));
-        assert!(matches!(payload.audience(), Some(vals) if vals == &vec!["aud0", "aud1"]));
-        assert!(matches!(payload.expires_at(), Some(val) if val == &SystemTime::UNIX_EPOCH));
-        assert!(matches!(payload.not_before(), Some(val) if val == &SystemTime::UNIX_EPOCH));
-        assert!(matches!(payload.issued_at(), Some(val) if val == &SystemTime::UNIX_EPOCH));
-        assert!(matches!(payload.jwt_id(), Some("jti")));
-        assert!(
-            matches!(payload.claim("payload_claim"), Some(val) if val == &json!("payload_claim"))
-        );
+        payload.set_audience_array(vec!["a"]);
+        assert_eq!(payload.claims_set().get("aud"), Some(&json!(["a"])));
+        assert_eq!(payload.audience(), Some(&vec!["a".to_string()]));
+
+        payload.set_audience_array(vec!["a", "b"]);
+        assert_eq!(payload.claims_set().get("aud"), Some(&json!(["a", "b"])));
 
         Ok(())
     }
 
     #[test]
-    fn test_jwt_unsecured() -> Result<()> {
+    fn test_jwt_with_surrounding_whitespace() -> Result<()> {
+        let private_key = util::rand_bytes(64);
+
         let mut src_header = JwsHeader::new();
         src_header.set_token_type("JWT");
         let src_payload = JwtPayload::new();
-        let jwt_string = jwt::encode_unsecured(&src_payload, &src_header)?;
-        let (dst_payload, dst_header) = jwt::decode_unsecured(&jwt_string)?;
+        let signer = HS256.signer_from_slice(&private_key)?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &src_header, &signer)?;
 
-        src_header.set_claim("alg", Some(json!("none")))?;
-        assert_eq!(src_header, dst_header);
+        let verifier = HS256.verifier_from_slice(&private_key)?;
+        let (dst_payload, _) = jwt::decode_with_verifier(&format!("{}\n", jwt_string), &verifier)?;
+        assert_eq!(src_payload, dst_payload);
+
+        let (dst_payload, _) =
+            jwt::decode_with_verifier(&format!(" {}\r\n", jwt_string), &verifier)?;
         assert_eq!(src_payload, dst_payload);
 
+        let jwt_string = jwt_string.replacen(".", ". ", 1);
+        assert!(jwt::decode_with_verifier(&jwt_string, &verifier).is_err());
+
         Ok(())
     }
 
     #[test]
-    fn test_jwt_with_hmac() -> Result<()> {
-        for alg in &[HS256, HS384, HS512] {
-            let private_key = util::rand_bytes(64);
-
-            let mut src_header = JwsHeader::new();
-            src_header.set_token_type("JWT");
-            let src_payload = JwtPayload::new();
-            let signer = alg.signer_from_slice(&private_key)?;
-            let jwt_string = jwt::encode_with_signer(&src_payload, &src_header, &signer)?;
+    fn test_jwt_decode_auto() -> Result<()> {
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("test");
 
-            let verifier = alg.verifier_from_slice(&private_key)?;
-            let (dst_payload, dst_header) = jwt::decode_with_verifier(&jwt_string, &verifier)?;
+        let private_key = util::rand_bytes(64);
+        let signer = HS256.signer_from_slice(&private_key)?;
+        let verifier = HS256.verifier_from_slice(&private_key)?;
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        let jws_string = jwt::encode_with_signer(&src_payload, &header, &signer)?;
+
+        let key = b"01234567890123456789012345678901";
+        let encrypter = Dir.encrypter_from_slice(key)?;
+        let decrypter = Dir.decrypter_from_slice(key)?;
+        let mut header = JweHeader::new();
+        header.set_token_type("JWT");
+        header.set_content_encryption("A128CBC-HS256");
+        let jwe_string = jwt::encode_with_encrypter(&src_payload, &header, &encrypter)?;
+
+        match jwt::decode_auto(&jws_string, Some(&verifier), Some(&decrypter))? {
+            DecodedJwt::Signed(dst_payload, dst_header) => {
+                assert_eq!(src_payload, dst_payload);
+                assert_eq!(dst_header.algorithm(), Some("HS256"));
+            }
+            DecodedJwt::Encrypted(..) => unreachable!(),
+        }
 
-            src_header.set_claim("alg", Some(json!(alg.name())))?;
-            assert_eq!(src_header, dst_header);
-            assert_eq!(src_payload, dst_payload);
+        match jwt::decode_auto(&jwe_string, Some(&verifier), Some(&decrypter))? {
+            DecodedJwt::Encrypted(dst_payload, dst_header) => {
+                assert_eq!(src_payload, dst_payload);
+                assert_eq!(dst_header.algorithm(), Some("dir"));
+            }
+            DecodedJwt::Signed(..) => unreachable!(),
         }
 
+        assert!(jwt::decode_auto(&jws_string, None, Some(&decrypter)).is_err());
+        assert!(jwt::decode_auto(&jwe_string, Some(&verifier), None).is_err());
+
         Ok(())
     }
 
@@ -1298,19 +3240,18 @@ mod tests {
 
     #[test]
     fn test_jwt_with_ecdsa_pem() -> Result<()> {
-        for alg in &[ES256, ES384, ES512, ES256K] {
-            let private_key = load_file(match alg {
-                ES256 => "pem/EC_P-256_private.pem",
-                ES384 => "pem/EC_P-384_private.pem",
-                ES512 => "pem/EC_P-521_private.pem",
-                ES256K => "pem/EC_secp256k1_private.pem",
-            })?;
-            let public_key = load_file(match alg {
-                ES256 => "pem/EC_P-256_public.pem",
-                ES384 => "pem/EC_P-384_public.pem",
-                ES512 => "pem/EC_P-521_public.pem",
-                ES256K => "pem/EC_secp256k1_public.pem",
-            })?;
+        for (alg, private_key, public_key) in &[
+            (ES256, "pem/EC_P-256_private.pem", "pem/EC_P-256_public.pem"),
+            (ES384, "pem/EC_P-384_private.pem", "pem/EC_P-384_public.pem"),
+            (ES512, "pem/EC_P-521_private.pem", "pem/EC_P-521_public.pem"),
+            (
+                ES256K,
+                "pem/EC_secp256k1_private.pem",
+                "pem/EC_secp256k1_public.pem",
+            ),
+        ] {
+            let private_key = load_file(private_key)?;
+            let public_key = load_file(public_key)?;
 
             let mut src_header = JwsHeader::new();
             src_header.set_token_type("JWT");
@@ -1329,21 +3270,110 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwt_decode_with_verifier_as() -> Result<()> {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Claims {
+            sub: String,
+            roles: Vec<String>,
+            exp: u64,
+        }
+
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("subject");
+        src_payload.set_claim("roles", Some(json!(["admin", "user"])))?;
+        src_payload.set_expires_at(SystemTime::UNIX_EPOCH + Duration::from_secs(1600000000));
+
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let verifier = HS256.verifier_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+
+        let (claims, header) = jwt::decode_with_verifier_as::<Claims>(&jwt_string, &verifier)?;
+        assert_eq!(
+            claims,
+            Claims {
+                sub: "subject".to_string(),
+                roles: vec!["admin".to_string(), "user".to_string()],
+                exp: 1600000000,
+            }
+        );
+        assert_eq!(header.algorithm(), Some("HS256"));
+
+        let other = HS256.verifier_from_slice(b"ABCDEF0123456789ABCDEF0123456789")?;
+        assert!(jwt::decode_with_verifier_as::<Claims>(&jwt_string, &other).is_err());
+
+        #[derive(Debug, Deserialize)]
+        struct OtherClaims {
+            #[allow(dead_code)]
+            email: String,
+        }
+        let err = jwt::decode_with_verifier_as::<OtherClaims>(&jwt_string, &verifier).unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJwtPayload(_)));
+
+        // Duplicate members are refused like the other decoders do, even if T ignores them.
+        let payload = b"{\"sub\":\"a\",\"roles\":[],\"exp\":1,\"x\":1,\"x\":2}";
+        let jwt_string = jws::serialize_compact(payload, &JwsHeader::new(), &signer)?;
+        let err = jwt::decode_with_verifier_as::<Claims>(&jwt_string, &verifier).unwrap_err();
+        assert!(matches!(err, JoseError::InvalidJwtPayload(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_verify_with_pem() -> Result<()> {
+        let rsa_public_key = load_file("pem/RSA_2048bit_public.pem")?;
+        let ec_public_key = load_file("pem/EC_P-256_public.pem")?;
+
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("subject");
+
+        let signer = RS256.signer_from_pem(load_file("pem/RSA_2048bit_private.pem")?)?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        let (dst_payload, dst_header) = jwt::verify_with_pem(&jwt_string, &rsa_public_key)?;
+        assert_eq!(dst_header.algorithm(), Some("RS256"));
+        assert_eq!(src_payload, dst_payload);
+        assert!(jwt::verify_with_pem(&jwt_string, &ec_public_key).is_err());
+
+        let signer = ES256.signer_from_pem(load_file("pem/EC_P-256_private.pem")?)?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        let (dst_payload, dst_header) = jwt::verify_with_pem(&jwt_string, &ec_public_key)?;
+        assert_eq!(dst_header.algorithm(), Some("ES256"));
+        assert_eq!(src_payload, dst_payload);
+        assert!(jwt::verify_with_pem(&jwt_string, &rsa_public_key).is_err());
+
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let jwt_string = jwt::encode_with_signer(&src_payload, &JwsHeader::new(), &signer)?;
+        assert!(jwt::verify_with_pem(&jwt_string, &rsa_public_key).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_jwt_with_ecdsa_der() -> Result<()> {
-        for alg in &[ES256, ES384, ES512, ES256K] {
-            let private_key = load_file(match alg {
-                ES256 => "der/EC_P-256_pkcs8_private.der",
-                ES384 => "der/EC_P-384_pkcs8_private.der",
-                ES512 => "der/EC_P-521_pkcs8_private.der",
-                ES256K => "der/EC_secp256k1_pkcs8_private.der",
-            })?;
-            let public_key = load_file(match alg {
-                ES256 => "der/EC_P-256_spki_public.der",
-                ES384 => "der/EC_P-384_spki_public.der",
-                ES512 => "der/EC_P-521_spki_public.der",
-                ES256K => "der/EC_secp256k1_spki_public.der",
-            })?;
+        for (alg, private_key, public_key) in &[
+            (
+                ES256,
+                "der/EC_P-256_pkcs8_private.der",
+                "der/EC_P-256_spki_public.der",
+            ),
+            (
+                ES384,
+                "der/EC_P-384_pkcs8_private.der",
+                "der/EC_P-384_spki_public.der",
+            ),
+            (
+                ES512,
+                "der/EC_P-521_pkcs8_private.der",
+                "der/EC_P-521_spki_public.der",
+            ),
+            (
+                ES256K,
+                "der/EC_secp256k1_pkcs8_private.der",
+                "der/EC_secp256k1_spki_public.der",
+            ),
+        ] {
+            let private_key = load_file(private_key)?;
+            let public_key = load_file(public_key)?;
 
             let mut src_header = JwsHeader::new();
             src_header.set_token_type("JWT");
@@ -1384,6 +3414,438 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_jwt_payload_validate_issued_at_required() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("iss");
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.validate(&payload)?;
+
+        validator.require_issued_at();
+        assert!(validator.validate(&payload).is_err());
+
+        payload.set_issued_at(SystemTime::UNIX_EPOCH);
+        validator.validate(&payload)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_expiration_required() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_not_before(SystemTime::UNIX_EPOCH);
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_base_time(SystemTime::UNIX_EPOCH + Duration::from_secs(u32::MAX as u64));
+        validator.validate(&payload)?;
+
+        validator.require_expiration();
+        assert!(validator.is_expiration_required());
+        assert!(validator.validate(&payload).is_err());
+
+        payload.set_expires_at(SystemTime::UNIX_EPOCH + Duration::from_secs(u32::MAX as u64 + 1));
+        validator.validate(&payload)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_nonce() -> Result<()> {
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_nonce("n-0S6_WzA2Mj");
+        assert_eq!(validator.nonce(), Some("n-0S6_WzA2Mj"));
+
+        let mut payload = JwtPayload::new();
+        assert!(validator.validate(&payload).is_err());
+
+        payload.set_claim("nonce", Some(json!("n-0S6_WzA2Mj")))?;
+        validator.validate(&payload)?;
+
+        payload.set_claim("nonce", Some(json!("other")))?;
+        assert!(validator.validate(&payload).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_authorized_party() -> Result<()> {
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_audience("s6BhdRkqt3");
+        validator.set_authorized_party("s6BhdRkqt3");
+        assert_eq!(validator.authorized_party(), Some("s6BhdRkqt3"));
+
+        // A single audience does not require azp.
+        let mut payload = JwtPayload::new();
+        payload.set_audience(vec!["s6BhdRkqt3"]);
+        validator.validate(&payload)?;
+
+        payload.set_claim("azp", Some(json!("s6BhdRkqt3")))?;
+        validator.validate(&payload)?;
+
+        payload.set_claim("azp", Some(json!("other")))?;
+        assert!(validator.validate(&payload).is_err());
+
+        // Multiple audiences require azp.
+        let mut payload = JwtPayload::new();
+        payload.set_audience(vec!["s6BhdRkqt3", "https://rs.example.com/"]);
+        assert!(validator.validate(&payload).is_err());
+
+        payload.set_claim("azp", Some(json!("s6BhdRkqt3")))?;
+        validator.validate(&payload)?;
+
+        payload.set_claim("azp", Some(json!("other")))?;
+        assert!(validator.validate(&payload).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_from_str() -> Result<()> {
+        let payload: JwtPayload = r#"{"iss":"joe","exp":1300819380}"#.parse()?;
+        assert_eq!(payload.issuer(), Some("joe"));
+        assert_eq!(
+            payload.expires_at(),
+            Some(&(SystemTime::UNIX_EPOCH + Duration::from_secs(1300819380)))
+        );
+        assert_eq!(payload.to_string().parse::<JwtPayload>()?, payload);
+
+        assert!(matches!(
+            "[]".parse::<JwtPayload>(),
+            Err(JoseError::InvalidJson(_))
+        ));
+        assert!(matches!(
+            r#"{"iss":1}"#.parse::<JwtPayload>(),
+            Err(JoseError::InvalidJwtFormat(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_validate_subject_pattern() -> Result<()> {
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_subject_pattern(|sub| sub.contains("://"));
+
+        let mut payload = JwtPayload::new();
+        assert!(validator.validate(&payload).is_err());
+
+        payload.set_subject("https://x");
+        validator.validate(&payload)?;
+
+        payload.set_subject("abc");
+        assert!(validator.validate(&payload).is_err());
+
+        // The exact match is checked in addition to the predicate.
+        validator.set_subject("https://y");
+        payload.set_subject("https://x");
+        assert!(validator.validate(&payload).is_err());
+        payload.set_subject("https://y");
+        validator.validate(&payload)?;
+
+        validator.remove_subject_pattern();
+        validator.set_subject("abc");
+        payload.set_subject("abc");
+        validator.validate(&payload)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_access_token_builder() -> Result<()> {
+        let mut builder = AccessTokenBuilder::new();
+        builder.set_issuer("https://as.example.com/");
+        builder.set_audience(vec!["https://rs.example.com/"]);
+        builder.set_client_id("s6BhdRkqt3");
+        builder.set_issued_at(SystemTime::UNIX_EPOCH + Duration::from_secs(1600000000));
+        builder.set_expires_at(SystemTime::UNIX_EPOCH + Duration::from_secs(1600003600));
+        builder.set_jwt_id("dbe39bf3a3ba4238a513f51d6e1691c4");
+        builder.set_scope(vec!["openid", "profile"]);
+
+        // sub is missing.
+        assert!(builder.build().is_err());
+
+        builder.set_subject("5ba552d67");
+        let (payload, header) = builder.build()?;
+        assert_eq!(header.token_type(), Some("at+jwt"));
+        assert_eq!(payload.subject(), Some("5ba552d67"));
+        assert_eq!(payload.claim("client_id"), Some(&json!("s6BhdRkqt3")));
+        assert_eq!(payload.claim("scope"), Some(&json!("openid profile")));
+
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let verifier = HS256.verifier_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let jwt = jwt::encode_with_signer(&payload, &header, &signer)?;
+        let (decoded_payload, decoded_header) = jwt::decode_with_verifier(&jwt, &verifier)?;
+        assert_eq!(decoded_header.token_type(), Some("at+jwt"));
+        assert_eq!(decoded_payload, payload);
+
+        builder.set_token_type("AT+JWT");
+        let (_, header) = builder.build()?;
+        assert_eq!(header.token_type(), Some("AT+JWT"));
+        assert!(header.is_token_type("at+jwt"));
+        assert!(header.is_token_type("application/at+jwt"));
+        assert!(!header.is_token_type("jwt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_from_slice_and_to_vec() -> Result<()> {
+        let mut src_payload = JwtPayload::new();
+        src_payload.set_subject("subject");
+        src_payload.set_audience(vec!["a", "b"]);
+        src_payload.set_expires_at(SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        src_payload.set_claim("custom", Some(json!({ "nested": [1, 2] })))?;
+
+        let bytes = src_payload.to_vec();
+        assert_eq!(bytes, src_payload.to_string().into_bytes());
+
+        let dst_payload = JwtPayload::from_slice(&bytes)?;
+        assert_eq!(dst_payload, src_payload);
+        assert_eq!(
+            dst_payload.audience(),
+            Some(&vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(dst_payload.expires_at(), src_payload.expires_at());
+
+        assert!(JwtPayload::from_slice(b"[]").is_err());
+        assert!(JwtPayload::from_slice(b"{\"exp\":\"tomorrow\"}").is_err());
+        assert!(JwtPayload::from_slice(b"{\"sub\":\"a\",\"sub\":\"b\"}").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_oidc_claims() -> Result<()> {
+        let auth_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1600000000);
+
+        let mut payload = JwtPayload::new();
+        assert_eq!(payload.auth_time(), None);
+        assert_eq!(payload.acr(), None);
+        assert_eq!(payload.amr(), None);
+
+        payload.set_auth_time(auth_time);
+        payload.set_acr("urn:mace:incommon:iap:silver");
+        payload.set_amr(vec!["pwd", "otp"]);
+        assert_eq!(payload.auth_time(), Some(auth_time));
+        assert_eq!(payload.acr(), Some("urn:mace:incommon:iap:silver"));
+        assert_eq!(
+            payload.amr(),
+            Some(vec!["pwd".to_string(), "otp".to_string()])
+        );
+
+        let parsed = JwtPayload::from_slice(payload.to_vec())?;
+        assert_eq!(parsed.auth_time(), Some(auth_time));
+        assert_eq!(parsed.acr(), Some("urn:mace:incommon:iap:silver"));
+        assert_eq!(
+            parsed.amr(),
+            Some(vec!["pwd".to_string(), "otp".to_string()])
+        );
+
+        let signer = HS256.signer_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let verifier = HS256.verifier_from_slice(b"0123456789ABCDEF0123456789ABCDEF")?;
+        let jwt = jwt::encode_with_signer(&payload, &JwsHeader::new(), &signer)?;
+        let (decoded, _) = jwt::decode_with_verifier(&jwt, &verifier)?;
+        assert_eq!(decoded.auth_time(), Some(auth_time));
+        assert_eq!(decoded.acr(), payload.acr());
+        assert_eq!(decoded.amr(), payload.amr());
+
+        let mut map = Map::new();
+        map.insert("auth_time".to_string(), json!("1600000000"));
+        assert!(JwtPayload::from_map(map).is_err());
+        let mut map = Map::new();
+        map.insert("acr".to_string(), json!(1));
+        assert!(JwtPayload::from_map(map).is_err());
+        let mut map = Map::new();
+        map.insert("amr".to_string(), json!("pwd"));
+        assert!(JwtPayload::from_map(map).is_err());
+        let mut map = Map::new();
+        map.insert("amr".to_string(), json!(["pwd", 1]));
+        assert!(JwtPayload::from_map(map).is_err());
+
+        let mut payload = JwtPayload::new();
+        payload.set_claim("amr", Some(json!(["mfa"])))?;
+        assert_eq!(payload.amr(), Some(vec!["mfa".to_string()]));
+        payload.set_claim("amr", None)?;
+        assert_eq!(payload.amr(), None);
+        assert!(payload.set_claim("auth_time", Some(json!(-1))).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_set_random_jwt_id() -> Result<()> {
+        let mut payload1 = JwtPayload::new();
+        payload1.set_random_jwt_id();
+        let mut payload2 = JwtPayload::new();
+        payload2.set_random_jwt_id();
+
+        let jti1 = payload1.jwt_id().unwrap();
+        let jti2 = payload2.jwt_id().unwrap();
+        assert_eq!(jti1.len(), 22);
+        assert_eq!(jti2.len(), 22);
+        assert_ne!(jti1, jti2);
+        assert_eq!(
+            base64::decode_config(jti1, base64::URL_SAFE_NO_PAD)?.len(),
+            16
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_payload_claim_at() -> Result<()> {
+        let mut payload = JwtPayload::new();
+        payload.set_claim_at("/realm_access/roles", json!(["admin"]))?;
+        payload.set_claim_at("/realm_access/roles/-", json!("user"))?;
+        payload.set_claim_at("/a~1b/c~0d", json!(1))?;
+        payload.set_claim_at("/sub", json!("subject"))?;
+
+        assert_eq!(
+            payload.claim_at("/realm_access/roles"),
+            Some(&json!(["admin", "user"]))
+        );
+        assert_eq!(
+            payload.claim_at("/realm_access/roles/1"),
+            Some(&json!("user"))
+        );
+        assert_eq!(payload.claim("a/b"), Some(&json!({ "c~d": 1 })));
+        assert_eq!(payload.claim_at("/a~1b/c~0d"), Some(&json!(1)));
+        assert_eq!(payload.subject(), Some("subject"));
+        assert_eq!(payload.claim_at("/realm_access/groups"), None);
+        assert_eq!(payload.claim_at("sub"), None);
+
+        assert!(payload.set_claim_at("/sub/name", json!("x")).is_err());
+        assert!(payload
+            .set_claim_at("/realm_access/roles/5", json!("x"))
+            .is_err());
+        assert!(payload.set_claim_at("/iss", json!(1)).is_err());
+        assert!(payload.set_claim_at("realm_access", json!(1)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_decode_with_jwk_set_without_kid() -> Result<()> {
+        let private_key1 = util::rand_bytes(64);
+        let private_key2 = util::rand_bytes(64);
+        let mut jwk_set = JwkSet::new();
+        jwk_set.push_key(HS256.to_jwk(&private_key1));
+        jwk_set.push_key(HS256.to_jwk(&private_key2));
+
+        let verifiers = [
+            HS256.verifier_from_slice(&private_key1)?,
+            HS256.verifier_from_slice(&private_key2)?,
+        ];
+        let keys = jwk_set.keys();
+
+        let payload = JwtPayload::new();
+        let signer = HS256.signer_from_slice(&private_key2)?;
+        let jwt_string = jwt::encode_with_signer(&payload, &JwsHeader::new(), &signer)?;
+        let other_signer = HS256.signer_from_slice(util::rand_bytes(64))?;
+        let other_jwt_string = jwt::encode_with_signer(&payload, &JwsHeader::new(), &other_signer)?;
+
+        let mut context = JwtContext::new();
+        for (max_keys, input, expected) in &[
+            (0, &jwt_string, false),
+            (1, &jwt_string, false),
+            (2, &jwt_string, true),
+            (2, &other_jwt_string, false),
+        ] {
+            context.set_max_keys_without_kid(*max_keys);
+            let result = context.decode_with_verifier_in_jwk_set(input, &jwk_set, |jwk| {
+                let index = keys.iter().position(|key| *key == jwk);
+                Ok(index.map(|index| &verifiers[index] as &dyn JwsVerifier))
+            });
+            assert_eq!(result.is_ok(), *expected);
+            if let Ok((dst_payload, _)) = result {
+                assert_eq!(payload, dst_payload);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_decode_binary_payload() -> Result<()> {
+        let private_key = util::rand_bytes(64);
+        let signer = HS256.signer_from_slice(&private_key)?;
+        let verifier = HS256.verifier_from_slice(&private_key)?;
+
+        let input = jws::serialize_compact(&[0xFF, 0xFE, 0x00, 0x01], &JwsHeader::new(), &signer)?;
+        match jwt::decode_with_verifier(&input, &verifier) {
+            Err(JoseError::InvalidJwtPayload(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let other_verifier = HS256.verifier_from_slice(util::rand_bytes(64))?;
+        match jwt::decode_with_verifier(&input, &other_verifier) {
+            Err(JoseError::InvalidJwtPayload(_)) => panic!("the signature must be checked first"),
+            Err(_) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_default_token_type() -> Result<()> {
+        let mut context = JwtContext::new();
+        assert_eq!(context.default_token_type(), None);
+        context.set_default_token_type(Some("at+jwt".to_string()));
+        assert_eq!(context.default_token_type(), Some("at+jwt"));
+
+        let private_key = util::rand_bytes(64);
+        let signer = HS256.signer_from_slice(&private_key)?;
+        let verifier = HS256.verifier_from_slice(&private_key)?;
+        let payload = JwtPayload::new();
+
+        let jwt_string = context.encode_with_signer(&payload, &JwsHeader::new(), &signer)?;
+        let (_, header) = context.decode_with_verifier(&jwt_string, &verifier)?;
+        assert_eq!(header.token_type(), Some("at+jwt"));
+
+        let mut src_header = JwsHeader::new();
+        src_header.set_token_type("JWT");
+        let jwt_string = context.encode_with_signer(&payload, &src_header, &signer)?;
+        let (_, header) = context.decode_with_verifier(&jwt_string, &verifier)?;
+        assert_eq!(header.token_type(), Some("JWT"));
+
+        let key = util::rand_bytes(32);
+        let mut src_header = JweHeader::new();
+        src_header.set_content_encryption("A128CBC-HS256");
+        let encrypter = Dir.encrypter_from_slice(&key)?;
+        let decrypter = Dir.decrypter_from_slice(&key)?;
+        let jwt_string = context.encode_with_encrypter(&payload, &src_header, &encrypter)?;
+        let (_, header) = context.decode_with_decrypter(&jwt_string, &decrypter)?;
+        assert_eq!(header.token_type(), Some("at+jwt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_decode_and_validate() -> Result<()> {
+        let private_key = util::rand_bytes(64);
+        let signer = HS256.signer_from_slice(&private_key)?;
+        let verifier = HS256.verifier_from_slice(&private_key)?;
+
+        let mut payload = JwtPayload::new();
+        payload.set_issuer("joe");
+        let jwt_string = jwt::encode_with_signer(&payload, &JwsHeader::new(), &signer)?;
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_issuer("joe");
+        let (dst_payload, _) = jwt::decode_and_validate(&jwt_string, &verifier, &validator)?;
+        assert_eq!(payload, dst_payload);
+
+        validator.set_issuer("bob");
+        match jwt::decode_and_validate(&jwt_string, &verifier, &validator) {
+            Err(JoseError::InvalidClaim(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_external_jwt_verify_with_hmac() -> Result<()> {
         let jwk = Jwk::from_slice(&load_file("jwk/oct_private.jwk")?)?;
@@ -1458,13 +3920,13 @@ mod tests {
 
     #[test]
     fn test_external_jwt_verify_with_ecdsa() -> Result<()> {
-        for alg in &[ES256, ES384, ES512, ES256K] {
-            let jwk = Jwk::from_slice(&load_file(match alg {
-                ES256 => "jwk/EC_P-256_public.jwk",
-                ES384 => "jwk/EC_P-384_public.jwk",
-                ES512 => "jwk/EC_P-521_public.jwk",
-                ES256K => "jwk/EC_secp256k1_public.jwk",
-            })?)?;
+        for (alg, jwk) in &[
+            (ES256, "jwk/EC_P-256_public.jwk"),
+            (ES384, "jwk/EC_P-384_public.jwk"),
+            (ES512, "jwk/EC_P-521_public.jwk"),
+            (ES256K, "jwk/EC_secp256k1_public.jwk"),
+        ] {
+            let jwk = Jwk::from_slice(&load_file(jwk)?)?;
             let verifier = alg.verifier_from_jwk(&jwk)?;
             let jwt_string = String::from_utf8(load_file(&format!("jwt/{}.jwt", alg.name()))?)?;
             let (payload, header) = jwt::decode_with_verifier(&jwt_string, &verifier)?;
@@ -4,7 +4,11 @@ use openssl::bn::BigNumRef;
 use openssl::hash::MessageDigest;
 use openssl::rand;
 use regex::bytes::{NoExpand, Regex};
-use std::fmt::Display;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Number, Value};
+use std::fmt::{self, Display};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
 use std::time::SystemTime;
 
 use openssl::error::ErrorStack;
@@ -15,6 +19,8 @@ use openssl_sys::{
 };
 use std::os::raw::c_int;
 use std::ptr;
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
 
 use crate::jwk::Jwk;
 
@@ -27,6 +33,104 @@ pub enum SourceValue {
     SystemTime(SystemTime),
 }
 
+/// A byte buffer holding secret key material.
+///
+/// The buffer is zeroized on drop when the `zeroize` feature is enabled.
+/// The derived `PartialEq` is not constant-time, so don't use it to compare secrets that
+/// an attacker can probe.
+#[derive(Clone, Eq, PartialEq)]
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    #[cfg(feature = "zeroize")]
+    fn clear(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.0.len())
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(value: Vec<u8>) -> Self {
+        Self(value)
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SecretBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        #[cfg(feature = "zeroize")]
+        self.clear();
+    }
+}
+
+/// A function that is registered in a context or a validator.
+///
+/// Two values are equal only when they share the same function, so that the owner can
+/// derive `Debug`, `PartialEq` and `Eq`.
+pub struct SharedFn<F: ?Sized>(pub Arc<F>);
+
+impl<F: ?Sized> SharedFn<F> {
+    pub fn new(value: Arc<F>) -> Self {
+        Self(value)
+    }
+}
+
+impl<F: ?Sized> Clone for SharedFn<F> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for SharedFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedFn")
+    }
+}
+
+impl<F: ?Sized> PartialEq for SharedFn<F> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::as_ptr(&self.0) as *const u8 == Arc::as_ptr(&other.0) as *const u8
+    }
+}
+
+impl<F: ?Sized> Eq for SharedFn<F> {}
+
+/// Implement `PartialEq` and `Eq` for a header that compares only its claims.
+///
+/// The decoded values in the sources are derived from the claims, so how the header was built
+/// does not matter.
+macro_rules! impl_eq_by_claims {
+    ($name:ident) => {
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.claims == other.claims
+            }
+        }
+
+        impl Eq for $name {}
+    };
+}
+
+pub(crate) use impl_eq_by_claims;
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum HashAlgorithm {
     Sha256,
@@ -72,10 +176,134 @@ pub fn rand_bytes(len: usize) -> Vec<u8> {
     vec
 }
 
+/// Return the media type that "application/" is prepended if it does not contain "/" (RFC 7515 4.1.10).
+pub fn normalize_media_type(value: &str) -> String {
+    if value.contains('/') {
+        value.to_string()
+    } else {
+        format!("application/{}", value)
+    }
+}
+
+/// Reject a JWK that has no private key parameter when it is used for signing.
+pub fn check_private_jwk(jwk: &Jwk) -> anyhow::Result<()> {
+    if jwk.parameter("d").is_none() {
+        bail!("The JWK is a public key but signing requires a private key.");
+    }
+    Ok(())
+}
+
+/// Parse a JSON object and reject it when an object contains duplicate member names (RFC 7515 5.2).
+pub fn parse_json_object(input: &[u8]) -> anyhow::Result<Map<String, Value>> {
+    let mut de = serde_json::Deserializer::from_slice(input);
+    let value = StrictValue::deserialize(&mut de)?;
+    de.end()?;
+
+    match value.0 {
+        Value::Object(map) => Ok(map),
+        _ => bail!("The JSON value must be an object."),
+    }
+}
+
+struct StrictValue(Value);
+
+impl<'de> Deserialize<'de> for StrictValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(StrictValueVisitor)
+    }
+}
+
+struct StrictValueVisitor;
+
+impl<'de> Visitor<'de> for StrictValueVisitor {
+    type Value = StrictValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<StrictValue, E> {
+        Ok(StrictValue(Value::Bool(value)))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<StrictValue, E> {
+        Ok(StrictValue(Value::Number(value.into())))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<StrictValue, E> {
+        Ok(StrictValue(Value::Number(value.into())))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<StrictValue, E> {
+        Ok(StrictValue(
+            Number::from_f64(value).map_or(Value::Null, Value::Number),
+        ))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<StrictValue, E> {
+        Ok(StrictValue(Value::String(value.to_string())))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<StrictValue, E> {
+        Ok(StrictValue(Value::String(value)))
+    }
+
+    fn visit_none<E>(self) -> Result<StrictValue, E> {
+        Ok(StrictValue(Value::Null))
+    }
+
+    fn visit_unit<E>(self) -> Result<StrictValue, E> {
+        Ok(StrictValue(Value::Null))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<StrictValue, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut vec = Vec::new();
+        while let Some(StrictValue(value)) = seq.next_element()? {
+            vec.push(value);
+        }
+        Ok(StrictValue(Value::Array(vec)))
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<StrictValue, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut map = Map::new();
+        while let Some(key) = access.next_key::<String>()? {
+            if map.contains_key(&key) {
+                return Err(de::Error::custom(format!(
+                    "The JSON object has a duplicate member: {}",
+                    key
+                )));
+            }
+            let StrictValue(value) = access.next_value()?;
+            map.insert(key, value);
+        }
+        Ok(StrictValue(Value::Object(map)))
+    }
+}
+
 pub fn ceiling(len: usize, div: usize) -> usize {
     (len + (div - 1)) / div
 }
 
+pub fn segment_spans(input: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for (i, _) in input.match_indices('.') {
+        spans.push(start..i);
+        start = i + 1;
+    }
+    spans.push(start..input.len());
+    spans
+}
+
 pub fn parse_pem(input: &[u8]) -> anyhow::Result<(String, Vec<u8>)> {
     static RE_PEM: Lazy<Regex> = Lazy::new(|| {
         Regex::new(concat!(
@@ -170,3 +398,61 @@ fn generate_der(nid: c_int) -> Result<PKey<Private>, ErrorStack> {
 
     PKey::private_key_from_der(&der)
 }
+
+/// Return a self-signed certificate of the key for tests.
+#[cfg(test)]
+pub(crate) fn self_signed_certificate(pkey: &PKey<Private>) -> anyhow::Result<openssl::x509::X509> {
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::x509::{X509NameBuilder, X509};
+
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_text("CN", "example.com")?;
+    let name = name.build();
+
+    let mut builder = X509::builder()?;
+    builder.set_version(2)?;
+    builder.set_subject_name(&name)?;
+    builder.set_issuer_name(&name)?;
+    builder.set_pubkey(pkey)?;
+    builder.set_not_before(Asn1Time::days_from_now(0)?.as_ref())?;
+    builder.set_not_after(Asn1Time::days_from_now(1)?.as_ref())?;
+    builder.sign(pkey, MessageDigest::sha256())?;
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_secret_bytes() {
+        let secret = SecretBytes::from(vec![0xAB; 32]);
+        assert_eq!(format!("{:?}", secret), "SecretBytes([REDACTED; 32])");
+    }
+
+    #[test]
+    fn eq_shared_fn() {
+        type Predicate = dyn Fn(&str) -> bool + Send + Sync;
+
+        let a = SharedFn::<Predicate>::new(Arc::new(|_: &str| true));
+        let b = SharedFn::<Predicate>::new(Arc::new(|_: &str| true));
+        assert_eq!(a, a.clone());
+        assert_ne!(a, b);
+        assert_eq!(format!("{:?}", a), "SharedFn");
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn clear_secret_bytes() {
+        let mut secret = SecretBytes::from(vec![0xAB; 32]);
+        let capacity = secret.0.capacity();
+
+        // Drop calls clear, so check the buffer that is still owned after clearing it.
+        secret.clear();
+
+        assert_eq!(secret.0.capacity(), capacity);
+        let memory = unsafe { std::slice::from_raw_parts(secret.0.as_ptr(), capacity) };
+        assert!(memory.iter().all(|b| *b == 0));
+    }
+}